@@ -0,0 +1,54 @@
+-- Rebuild clients table: SQLite cannot alter a CHECK constraint in place.
+-- Adds the 'reconnecting' status, webhook settings and reconnect supervisor settings.
+CREATE TABLE clients_new (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    description TEXT,
+
+    -- Local settings
+    local_host TEXT NOT NULL DEFAULT 'localhost',
+    local_port INTEGER NOT NULL,
+
+    -- Remote server settings
+    remote_server TEXT NOT NULL,  -- hostname/IP
+    remote_port INTEGER NOT NULL DEFAULT 0,  -- 0 = any available port
+    assigned_port INTEGER,  -- actual port assigned by server
+    secret TEXT,  -- Optional authentication secret
+
+    -- Status
+    status TEXT NOT NULL CHECK(status IN ('stopped', 'starting', 'connected', 'reconnecting', 'error')) DEFAULT 'stopped',
+    auto_start BOOLEAN NOT NULL DEFAULT 0,
+
+    -- Reconnect supervisor
+    auto_reconnect BOOLEAN NOT NULL DEFAULT 1,
+    reconnect_max_attempts INTEGER NOT NULL DEFAULT 0,  -- 0 = retry forever
+    reconnect_max_delay INTEGER NOT NULL DEFAULT 60,  -- seconds
+
+    -- Webhook notifications
+    webhook_url TEXT,
+    webhook_format TEXT NOT NULL DEFAULT 'json',
+    webhook_template TEXT,
+
+    -- Timestamps
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    last_connected_at TEXT,
+    error_message TEXT
+);
+
+INSERT INTO clients_new (
+    id, name, description, local_host, local_port, remote_server, remote_port,
+    assigned_port, secret, status, auto_start, created_at, updated_at,
+    last_connected_at, error_message
+)
+SELECT
+    id, name, description, local_host, local_port, remote_server, remote_port,
+    assigned_port, secret, status, auto_start, created_at, updated_at,
+    last_connected_at, error_message
+FROM clients;
+
+DROP TABLE clients;
+ALTER TABLE clients_new RENAME TO clients;
+
+CREATE INDEX idx_clients_status ON clients(status);
+CREATE INDEX idx_clients_remote ON clients(remote_server);
@@ -0,0 +1,4 @@
+-- Record when a session ended; NULL while the tunnel is still running
+ALTER TABLE sessions ADD COLUMN ended_at TEXT;
+
+CREATE INDEX idx_sessions_open ON sessions(ended_at) WHERE ended_at IS NULL;
@@ -0,0 +1,2 @@
+-- Per-server control port (bore's default is 7835)
+ALTER TABLE servers ADD COLUMN control_port INTEGER NOT NULL DEFAULT 7835;
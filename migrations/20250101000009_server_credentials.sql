@@ -0,0 +1,16 @@
+-- Named client credentials accepted by a bore server, alongside its shared secret
+CREATE TABLE server_credentials (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    server_id INTEGER NOT NULL REFERENCES servers(id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    secret TEXT NOT NULL,
+    enabled BOOLEAN NOT NULL DEFAULT 1,
+    port_range_start INTEGER,  -- NULL means the server's port range applies
+    port_range_end INTEGER,
+    expires_at TEXT,  -- UTC, NULL means the credential never expires
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE(server_id, name)
+);
+
+CREATE INDEX idx_server_credentials_server ON server_credentials(server_id);
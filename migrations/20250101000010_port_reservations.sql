@@ -0,0 +1,12 @@
+-- Ports on a bore server that only one credential may open tunnels on
+CREATE TABLE port_reservations (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    server_id INTEGER NOT NULL REFERENCES servers(id) ON DELETE CASCADE,
+    owner TEXT NOT NULL,  -- Name of the credential allowed to bind the port
+    port INTEGER NOT NULL,
+    description TEXT,
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE(server_id, port)
+);
+
+CREATE INDEX idx_port_reservations_server ON port_reservations(server_id);
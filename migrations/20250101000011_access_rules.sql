@@ -0,0 +1,8 @@
+-- Source address rules for public connections to tunnel ports, as comma separated CIDRs.
+-- An empty allow list admits everyone not denied; deny rules always win.
+ALTER TABLE servers ADD COLUMN allow_cidrs TEXT NOT NULL DEFAULT '';
+ALTER TABLE servers ADD COLUMN deny_cidrs TEXT NOT NULL DEFAULT '';
+
+-- Per reserved port override; NULL in both columns means the server's rules apply
+ALTER TABLE port_reservations ADD COLUMN allow_cidrs TEXT;
+ALTER TABLE port_reservations ADD COLUMN deny_cidrs TEXT;
@@ -0,0 +1,8 @@
+-- Bandwidth limits in bytes per second, 0 meaning unlimited.
+-- Upload is traffic from the forwarded service to public peers, download the reverse.
+ALTER TABLE clients ADD COLUMN upload_limit INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE clients ADD COLUMN download_limit INTEGER NOT NULL DEFAULT 0;
+
+-- Defaults applied to every tunnel opened on an embedded server
+ALTER TABLE servers ADD COLUMN upload_limit INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE servers ADD COLUMN download_limit INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,15 @@
+-- Periodic probes of a client's local target
+ALTER TABLE clients ADD COLUMN health_check_enabled BOOLEAN NOT NULL DEFAULT 0;
+ALTER TABLE clients ADD COLUMN health_check_interval INTEGER NOT NULL DEFAULT 30;  -- seconds
+ALTER TABLE clients ADD COLUMN health_check_path TEXT;  -- HTTP GET this path instead of a plain TCP connect
+
+-- Every change of a local target between healthy and down
+CREATE TABLE client_health_events (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    client_id INTEGER NOT NULL REFERENCES clients(id) ON DELETE CASCADE,
+    healthy BOOLEAN NOT NULL,
+    message TEXT,  -- Why the probe failed
+    checked_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_client_health_events_client ON client_health_events(client_id, checked_at);
@@ -0,0 +1,18 @@
+-- One row per public connection carried by a tunnel
+CREATE TABLE connections (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    tunnel_type TEXT NOT NULL CHECK(tunnel_type IN ('server', 'client')),
+    entity_id INTEGER NOT NULL,  -- servers.id or clients.id
+    port INTEGER NOT NULL,  -- Public port of the tunnel
+    peer TEXT,  -- Public peer address; unknown to clients
+    started_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    ended_at TEXT,
+    bytes_sent INTEGER NOT NULL DEFAULT 0,
+    bytes_received INTEGER NOT NULL DEFAULT 0,
+    -- NULL while the connection is open
+    close_reason TEXT CHECK(close_reason IN ('closed', 'shutdown', 'error', 'unclaimed', 'interrupted')),
+    error_message TEXT
+);
+
+CREATE INDEX idx_connections_tunnel ON connections(tunnel_type, entity_id, started_at);
+CREATE INDEX idx_connections_started ON connections(started_at);
@@ -0,0 +1,16 @@
+-- Traffic of each tunnel over time. Raw samples cover one sampling interval each and
+-- are rolled up into minute, hour and day buckets; each level is kept for a limited time.
+CREATE TABLE metric_samples (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    entity_type TEXT NOT NULL CHECK(entity_type IN ('server', 'client')),
+    entity_id INTEGER NOT NULL,
+    resolution TEXT NOT NULL CHECK(resolution IN ('raw', 'minute', 'hour', 'day')),
+    bucket_start TEXT NOT NULL,
+    bytes_sent INTEGER NOT NULL DEFAULT 0,
+    bytes_received INTEGER NOT NULL DEFAULT 0,
+    connections INTEGER NOT NULL DEFAULT 0,  -- Connections opened in the bucket
+    active_connections INTEGER NOT NULL DEFAULT 0,  -- Peak of concurrently open connections
+    UNIQUE(entity_type, entity_id, resolution, bucket_start)
+);
+
+CREATE INDEX idx_metric_samples_resolution ON metric_samples(resolution, bucket_start);
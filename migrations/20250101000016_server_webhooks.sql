@@ -0,0 +1,4 @@
+-- Webhook notifications for server lifecycle events, configured like those of clients
+ALTER TABLE servers ADD COLUMN webhook_url TEXT;
+ALTER TABLE servers ADD COLUMN webhook_format TEXT NOT NULL DEFAULT 'json';
+ALTER TABLE servers ADD COLUMN webhook_template TEXT;
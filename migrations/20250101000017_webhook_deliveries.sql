@@ -0,0 +1,22 @@
+-- Outgoing webhooks, rendered when the event happens and delivered by a background worker
+CREATE TABLE webhook_deliveries (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    entity_type TEXT NOT NULL CHECK(entity_type IN ('server', 'client')),
+    entity_id INTEGER NOT NULL,  -- servers.id or clients.id
+    event TEXT NOT NULL,  -- e.g. 'client.connected'
+    url TEXT NOT NULL,
+    content_type TEXT NOT NULL,
+    body TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending' CHECK(status IN ('pending', 'delivered', 'failed')),
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    last_attempt_at TEXT,
+    response_status INTEGER,  -- HTTP status of the last attempt
+    response_body TEXT,  -- Start of the receiver's last response
+    error TEXT,  -- Why the last attempt failed
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    delivered_at TEXT
+);
+
+CREATE INDEX idx_webhook_deliveries_due ON webhook_deliveries(status, next_attempt_at);
+CREATE INDEX idx_webhook_deliveries_entity ON webhook_deliveries(entity_type, entity_id, created_at);
@@ -0,0 +1,6 @@
+-- Optional secret used to sign webhook payloads with HMAC-SHA256
+ALTER TABLE clients ADD COLUMN webhook_secret TEXT;
+ALTER TABLE servers ADD COLUMN webhook_secret TEXT;
+
+-- Secret each queued delivery is signed with, captured when it was queued
+ALTER TABLE webhook_deliveries ADD COLUMN signing_secret TEXT;
@@ -0,0 +1,29 @@
+-- Webhook subscriptions, besides the single webhook of each server and client
+CREATE TABLE webhooks (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    url TEXT NOT NULL,
+    format TEXT NOT NULL DEFAULT 'json',
+    template TEXT,
+    headers TEXT NOT NULL DEFAULT '{}',  -- JSON object of extra request headers
+    secret TEXT,
+    events TEXT NOT NULL,  -- JSON array of event names such as 'connected'
+    enabled INTEGER NOT NULL DEFAULT 1,
+    created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Servers and clients a webhook reports on
+CREATE TABLE webhook_targets (
+    webhook_id INTEGER NOT NULL REFERENCES webhooks(id) ON DELETE CASCADE,
+    entity_type TEXT NOT NULL CHECK(entity_type IN ('server', 'client')),
+    entity_id INTEGER  -- servers.id or clients.id, NULL for all of them
+);
+
+CREATE INDEX idx_webhook_targets_webhook ON webhook_targets(webhook_id);
+CREATE INDEX idx_webhook_targets_entity ON webhook_targets(entity_type, entity_id);
+
+-- Subscription a delivery was queued for, NULL for the entity's own webhook
+ALTER TABLE webhook_deliveries ADD COLUMN webhook_id INTEGER;
+-- Extra request headers sent on each attempt, as a JSON object
+ALTER TABLE webhook_deliveries ADD COLUMN headers TEXT NOT NULL DEFAULT '{}';
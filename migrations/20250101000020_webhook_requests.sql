@@ -0,0 +1,6 @@
+-- HTTP method and authentication of webhook subscriptions
+ALTER TABLE webhooks ADD COLUMN method TEXT NOT NULL DEFAULT 'POST';
+ALTER TABLE webhooks ADD COLUMN auth TEXT;  -- JSON such as {"type": "bearer", "token": "..."}
+
+-- Method each queued delivery is sent with
+ALTER TABLE webhook_deliveries ADD COLUMN method TEXT NOT NULL DEFAULT 'POST';
@@ -0,0 +1,3 @@
+-- Role of each user: admin, operator or viewer.
+-- Users from before roles existed could do everything, so they become admins.
+ALTER TABLE users ADD COLUMN role TEXT NOT NULL DEFAULT 'admin' CHECK (role IN ('admin', 'operator', 'viewer'));
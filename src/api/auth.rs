@@ -7,20 +7,12 @@ use axum::{
 use argon2::{PasswordVerifier, PasswordHasher, password_hash::SaltString};
 use rand_core::OsRng;
 use jsonwebtoken::{encode, EncodingKey, Header};
-use serde::{Deserialize, Serialize};
 
 use crate::db;
 use crate::error::{AppError, Result};
 use crate::models::{LoginRequest, LoginResponse, TokenRefreshResponse, UserInfo, UpdateUsernameRequest, UpdateDisplayNameRequest, UpdatePasswordRequest};
 use crate::state::AppState;
-use crate::middleware::AuthUser;
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Claims {
-    pub sub: i64, // user id
-    pub username: String,
-    pub exp: usize,
-}
+use crate::middleware::{AuthUser, Claims};
 
 pub fn router() -> Router<AppState> {
     Router::new()
@@ -62,6 +54,7 @@ async fn login(
     let claims = Claims {
         sub: user.id,
         username: user.username.clone(),
+        role: user.role,
         exp: expiration,
     };
 
@@ -77,11 +70,7 @@ async fn login(
 
     Ok(Json(LoginResponse {
         token,
-        user: UserInfo {
-            id: user.id,
-            username: user.username.clone(),
-            display_name: user.display_name,
-        },
+        user: user.into(),
     }))
 }
 
@@ -98,11 +87,7 @@ async fn me(
     // Get current user from database to get latest display_name
     let current_user = db::get_user_by_id(&state.db, user.id).await?;
 
-    Ok(Json(UserInfo {
-        id: current_user.id,
-        username: current_user.username,
-        display_name: current_user.display_name,
-    }))
+    Ok(Json(current_user.into()))
 }
 
 async fn refresh_token(
@@ -118,9 +103,11 @@ async fn refresh_token(
         .expect("valid timestamp")
         .timestamp() as usize;
 
+    // Carries the current role, which the UI shows; requests check the stored one
     let claims = Claims {
         sub: current_user.id,
         username: current_user.username.clone(),
+        role: current_user.role,
         exp: expiration,
     };
 
@@ -153,11 +140,7 @@ async fn update_username(
     // Update username in database
     let updated_user = db::update_username(&state.db, user.id, &input.new_username).await?;
 
-    Ok(Json(UserInfo {
-        id: updated_user.id,
-        username: updated_user.username,
-        display_name: updated_user.display_name,
-    }))
+    Ok(Json(updated_user.into()))
 }
 
 async fn update_password(
@@ -176,17 +159,8 @@ async fn update_password(
         .verify_password(input.current_password.as_bytes(), &parsed_hash)
         .map_err(|_| AppError::Unauthorized)?;
 
-    // Validate new password
-    if input.new_password.len() < 6 {
-        return Err(AppError::BadRequest("Password must be at least 6 characters".to_string()));
-    }
-
-    // Hash new password
-    let salt = SaltString::generate(&mut OsRng);
-    let password_hash = argon2::Argon2::default()
-        .hash_password(input.new_password.as_bytes(), &salt)
-        .map_err(|_| AppError::PasswordHash)?
-        .to_string();
+    // Validate and hash new password
+    let password_hash = hash_password(&input.new_password)?;
 
     // Update password in database
     db::update_password(&state.db, user.id, &password_hash).await?;
@@ -217,9 +191,20 @@ async fn update_display_name(
         input.display_name.as_deref()
     ).await?;
 
-    Ok(Json(UserInfo {
-        id: updated_user.id,
-        username: updated_user.username,
-        display_name: updated_user.display_name,
-    }))
+    Ok(Json(updated_user.into()))
+}
+
+/// Check a new password and hash it for storage
+pub(super) fn hash_password(password: &str) -> Result<String> {
+    if password.len() < 6 {
+        return Err(AppError::BadRequest("Password must be at least 6 characters".to_string()));
+    }
+
+    let salt = SaltString::generate(&mut OsRng);
+    let password_hash = argon2::Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|_| AppError::PasswordHash)?
+        .to_string();
+
+    Ok(password_hash)
 }
@@ -1,15 +1,23 @@
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::StatusCode,
     routing::{get, post},
-    Json, Router,
+    Extension, Json, Router,
 };
 
 use crate::db;
-use crate::error::Result;
-use crate::models::{Client, ClientStatus, CreateClient, UpdateClient};
+use crate::error::{AppError, Result};
+use crate::middleware::AuthUser;
+use crate::models::{
+    Client, ClientHealthEvent, ClientStatus, CreateClient, SessionType, UpdateClient,
+    WebhookTestRequest, WebhookTestResult,
+};
 use crate::state::AppState;
-use crate::webhook::{WebhookEvent, send_webhook};
+use crate::tunnel::DrainReport;
+use super::webhooks::parse_event;
+use super::{validate_bandwidth_limits, validate_webhook_format, StopQuery, StopResponse};
+use crate::webhook::sample::sample_extra;
+use crate::webhook::{AttemptOutcome, WebhookEntity, WebhookEvent, WebhookSender, send_webhook};
 
 pub fn router() -> Router<AppState> {
     Router::new()
@@ -18,27 +26,44 @@ pub fn router() -> Router<AppState> {
         .route("/{id}/start", post(start_client))
         .route("/{id}/stop", post(stop_client))
         .route("/{id}/status", get(get_client_status))
+        .route("/{id}/connections", get(super::connections::list_client_connections))
+        .route("/{id}/logs", get(super::logs::list_client_logs))
+        .route("/{id}/health", get(get_client_health))
+        .route("/{id}/webhook/test", post(test_client_webhook))
 }
 
+/// Number of health transitions returned by `/clients/{id}/health`
+const HEALTH_HISTORY_LIMIT: i64 = 100;
+
 async fn list_clients(
     State(state): State<AppState>,
+    Extension(user): Extension<AuthUser>,
 ) -> Result<Json<Vec<Client>>> {
     let clients = db::list_clients(&state.db).await?;
-    Ok(Json(clients))
+    Ok(Json(clients.into_iter().map(|client| client.masked_for(user.role)).collect()))
 }
 
 async fn get_client(
     State(state): State<AppState>,
+    Extension(user): Extension<AuthUser>,
     Path(id): Path<i64>,
 ) -> Result<Json<Client>> {
     let client = db::get_client(&state.db, id).await?;
-    Ok(Json(client))
+    Ok(Json(client.masked_for(user.role)))
 }
 
 async fn create_client(
     State(state): State<AppState>,
-    Json(input): Json<CreateClient>,
+    Json(mut input): Json<CreateClient>,
 ) -> Result<(StatusCode, Json<Client>)> {
+    validate_reconnect_settings(Some(input.reconnect_max_attempts), Some(input.reconnect_max_delay))?;
+    validate_bandwidth_limits(Some(input.upload_limit), Some(input.download_limit))?;
+    validate_webhook_format(&input.webhook_format, input.webhook_url.as_deref())?;
+    validate_health_check(Some(input.health_check_interval), input.health_check_path.as_deref())?;
+
+    // An empty secret from the form means unsigned, as on update
+    input.webhook_secret = input.webhook_secret.filter(|secret| !secret.is_empty());
+
     let client = db::create_client(&state.db, input).await?;
     Ok((StatusCode::CREATED, Json(client)))
 }
@@ -48,6 +73,18 @@ async fn update_client(
     Path(id): Path<i64>,
     Json(input): Json<UpdateClient>,
 ) -> Result<Json<Client>> {
+    validate_reconnect_settings(input.reconnect_max_attempts, input.reconnect_max_delay)?;
+    validate_bandwidth_limits(input.upload_limit, input.download_limit)?;
+    if input.webhook_format.is_some() || input.webhook_url.is_some() {
+        // Check the format against the URL it will be used with
+        let current = db::get_client(&state.db, id).await?;
+        validate_webhook_format(
+            input.webhook_format.as_deref().unwrap_or(&current.webhook_format),
+            input.webhook_url.as_deref().or(current.webhook_url.as_deref()),
+        )?;
+    }
+    validate_health_check(input.health_check_interval, input.health_check_path.as_deref())?;
+
     let client = db::update_client(&state.db, id, input).await?;
     Ok(Json(client))
 }
@@ -65,16 +102,18 @@ async fn delete_client(
     }
 
     db::delete_client(&state.db, id).await?;
+    state.tunnel_logs.remove(SessionType::Client, id);
     Ok(StatusCode::NO_CONTENT)
 }
 
 async fn start_client(
     State(state): State<AppState>,
+    Extension(user): Extension<AuthUser>,
     Path(id): Path<i64>,
 ) -> Result<Json<Client>> {
     let mut client = db::get_client(&state.db, id).await?;
 
-    if client.status == ClientStatus::Connected {
+    if client.status == ClientStatus::Connected || client.status == ClientStatus::Reconnecting {
         return Err(crate::error::AppError::BadRequest(
             "Client is already connected".to_string()
         ));
@@ -83,6 +122,7 @@ async fn start_client(
     // Update status to starting
     db::update_client_status(&state.db, id, ClientStatus::Starting, None, None).await?;
     db::update_client_last_connected(&state.db, id).await?;
+    state.ws_broadcaster.client_status(id, ClientStatus::Starting, None, None);
 
     // Start the client using ClientManager
     match state.client_manager.start_client(client.clone()).await {
@@ -94,24 +134,19 @@ async fn start_client(
                 Some(assigned_port as i64),
                 None
             ).await?;
+            state.ws_broadcaster.client_status(id, ClientStatus::Connected, Some(assigned_port as i64), None);
             client.status = ClientStatus::Connected;
             client.assigned_port = Some(assigned_port as i64);
 
             // Send webhook for connected event
-            if let Some(webhook_url) = &client.webhook_url {
-                send_webhook(
-                    webhook_url.clone(),
-                    WebhookEvent::Connected,
-                    client.clone(),
-                    serde_json::json!({}),
-                );
-            }
-
-            Ok(Json(client))
+            send_webhook(&state.db, WebhookEvent::Connected, client.clone(), serde_json::json!({}));
+
+            Ok(Json(client.masked_for(user.role)))
         }
         Err(e) => {
             let error_msg = e.to_string();
-            db::update_client_status(&state.db, id, ClientStatus::Error, None, Some(error_msg)).await?;
+            db::update_client_status(&state.db, id, ClientStatus::Error, None, Some(error_msg.clone())).await?;
+            state.ws_broadcaster.client_status(id, ClientStatus::Error, None, Some(error_msg));
             Err(e)
         }
     }
@@ -119,12 +154,17 @@ async fn start_client(
 
 async fn stop_client(
     State(state): State<AppState>,
+    Extension(user): Extension<AuthUser>,
     Path(id): Path<i64>,
-) -> Result<Json<Client>> {
+    Query(query): Query<StopQuery>,
+) -> Result<Json<StopResponse<Client>>> {
     let mut client = db::get_client(&state.db, id).await?;
 
     if client.status == ClientStatus::Stopped {
-        return Ok(Json(client));
+        return Ok(Json(StopResponse {
+            entity: client.masked_for(user.role),
+            drain: DrainReport::default(),
+        }));
     }
 
     // Get uptime before stopping
@@ -132,11 +172,17 @@ async fn stop_client(
         .map(|status| status.uptime_seconds)
         .unwrap_or(0);
 
-    // Try to stop the client using ClientManager
-    match state.client_manager.stop_client(id).await {
-        Ok(_) => {
+    // Try to stop the client using ClientManager, draining forwarded connections
+    let mut drain = DrainReport::default();
+    match state.client_manager.stop_client(id, query.drain_timeout(&state)).await {
+        Ok(report) => {
             // Successfully stopped
-            tracing::info!("Client {} stopped successfully", id);
+            tracing::info!(
+                client_id = id,
+                "Client {} stopped successfully ({} connections drained, {} killed)",
+                id, report.drained, report.killed
+            );
+            drain = report;
         }
         Err(e) => {
             // If client not found in manager, it's already stopped (e.g., after restart)
@@ -150,29 +196,31 @@ async fn stop_client(
 
     // Update status regardless of ClientManager result
     db::update_client_status(&state.db, id, ClientStatus::Stopped, None, None).await?;
+    state.ws_broadcaster.client_status(id, ClientStatus::Stopped, None, None);
     client.status = ClientStatus::Stopped;
     client.assigned_port = None;
 
     // Send webhook for disconnected event
-    if let Some(webhook_url) = &client.webhook_url {
-        send_webhook(
-            webhook_url.clone(),
-            WebhookEvent::Disconnected,
-            client.clone(),
-            serde_json::json!({
-                "uptime_seconds": uptime_seconds
-            }),
-        );
-    }
+    send_webhook(
+        &state.db,
+        WebhookEvent::Disconnected,
+        client.clone(),
+        serde_json::json!({
+            "uptime_seconds": uptime_seconds
+        }),
+    );
 
-    Ok(Json(client))
+    Ok(Json(StopResponse {
+        entity: client.masked_for(user.role),
+        drain,
+    }))
 }
 
 async fn get_client_status(
     State(state): State<AppState>,
     Path(id): Path<i64>,
 ) -> Result<Json<serde_json::Value>> {
-    let _ = db::get_client(&state.db, id).await?;
+    let client = db::get_client(&state.db, id).await?;
 
     if let Some(status) = state.client_manager.get_status(id) {
         Ok(Json(serde_json::to_value(status).unwrap()))
@@ -181,7 +229,117 @@ async fn get_client_status(
             "id": id,
             "status": "stopped",
             "assigned_port": null,
-            "uptime_seconds": 0
+            "uptime_seconds": 0,
+            "reconnect_attempts": 0,
+            "target_healthy": null,
+            "active_connections": 0,
+            "total_connections": 0,
+            "rejected_connections": 0,
+            "bytes_sent": 0,
+            "bytes_received": 0,
+            "send_rate": 0,
+            "receive_rate": 0,
+            "upload_limit": client.upload_limit,
+            "download_limit": client.download_limit,
+            "upload_throttled": false,
+            "download_throttled": false
         })))
     }
 }
+
+/// Recent changes of the local target between healthy and down, newest first
+async fn get_client_health(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<Json<Vec<ClientHealthEvent>>> {
+    let _ = db::get_client(&state.db, id).await?;
+
+    let events = db::list_client_health_events(&state.db, id, HEALTH_HISTORY_LIMIT).await?;
+    Ok(Json(events))
+}
+
+fn validate_reconnect_settings(max_attempts: Option<i64>, max_delay: Option<i64>) -> Result<()> {
+    if let Some(attempts) = max_attempts
+        && attempts < 0
+    {
+        return Err(crate::error::AppError::BadRequest(
+            "reconnect_max_attempts must be 0 (unlimited) or greater".to_string()
+        ));
+    }
+    if let Some(delay) = max_delay
+        && delay < 1
+    {
+        return Err(crate::error::AppError::BadRequest(
+            "reconnect_max_delay must be at least 1 second".to_string()
+        ));
+    }
+
+    Ok(())
+}
+
+fn validate_health_check(interval: Option<i64>, path: Option<&str>) -> Result<()> {
+    if let Some(interval) = interval
+        && interval < 1
+    {
+        return Err(crate::error::AppError::BadRequest(
+            "health_check_interval must be at least 1 second".to_string()
+        ));
+    }
+    if let Some(path) = path
+        && !path.is_empty()
+        && !path.starts_with('/')
+    {
+        return Err(crate::error::AppError::BadRequest(
+            "health_check_path must start with '/'".to_string()
+        ));
+    }
+
+    Ok(())
+}
+
+/// Send a test event to the client's webhook right away, bypassing the delivery queue,
+/// and report how the receiver answered; the URL is masked below Admin as on the client
+async fn test_client_webhook(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthUser>,
+    Path(id): Path<i64>,
+    input: Option<Json<WebhookTestRequest>>,
+) -> Result<Json<WebhookTestResult>> {
+    let client = db::get_client(&state.db, id).await?;
+    let event = match input.and_then(|Json(input)| input.event) {
+        Some(event) => parse_event(&event)?,
+        None => WebhookEvent::Connected,
+    };
+
+    let entity = WebhookEntity::from(client);
+    let config = entity
+        .webhook_config()
+        .ok_or_else(|| AppError::BadRequest(format!("Client {} has no webhook URL", id)))?;
+
+    let sender = &state.webhook_sender;
+    let name = format!("{}.{}", entity.kind(), event.as_str());
+    let extra_data = sample_extra(&event);
+    let (body, content_type) = WebhookSender::render(event, &entity, &config, extra_data)
+        .map_err(|e| AppError::BadRequest(format!("Failed to render webhook: {}", e)))?;
+
+    let outcome = sender
+        .attempt(&config.method, &config.url, content_type, &body, &config.headers, config.secret.as_deref())
+        .await;
+    let (delivered, response_status, response_body, error) = match outcome {
+        AttemptOutcome::Delivered { status, body } => (true, Some(status), Some(body), None),
+        AttemptOutcome::Rejected { status, body, error }
+        | AttemptOutcome::Failed { status, body, error } => (false, status, body, Some(error)),
+    };
+
+    Ok(Json(WebhookTestResult {
+        url: config.url,
+        event: name,
+        content_type: content_type.to_string(),
+        body,
+        delivered,
+        response_status,
+        response_body,
+        error,
+    }
+    .masked_for(user.role)))
+}
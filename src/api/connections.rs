@@ -0,0 +1,58 @@
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+
+use crate::db;
+use crate::error::Result;
+use crate::models::{ConnectionPage, ConnectionQuery, SessionType};
+use crate::state::AppState;
+use crate::time;
+use super::Pagination;
+
+/// `GET /servers/{id}/connections`
+pub async fn list_server_connections(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Query(query): Query<ConnectionQuery>,
+) -> Result<Json<ConnectionPage>> {
+    let _ = db::get_server(&state.db, id).await?;
+    list_connections(&state, SessionType::Server, id, query).await
+}
+
+/// `GET /clients/{id}/connections`
+pub async fn list_client_connections(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Query(query): Query<ConnectionQuery>,
+) -> Result<Json<ConnectionPage>> {
+    let _ = db::get_client(&state.db, id).await?;
+    list_connections(&state, SessionType::Client, id, query).await
+}
+
+async fn list_connections(
+    state: &AppState,
+    tunnel_type: SessionType,
+    entity_id: i64,
+    mut query: ConnectionQuery,
+) -> Result<Json<ConnectionPage>> {
+    let Pagination { page, per_page } = Pagination::new(query.page, query.per_page)?;
+
+    // Compare in the format SQLite stores timestamps in
+    query.since = query.since.as_deref().map(|since| normalize_time("since", since)).transpose()?;
+    query.until = query.until.as_deref().map(|until| normalize_time("until", until)).transpose()?;
+
+    let (items, total) =
+        db::list_connections(&state.db, tunnel_type, entity_id, &query, page, per_page).await?;
+
+    Ok(Json(ConnectionPage {
+        items,
+        total,
+        page,
+        per_page,
+    }))
+}
+
+fn normalize_time(field: &str, value: &str) -> Result<String> {
+    time::parse_field(field, value).map(time::to_db)
+}
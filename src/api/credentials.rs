@@ -0,0 +1,155 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    routing::get,
+    Json, Router,
+};
+
+use crate::db;
+use crate::error::{AppError, Result};
+use crate::models::{CreateServerCredential, ServerCredential, UpdateServerCredential};
+use crate::state::AppState;
+use crate::time;
+
+/// Routes nested under `/servers/{id}/credentials`
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(list_credentials).post(create_credential))
+        .route(
+            "/{credential_id}",
+            get(get_credential).put(update_credential).delete(delete_credential),
+        )
+}
+
+async fn list_credentials(
+    State(state): State<AppState>,
+    Path(server_id): Path<i64>,
+) -> Result<Json<Vec<ServerCredential>>> {
+    let _ = db::get_server(&state.db, server_id).await?;
+
+    let credentials = db::list_server_credentials(&state.db, server_id).await?;
+    Ok(Json(credentials))
+}
+
+async fn get_credential(
+    State(state): State<AppState>,
+    Path((server_id, id)): Path<(i64, i64)>,
+) -> Result<Json<ServerCredential>> {
+    let credential = db::get_server_credential(&state.db, server_id, id).await?;
+    Ok(Json(credential))
+}
+
+async fn create_credential(
+    State(state): State<AppState>,
+    Path(server_id): Path<i64>,
+    Json(mut input): Json<CreateServerCredential>,
+) -> Result<(StatusCode, Json<ServerCredential>)> {
+    let _ = db::get_server(&state.db, server_id).await?;
+
+    validate_name_and_secret(Some(&input.name), Some(&input.secret))?;
+    validate_port_range(input.port_range_start, input.port_range_end)?;
+    if let Some(expires_at) = &input.expires_at {
+        input.expires_at = Some(normalize_expiry(expires_at)?);
+    }
+    ensure_unique_name(&state, server_id, &input.name, None).await?;
+
+    let credential = db::create_server_credential(&state.db, server_id, input).await?;
+    state.server_manager.reload_credentials(server_id).await?;
+
+    Ok((StatusCode::CREATED, Json(credential)))
+}
+
+async fn update_credential(
+    State(state): State<AppState>,
+    Path((server_id, id)): Path<(i64, i64)>,
+    Json(mut input): Json<UpdateServerCredential>,
+) -> Result<Json<ServerCredential>> {
+    let existing = db::get_server_credential(&state.db, server_id, id).await?;
+
+    validate_name_and_secret(input.name.as_deref(), input.secret.as_deref())?;
+
+    // Validate the range as it will be stored; 0 clears a bound
+    let merge = |new: Option<i64>, old: Option<i64>| match new {
+        Some(0) => None,
+        Some(port) => Some(port),
+        None => old,
+    };
+    validate_port_range(
+        merge(input.port_range_start, existing.port_range_start),
+        merge(input.port_range_end, existing.port_range_end),
+    )?;
+
+    if let Some(expires_at) = &input.expires_at
+        && !expires_at.is_empty()
+    {
+        input.expires_at = Some(normalize_expiry(expires_at)?);
+    }
+    if let Some(name) = &input.name {
+        ensure_unique_name(&state, server_id, name, Some(id)).await?;
+    }
+
+    let credential = db::update_server_credential(&state.db, server_id, id, input).await?;
+    state.server_manager.reload_credentials(server_id).await?;
+    state.server_manager.reload_reservations(server_id).await?;
+
+    Ok(Json(credential))
+}
+
+async fn delete_credential(
+    State(state): State<AppState>,
+    Path((server_id, id)): Path<(i64, i64)>,
+) -> Result<StatusCode> {
+    db::delete_server_credential(&state.db, server_id, id).await?;
+    state.server_manager.reload_credentials(server_id).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+fn validate_name_and_secret(name: Option<&str>, secret: Option<&str>) -> Result<()> {
+    if name.is_some_and(|name| name.trim().is_empty()) {
+        return Err(AppError::BadRequest("name must not be empty".to_string()));
+    }
+    if secret.is_some_and(str::is_empty) {
+        return Err(AppError::BadRequest("secret must not be empty".to_string()));
+    }
+
+    Ok(())
+}
+
+fn validate_port_range(start: Option<i64>, end: Option<i64>) -> Result<()> {
+    match (start, end) {
+        (None, None) => Ok(()),
+        (Some(start), Some(end)) if (1..=65535).contains(&start) && (start..=65535).contains(&end) => Ok(()),
+        (Some(_), Some(_)) => Err(AppError::BadRequest(
+            "port_range_start and port_range_end must satisfy 1 <= start <= end <= 65535".to_string()
+        )),
+        _ => Err(AppError::BadRequest(
+            "port_range_start and port_range_end must be set together".to_string()
+        )),
+    }
+}
+
+fn normalize_expiry(value: &str) -> Result<String> {
+    time::parse_field("expires_at", value).map(time::to_db)
+}
+
+async fn ensure_unique_name(
+    state: &AppState,
+    server_id: i64,
+    name: &str,
+    exclude_id: Option<i64>,
+) -> Result<()> {
+    let taken = db::list_server_credentials(&state.db, server_id)
+        .await?
+        .iter()
+        .any(|credential| credential.name == name && Some(credential.id) != exclude_id);
+
+    if taken {
+        return Err(AppError::BadRequest(format!(
+            "A credential named '{}' already exists on this server",
+            name
+        )));
+    }
+
+    Ok(())
+}
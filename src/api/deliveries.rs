@@ -0,0 +1,56 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    routing::{get, post},
+    Json, Router,
+};
+
+use crate::db;
+use crate::error::Result;
+use crate::models::{DeliveryPage, DeliveryQuery, WebhookDelivery};
+use crate::state::AppState;
+use crate::webhook::queue::wake_worker;
+use super::metrics::parse_entity;
+use super::Pagination;
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(list_deliveries))
+        .route("/{id}", get(get_delivery))
+        .route("/{id}/redeliver", post(redeliver))
+}
+
+async fn list_deliveries(
+    State(state): State<AppState>,
+    Query(query): Query<DeliveryQuery>,
+) -> Result<Json<DeliveryPage>> {
+    let Pagination { page, per_page } = Pagination::new(query.page, query.per_page)?;
+    let entity = query.entity.as_deref().map(parse_entity).transpose()?;
+
+    let (items, total) = db::list_webhook_deliveries(&state.db, entity, &query, page, per_page).await?;
+
+    Ok(Json(DeliveryPage {
+        items: items.into_iter().map(WebhookDelivery::masked).collect(),
+        total,
+        page,
+        per_page,
+    }))
+}
+
+async fn get_delivery(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<Json<WebhookDelivery>> {
+    let delivery = db::get_webhook_delivery(&state.db, id).await?;
+    Ok(Json(delivery.masked()))
+}
+
+/// Queue the payload of a delivery again, as a new delivery with its own attempts
+async fn redeliver(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<(StatusCode, Json<WebhookDelivery>)> {
+    let delivery = db::copy_webhook_delivery(&state.db, id).await?;
+    wake_worker();
+    Ok((StatusCode::CREATED, Json(delivery.masked())))
+}
@@ -0,0 +1,39 @@
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use serde::Deserialize;
+
+use crate::db;
+use crate::error::Result;
+use crate::logs::LogEntry;
+use crate::models::SessionType;
+use crate::state::AppState;
+
+/// Query parameters of the log endpoints
+#[derive(Debug, Deserialize)]
+pub struct LogQuery {
+    /// Only lines after this `seq`, to poll for new ones
+    #[serde(default)]
+    pub since: u64,
+}
+
+/// `GET /servers/{id}/logs`
+pub async fn list_server_logs(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Query(query): Query<LogQuery>,
+) -> Result<Json<Vec<LogEntry>>> {
+    let _ = db::get_server(&state.db, id).await?;
+    Ok(Json(state.tunnel_logs.since(SessionType::Server, id, query.since)))
+}
+
+/// `GET /clients/{id}/logs`
+pub async fn list_client_logs(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Query(query): Query<LogQuery>,
+) -> Result<Json<Vec<LogEntry>>> {
+    let _ = db::get_client(&state.db, id).await?;
+    Ok(Json(state.tunnel_logs.since(SessionType::Client, id, query.since)))
+}
@@ -0,0 +1,111 @@
+use axum::{
+    extract::{Query, State},
+    routing::get,
+    Json, Router,
+};
+use chrono::{Duration, Utc};
+
+use crate::db;
+use crate::error::{AppError, Result};
+use crate::metrics::choose_resolution;
+use crate::models::{MetricsQuery, MetricsResponse, SessionType};
+use crate::state::AppState;
+use crate::time;
+
+/// Range covered when `from` is omitted
+const DEFAULT_RANGE_SECONDS: i64 = 3600;
+
+/// Number of buckets aimed for when `step` is omitted
+const DEFAULT_POINTS: i64 = 300;
+
+/// Most buckets a single query may ask for
+const MAX_POINTS: i64 = 10_000;
+
+pub fn router() -> Router<AppState> {
+    Router::new().route("/", get(get_metrics))
+}
+
+async fn get_metrics(
+    State(state): State<AppState>,
+    Query(query): Query<MetricsQuery>,
+) -> Result<Json<MetricsResponse>> {
+    let (entity_type, entity_id) = parse_entity(&query.entity)?;
+    match entity_type {
+        SessionType::Server => {
+            let _ = db::get_server(&state.db, entity_id).await?;
+        }
+        SessionType::Client => {
+            let _ = db::get_client(&state.db, entity_id).await?;
+        }
+    }
+
+    let to = match &query.to {
+        Some(to) => time::parse_field("to", to)?,
+        None => Utc::now(),
+    };
+    let from = match &query.from {
+        Some(from) => time::parse_field("from", from)?,
+        None => to - Duration::seconds(DEFAULT_RANGE_SECONDS),
+    };
+    if from >= to {
+        return Err(AppError::BadRequest("from must be before to".to_string()));
+    }
+
+    let range = (to - from).num_seconds();
+    let step = match query.step {
+        Some(step) if step < 1 => {
+            return Err(AppError::BadRequest("step must be at least 1 second".to_string()));
+        }
+        Some(step) => step,
+        None => (range / DEFAULT_POINTS).max(1),
+    };
+
+    let (resolution, step) = choose_resolution(from, step, state.config.metrics_sample_interval);
+    if range / step > MAX_POINTS {
+        return Err(AppError::BadRequest(format!(
+            "Range would produce more than {} points, increase step",
+            MAX_POINTS
+        )));
+    }
+
+    let from = time::to_db(from);
+    let to = time::to_db(to);
+    let points = db::query_metrics(
+        &state.db,
+        entity_type.clone(),
+        entity_id,
+        resolution,
+        &from,
+        &to,
+        step,
+    )
+    .await?;
+
+    Ok(Json(MetricsResponse {
+        entity_type,
+        entity_id,
+        from,
+        to,
+        step,
+        resolution,
+        points,
+    }))
+}
+
+/// Parse `server:<id>` or `client:<id>`
+pub(super) fn parse_entity(entity: &str) -> Result<(SessionType, i64)> {
+    let invalid = || AppError::BadRequest(format!(
+        "Invalid entity '{}', expected server:<id> or client:<id>",
+        entity
+    ));
+
+    let (kind, id) = entity.split_once(':').ok_or_else(invalid)?;
+    let entity_type = match kind {
+        "server" => SessionType::Server,
+        "client" => SessionType::Client,
+        _ => return Err(invalid()),
+    };
+    let id = id.parse().map_err(|_| invalid())?;
+
+    Ok((entity_type, id))
+}
@@ -1,23 +1,41 @@
 pub mod auth;
 pub mod servers;
 pub mod clients;
+pub mod credentials;
+pub mod reservations;
+pub mod connections;
+pub mod logs;
+pub mod deliveries;
+pub mod metrics;
 pub mod status;
+pub mod webhooks;
+pub mod users;
 
 use axum::{middleware, Router};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use crate::error::{AppError, Result};
 use crate::state::AppState;
-use crate::middleware::auth_middleware;
+use crate::middleware::{auth_middleware, authorize};
+use crate::tunnel::DrainReport;
+use crate::webhook::format::{telegram_chat_id, WebhookFormat};
 
-pub fn api_router() -> Router<AppState> {
+pub fn api_router(state: AppState) -> Router<AppState> {
     // Public routes (no authentication required)
     let public_routes = Router::new()
         .nest("/auth", auth::router());
 
-    // Protected routes (authentication required)
+    // Protected routes (authentication required, role checked per route)
     let protected_routes = Router::new()
         .nest("/auth", auth::protected_router())
         .nest("/servers", servers::router())
         .nest("/clients", clients::router())
+        .nest("/metrics", metrics::router())
+        .nest("/webhooks", webhooks::router())
+        .nest("/webhook-deliveries", deliveries::router())
         .nest("/system", status::router())
+        .nest("/users", users::router())
+        .route_layer(middleware::from_fn_with_state(state, authorize))
         .route_layer(middleware::from_fn(auth_middleware));
 
     // Combine routes
@@ -25,3 +43,89 @@ pub fn api_router() -> Router<AppState> {
         .merge(public_routes)
         .merge(protected_routes)
 }
+
+/// Query parameters accepted by the stop endpoints
+#[derive(Debug, Deserialize)]
+pub struct StopQuery {
+    /// Seconds to wait for in-flight connections, overriding `DRAIN_TIMEOUT`
+    pub drain_timeout: Option<u64>,
+}
+
+impl StopQuery {
+    pub fn drain_timeout(&self, state: &AppState) -> Duration {
+        Duration::from_secs(self.drain_timeout.unwrap_or(state.config.drain_timeout))
+    }
+}
+
+/// A stopped server or client along with how its connections were wound down
+#[derive(Debug, Serialize)]
+pub struct StopResponse<T> {
+    #[serde(flatten)]
+    pub entity: T,
+    pub drain: DrainReport,
+}
+
+/// Page requested through the `page` and `per_page` query parameters of a list
+#[derive(Debug, Clone, Copy)]
+pub struct Pagination {
+    pub page: i64,
+    pub per_page: i64,
+}
+
+impl Pagination {
+    const DEFAULT_PER_PAGE: i64 = 50;
+    const MAX_PER_PAGE: i64 = 500;
+
+    /// Validate the parameters, defaulting to the first page of 50
+    pub fn new(page: Option<i64>, per_page: Option<i64>) -> Result<Self> {
+        let page = page.unwrap_or(1);
+        if page < 1 {
+            return Err(AppError::BadRequest("page must be at least 1".to_string()));
+        }
+        let per_page = per_page.unwrap_or(Self::DEFAULT_PER_PAGE);
+        if !(1..=Self::MAX_PER_PAGE).contains(&per_page) {
+            return Err(AppError::BadRequest(format!(
+                "per_page must be between 1 and {}",
+                Self::MAX_PER_PAGE
+            )));
+        }
+
+        Ok(Self { page, per_page })
+    }
+}
+
+/// Bandwidth limits are bytes per second, with 0 meaning unlimited
+fn validate_bandwidth_limits(upload_limit: Option<i64>, download_limit: Option<i64>) -> Result<()> {
+    for (field, limit) in [("upload_limit", upload_limit), ("download_limit", download_limit)] {
+        if let Some(limit) = limit
+            && limit < 0
+        {
+            return Err(AppError::BadRequest(format!(
+                "{} must be 0 (unlimited) or a positive number of bytes per second",
+                field
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Reject unknown webhook formats, and Telegram webhooks whose URL lacks the chat to post to
+fn validate_webhook_format(format: &str, url: Option<&str>) -> Result<()> {
+    let Some(format) = WebhookFormat::parse(format) else {
+        let known: Vec<_> = WebhookFormat::ALL.iter().map(|format| format.as_str()).collect();
+        return Err(AppError::BadRequest(format!(
+            "Unknown webhook_format '{}', expected one of: {}",
+            format,
+            known.join(", ")
+        )));
+    };
+
+    if format == WebhookFormat::Telegram
+        && let Some(url) = url.filter(|url| !url.is_empty())
+    {
+        telegram_chat_id(url).map_err(AppError::BadRequest)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,123 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    routing::get,
+    Json, Router,
+};
+
+use crate::db;
+use crate::error::{AppError, Result};
+use crate::models::{CreatePortReservation, PortReservation, UpdatePortReservation};
+use crate::state::AppState;
+use super::servers::validate_cidrs;
+
+/// Routes nested under `/servers/{id}/reservations`
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(list_reservations).post(create_reservation))
+        .route("/{reservation_id}", get(get_reservation).put(update_reservation).delete(delete_reservation))
+}
+
+async fn list_reservations(
+    State(state): State<AppState>,
+    Path(server_id): Path<i64>,
+) -> Result<Json<Vec<PortReservation>>> {
+    let _ = db::get_server(&state.db, server_id).await?;
+
+    let reservations = db::list_port_reservations(&state.db, server_id).await?;
+    Ok(Json(reservations))
+}
+
+async fn create_reservation(
+    State(state): State<AppState>,
+    Path(server_id): Path<i64>,
+    Json(mut input): Json<CreatePortReservation>,
+) -> Result<(StatusCode, Json<PortReservation>)> {
+    let server = db::get_server(&state.db, server_id).await?;
+
+    if input.owner.trim().is_empty() {
+        return Err(AppError::BadRequest("owner must not be empty".to_string()));
+    }
+    validate_cidrs(input.allow_cidrs.as_deref(), input.deny_cidrs.as_deref())?;
+    ensure_known_owner(&state, server_id, &input.owner).await?;
+
+    // Empty lists mean no override, like on update
+    input.allow_cidrs = input.allow_cidrs.filter(|list| !list.trim().is_empty());
+    input.deny_cidrs = input.deny_cidrs.filter(|list| !list.trim().is_empty());
+    if !(server.port_range_start..=server.port_range_end).contains(&input.port) {
+        return Err(AppError::BadRequest(format!(
+            "port must be within the server's port range {}-{}",
+            server.port_range_start, server.port_range_end
+        )));
+    }
+
+    let reservations = db::list_port_reservations(&state.db, server_id).await?;
+    if let Some(existing) = reservations.iter().find(|r| r.port == input.port) {
+        return Err(AppError::BadRequest(format!(
+            "Port {} is already reserved for '{}'",
+            existing.port, existing.owner
+        )));
+    }
+
+    let reservation = db::create_port_reservation(&state.db, server_id, input).await?;
+    state.server_manager.reload_reservations(server_id).await?;
+
+    Ok((StatusCode::CREATED, Json(reservation)))
+}
+
+async fn get_reservation(
+    State(state): State<AppState>,
+    Path((server_id, id)): Path<(i64, i64)>,
+) -> Result<Json<PortReservation>> {
+    let reservation = db::get_port_reservation(&state.db, server_id, id).await?;
+    Ok(Json(reservation))
+}
+
+async fn update_reservation(
+    State(state): State<AppState>,
+    Path((server_id, id)): Path<(i64, i64)>,
+    Json(input): Json<UpdatePortReservation>,
+) -> Result<Json<PortReservation>> {
+    let _ = db::get_port_reservation(&state.db, server_id, id).await?;
+
+    if input.owner.as_deref().is_some_and(|owner| owner.trim().is_empty()) {
+        return Err(AppError::BadRequest("owner must not be empty".to_string()));
+    }
+    validate_cidrs(input.allow_cidrs.as_deref(), input.deny_cidrs.as_deref())?;
+    if let Some(owner) = &input.owner {
+        ensure_known_owner(&state, server_id, owner).await?;
+    }
+
+    let reservation = db::update_port_reservation(&state.db, server_id, id, input).await?;
+    state.server_manager.reload_reservations(server_id).await?;
+
+    Ok(Json(reservation))
+}
+
+async fn delete_reservation(
+    State(state): State<AppState>,
+    Path((server_id, id)): Path<(i64, i64)>,
+) -> Result<StatusCode> {
+    db::delete_port_reservation(&state.db, server_id, id).await?;
+    state.server_manager.reload_reservations(server_id).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Owners are matched against the credential a client authenticated with, so a
+/// name no credential has would lock the port for everyone
+async fn ensure_known_owner(state: &AppState, server_id: i64, owner: &str) -> Result<()> {
+    let known = db::list_server_credentials(&state.db, server_id)
+        .await?
+        .iter()
+        .any(|credential| credential.name == owner);
+
+    if !known {
+        return Err(AppError::BadRequest(format!(
+            "owner '{}' is not the name of a credential on this server",
+            owner
+        )));
+    }
+
+    Ok(())
+}
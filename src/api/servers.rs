@@ -1,14 +1,19 @@
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::StatusCode,
     routing::{get, post},
-    Json, Router,
+    Extension, Json, Router,
 };
+use std::collections::HashMap;
 
 use crate::db;
 use crate::error::Result;
-use crate::models::{CreateServer, Server, ServerStatus, UpdateServer};
+use crate::middleware::AuthUser;
+use crate::models::{CreateServer, Server, ServerDetail, ServerStatus, SessionType, UpdateServer};
 use crate::state::AppState;
+use crate::tunnel::DrainReport;
+use crate::tunnel::access::parse_cidrs;
+use super::{validate_bandwidth_limits, validate_webhook_format, StopQuery, StopResponse};
 
 pub fn router() -> Router<AppState> {
     Router::new()
@@ -17,27 +22,57 @@ pub fn router() -> Router<AppState> {
         .route("/{id}/start", post(start_server))
         .route("/{id}/stop", post(stop_server))
         .route("/{id}/status", get(get_server_status))
+        .route("/{id}/connections", get(super::connections::list_server_connections))
+        .route("/{id}/logs", get(super::logs::list_server_logs))
+        .nest("/{id}/credentials", super::credentials::router())
+        .nest("/{id}/reservations", super::reservations::router())
 }
 
 async fn list_servers(
     State(state): State<AppState>,
-) -> Result<Json<Vec<Server>>> {
+    Extension(user): Extension<AuthUser>,
+) -> Result<Json<Vec<ServerDetail>>> {
     let servers = db::list_servers(&state.db).await?;
+    let mut reservations: HashMap<i64, Vec<_>> = HashMap::new();
+    for reservation in db::list_all_port_reservations(&state.db).await? {
+        reservations.entry(reservation.server_id).or_default().push(reservation);
+    }
+
+    let servers = servers
+        .into_iter()
+        .map(|server| ServerDetail {
+            reservations: reservations.remove(&server.id).unwrap_or_default(),
+            server: server.masked_for(user.role),
+        })
+        .collect();
+
     Ok(Json(servers))
 }
 
 async fn get_server(
     State(state): State<AppState>,
+    Extension(user): Extension<AuthUser>,
     Path(id): Path<i64>,
-) -> Result<Json<Server>> {
-    let server = db::get_server(&state.db, id).await?;
-    Ok(Json(server))
+) -> Result<Json<ServerDetail>> {
+    let server = db::get_server(&state.db, id).await?.masked_for(user.role);
+    let reservations = db::list_port_reservations(&state.db, id).await?;
+    Ok(Json(ServerDetail { server, reservations }))
 }
 
 async fn create_server(
     State(state): State<AppState>,
-    Json(input): Json<CreateServer>,
+    Json(mut input): Json<CreateServer>,
 ) -> Result<(StatusCode, Json<Server>)> {
+    validate_control_port(Some(input.control_port))?;
+
+    // Empty fields from the form mean no webhook, as on update
+    input.webhook_url = input.webhook_url.filter(|url| !url.is_empty());
+    input.webhook_template = input.webhook_template.filter(|template| !template.is_empty());
+    input.webhook_secret = input.webhook_secret.filter(|secret| !secret.is_empty());
+    validate_cidrs(Some(&input.allow_cidrs), Some(&input.deny_cidrs))?;
+    validate_bandwidth_limits(Some(input.upload_limit), Some(input.download_limit))?;
+    validate_webhook_format(&input.webhook_format, input.webhook_url.as_deref())?;
+
     let server = db::create_server(&state.db, input).await?;
     Ok((StatusCode::CREATED, Json(server)))
 }
@@ -47,7 +82,24 @@ async fn update_server(
     Path(id): Path<i64>,
     Json(input): Json<UpdateServer>,
 ) -> Result<Json<Server>> {
+    validate_control_port(input.control_port)?;
+    validate_cidrs(input.allow_cidrs.as_deref(), input.deny_cidrs.as_deref())?;
+    validate_bandwidth_limits(input.upload_limit, input.download_limit)?;
+    if input.webhook_format.is_some() || input.webhook_url.is_some() {
+        // Check the format against the URL it will be used with
+        let current = db::get_server(&state.db, id).await?;
+        validate_webhook_format(
+            input.webhook_format.as_deref().unwrap_or(&current.webhook_format),
+            input.webhook_url.as_deref().or(current.webhook_url.as_deref()),
+        )?;
+    }
+
+    let access_changed = input.allow_cidrs.is_some() || input.deny_cidrs.is_some();
     let server = db::update_server(&state.db, id, input).await?;
+    if access_changed {
+        state.server_manager.reload_access(id).await?;
+    }
+
     Ok(Json(server))
 }
 
@@ -64,11 +116,13 @@ async fn delete_server(
     }
 
     db::delete_server(&state.db, id).await?;
+    state.tunnel_logs.remove(SessionType::Server, id);
     Ok(StatusCode::NO_CONTENT)
 }
 
 async fn start_server(
     State(state): State<AppState>,
+    Extension(user): Extension<AuthUser>,
     Path(id): Path<i64>,
 ) -> Result<Json<Server>> {
     let mut server = db::get_server(&state.db, id).await?;
@@ -82,17 +136,20 @@ async fn start_server(
     // Update status to starting
     db::update_server_status(&state.db, id, ServerStatus::Starting, None).await?;
     db::update_server_last_started(&state.db, id).await?;
+    state.ws_broadcaster.server_status(id, ServerStatus::Starting, None, None);
 
     // Start the server using ServerManager
     match state.server_manager.start_server(server.clone()).await {
         Ok(_) => {
             db::update_server_status(&state.db, id, ServerStatus::Running, None).await?;
+            state.ws_broadcaster.server_status(id, ServerStatus::Running, Some(server.control_port), None);
             server.status = ServerStatus::Running;
-            Ok(Json(server))
+            Ok(Json(server.masked_for(user.role)))
         }
         Err(e) => {
             let error_msg = e.to_string();
-            db::update_server_status(&state.db, id, ServerStatus::Error, Some(error_msg)).await?;
+            db::update_server_status(&state.db, id, ServerStatus::Error, Some(error_msg.clone())).await?;
+            state.ws_broadcaster.server_status(id, ServerStatus::Error, None, Some(error_msg));
             Err(e)
         }
     }
@@ -100,19 +157,30 @@ async fn start_server(
 
 async fn stop_server(
     State(state): State<AppState>,
+    Extension(user): Extension<AuthUser>,
     Path(id): Path<i64>,
-) -> Result<Json<Server>> {
+    Query(query): Query<StopQuery>,
+) -> Result<Json<StopResponse<Server>>> {
     let mut server = db::get_server(&state.db, id).await?;
 
     if server.status == ServerStatus::Stopped {
-        return Ok(Json(server));
+        return Ok(Json(StopResponse {
+            entity: server.masked_for(user.role),
+            drain: DrainReport::default(),
+        }));
     }
 
-    // Try to stop the server using ServerManager
-    match state.server_manager.stop_server(id).await {
-        Ok(_) => {
+    // Try to stop the server using ServerManager, draining in-flight connections
+    let mut drain = DrainReport::default();
+    match state.server_manager.stop_server(id, query.drain_timeout(&state)).await {
+        Ok(report) => {
             // Successfully stopped
-            tracing::info!("Server {} stopped successfully", id);
+            tracing::info!(
+                server_id = id,
+                "Server {} stopped successfully ({} connections drained, {} killed)",
+                id, report.drained, report.killed
+            );
+            drain = report;
         }
         Err(e) => {
             // If server not found in manager, it's already stopped (e.g., after restart)
@@ -126,16 +194,20 @@ async fn stop_server(
 
     // Update status regardless of ServerManager result
     db::update_server_status(&state.db, id, ServerStatus::Stopped, None).await?;
+    state.ws_broadcaster.server_status(id, ServerStatus::Stopped, None, None);
     server.status = ServerStatus::Stopped;
 
-    Ok(Json(server))
+    Ok(Json(StopResponse {
+        entity: server.masked_for(user.role),
+        drain,
+    }))
 }
 
 async fn get_server_status(
     State(state): State<AppState>,
     Path(id): Path<i64>,
 ) -> Result<Json<serde_json::Value>> {
-    let _ = db::get_server(&state.db, id).await?;
+    let server = db::get_server(&state.db, id).await?;
 
     if let Some(status) = state.server_manager.get_status(id) {
         Ok(Json(serde_json::to_value(status).unwrap()))
@@ -143,8 +215,43 @@ async fn get_server_status(
         Ok(Json(serde_json::json!({
             "id": id,
             "status": "stopped",
+            "uptime_seconds": 0,
             "active_connections": 0,
-            "uptime_seconds": 0
+            "total_connections": 0,
+            "rejected_connections": 0,
+            "bytes_sent": 0,
+            "bytes_received": 0,
+            "send_rate": 0,
+            "receive_rate": 0,
+            "upload_limit": server.upload_limit,
+            "download_limit": server.download_limit,
+            "upload_throttled": false,
+            "download_throttled": false
         })))
     }
 }
+
+fn validate_control_port(control_port: Option<i64>) -> Result<()> {
+    if let Some(port) = control_port
+        && !(1..=65535).contains(&port)
+    {
+        return Err(crate::error::AppError::BadRequest(
+            "control_port must be between 1 and 65535".to_string()
+        ));
+    }
+
+    Ok(())
+}
+
+/// Reject allow/deny lists that the embedded server would fail to parse
+pub(super) fn validate_cidrs(allow: Option<&str>, deny: Option<&str>) -> Result<()> {
+    for (field, list) in [("allow_cidrs", allow), ("deny_cidrs", deny)] {
+        if let Some(list) = list {
+            parse_cidrs(list).map_err(|e| {
+                crate::error::AppError::BadRequest(format!("{}: {}", field, e))
+            })?;
+        }
+    }
+
+    Ok(())
+}
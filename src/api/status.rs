@@ -72,9 +72,13 @@ async fn system_stats(
         .fetch_one(&state.db)
         .await?;
 
-    // Get running counts (this is a placeholder - actual implementation would query the managers)
-    let servers_running = 0;
-    let clients_connected = 0;
+    let servers_running = state.server_manager.list_statuses().len();
+    let clients_connected = state
+        .client_manager
+        .list_statuses()
+        .iter()
+        .filter(|status| status.status == "connected")
+        .count();
 
     Ok(Json(StatsResponse {
         servers_running,
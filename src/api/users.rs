@@ -0,0 +1,84 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    routing::get,
+    Extension, Json, Router,
+};
+
+use crate::db;
+use crate::error::{AppError, Result};
+use crate::middleware::AuthUser;
+use crate::models::{CreateUser, UpdateUser, UserInfo};
+use crate::state::AppState;
+use super::auth::hash_password;
+
+// Admin only, see `crate::middleware::required_role`
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(list_users).post(create_user))
+        .route("/{id}", get(get_user).put(update_user).delete(delete_user))
+}
+
+async fn list_users(State(state): State<AppState>) -> Result<Json<Vec<UserInfo>>> {
+    let users = db::list_users(&state.db).await?;
+    Ok(Json(users.into_iter().map(UserInfo::from).collect()))
+}
+
+async fn get_user(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<Json<UserInfo>> {
+    let user = db::get_user_by_id(&state.db, id).await?;
+    Ok(Json(user.into()))
+}
+
+async fn create_user(
+    State(state): State<AppState>,
+    Json(input): Json<CreateUser>,
+) -> Result<(StatusCode, Json<UserInfo>)> {
+    let username = input.username.trim();
+    if username.len() < 3 {
+        return Err(AppError::BadRequest("Username must be at least 3 characters".to_string()));
+    }
+
+    let password_hash = hash_password(&input.password)?;
+    let user = db::create_user(&state.db, username, &password_hash, input.role).await?;
+
+    Ok((StatusCode::CREATED, Json(user.into())))
+}
+
+async fn update_user(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Json(input): Json<UpdateUser>,
+) -> Result<Json<UserInfo>> {
+    let mut user = db::get_user_by_id(&state.db, id).await?;
+
+    // Demoting the last admin is refused
+    if let Some(role) = input.role
+        && role != user.role
+    {
+        user = db::update_user_role(&state.db, id, role).await?;
+    }
+
+    if let Some(password) = input.password {
+        let password_hash = hash_password(&password)?;
+        user = db::update_password(&state.db, id, &password_hash).await?;
+    }
+
+    Ok(Json(user.into()))
+}
+
+async fn delete_user(
+    State(state): State<AppState>,
+    Extension(current_user): Extension<AuthUser>,
+    Path(id): Path<i64>,
+) -> Result<StatusCode> {
+    if id == current_user.id {
+        return Err(AppError::BadRequest("Cannot delete your own account".to_string()));
+    }
+
+    // Deleting the last admin is refused, as it would lock everyone out of managing users
+    db::delete_user(&state.db, id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
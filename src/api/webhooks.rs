@@ -0,0 +1,325 @@
+use axum::{
+    extract::{Path, State},
+    http::{HeaderName, HeaderValue, StatusCode},
+    routing::{get, post},
+    Extension, Json, Router,
+};
+use std::collections::BTreeMap;
+
+use crate::db;
+use crate::error::{AppError, Result};
+use crate::middleware::AuthUser;
+use crate::models::{
+    CreateWebhook, SessionType, UpdateWebhook, Webhook, WebhookAuth, WebhookPreview,
+    WebhookPreviewRequest, WebhookTarget, MASKED,
+};
+use crate::state::AppState;
+use crate::webhook::sample::{sample_client, sample_extra};
+use crate::webhook::{WebhookConfig, WebhookEntity, WebhookEvent, WebhookSender};
+use super::metrics::parse_entity;
+use super::validate_webhook_format;
+
+/// Methods webhooks may be sent with
+const METHODS: [&str; 3] = ["POST", "PUT", "PATCH"];
+
+pub fn router() -> Router<AppState> {
+    Router::new()
+        .route("/", get(list_webhooks).post(create_webhook))
+        .route("/preview", post(preview_webhook))
+        .route("/{id}", get(get_webhook).put(update_webhook).delete(delete_webhook))
+}
+
+async fn list_webhooks(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthUser>,
+) -> Result<Json<Vec<Webhook>>> {
+    let webhooks = db::list_webhooks(&state.db).await?;
+    Ok(Json(webhooks.into_iter().map(|webhook| webhook.masked_for(user.role)).collect()))
+}
+
+async fn get_webhook(
+    State(state): State<AppState>,
+    Extension(user): Extension<AuthUser>,
+    Path(id): Path<i64>,
+) -> Result<Json<Webhook>> {
+    let webhook = db::get_webhook(&state.db, id).await?;
+    Ok(Json(webhook.masked_for(user.role)))
+}
+
+async fn create_webhook(
+    State(state): State<AppState>,
+    Json(mut input): Json<CreateWebhook>,
+) -> Result<(StatusCode, Json<Webhook>)> {
+    // Empty fields from a form mean none, as on update
+    input.template = input.template.filter(|template| !template.is_empty());
+    input.secret = input.secret.filter(|secret| !secret.is_empty());
+
+    input.method = validate_method(&input.method)?;
+    validate_url(&input.url)?;
+    validate_webhook_format(&input.format, Some(&input.url))?;
+    validate_template(&input.format, input.template.as_deref())?;
+    validate_headers(&input.headers)?;
+    if let Some(auth) = &input.auth {
+        validate_auth(auth)?;
+    }
+
+    let events = match &input.events {
+        Some(events) => validate_events(events)?,
+        None => WebhookEvent::ALL.iter().map(|event| event.as_str().to_string()).collect(),
+    };
+    let targets = match &input.targets {
+        Some(targets) => parse_targets(&state, targets).await?,
+        None => vec![(SessionType::Server, None), (SessionType::Client, None)],
+    };
+
+    let webhook = db::create_webhook(&state.db, &input, &events, &targets).await?;
+    Ok((StatusCode::CREATED, Json(webhook.masked())))
+}
+
+async fn update_webhook(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Json(mut input): Json<UpdateWebhook>,
+) -> Result<Json<Webhook>> {
+    let current = db::get_webhook(&state.db, id).await?;
+
+    // Masked values sent back from the API stand for the stored ones
+    if let Some(headers) = &mut input.headers {
+        for (name, value) in headers.iter_mut() {
+            if value == MASKED
+                && let Some(stored) = current.headers.get(name)
+            {
+                value.clone_from(stored);
+            }
+        }
+    }
+    if input.secret.as_deref() == Some(MASKED) {
+        input.secret = None;
+    }
+    input.auth = input.auth.map(|auth| keep_stored_credentials(auth, current.auth.as_ref()));
+
+    input.method = input.method.as_deref().map(validate_method).transpose()?;
+    if let Some(url) = &input.url {
+        validate_url(url)?;
+    }
+    if let Some(auth) = &input.auth {
+        validate_auth(auth)?;
+    }
+    let format = input.format.as_deref().unwrap_or(&current.format);
+    validate_webhook_format(format, Some(input.url.as_deref().unwrap_or(&current.url)))?;
+    let template = match input.template.as_deref() {
+        Some(template) => Some(template).filter(|template| !template.is_empty()),
+        None => current.template.as_deref(),
+    };
+    validate_template(format, template)?;
+    if let Some(headers) = &input.headers {
+        validate_headers(headers)?;
+    }
+
+    input.events = input.events.as_deref().map(validate_events).transpose()?;
+    let targets = match &input.targets {
+        Some(targets) => Some(parse_targets(&state, targets).await?),
+        None => None,
+    };
+
+    let webhook = db::update_webhook(&state.db, id, &input, targets.as_deref()).await?;
+    Ok(Json(webhook.masked()))
+}
+
+async fn delete_webhook(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<StatusCode> {
+    db::delete_webhook(&state.db, id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Render a webhook body without sending it, against a real server or client or a
+/// sample client
+async fn preview_webhook(
+    State(state): State<AppState>,
+    Json(input): Json<WebhookPreviewRequest>,
+) -> Result<Json<WebhookPreview>> {
+    let event = parse_event(&input.event)?;
+    let template = input.template.filter(|template| !template.is_empty());
+    validate_webhook_format(&input.format, input.url.as_deref())?;
+    validate_template(&input.format, template.as_deref())?;
+
+    let entity: WebhookEntity = match input.entity.as_deref().map(parse_entity).transpose()? {
+        Some((SessionType::Server, id)) => db::get_server(&state.db, id).await?.into(),
+        Some((SessionType::Client, id)) => db::get_client(&state.db, id).await?.into(),
+        None => sample_client().into(),
+    };
+    let config = WebhookConfig {
+        webhook_id: None,
+        method: "POST".to_string(),
+        url: input.url.unwrap_or_default(),
+        format: input.format,
+        template,
+        headers: BTreeMap::new(),
+        secret: None,
+    };
+
+    let extra_data = sample_extra(&event);
+    let (body, content_type) = WebhookSender::render(event, &entity, &config, extra_data)
+        .map_err(|e| AppError::BadRequest(format!("Failed to render webhook: {}", e)))?;
+
+    Ok(Json(WebhookPreview {
+        content_type: content_type.to_string(),
+        body,
+    }))
+}
+
+/// Check a method, returning it in upper case
+fn validate_method(method: &str) -> Result<String> {
+    let method = method.to_uppercase();
+    if !METHODS.contains(&method.as_str()) {
+        return Err(AppError::BadRequest(format!(
+            "Unsupported webhook method '{}', expected one of: {}",
+            method,
+            METHODS.join(", ")
+        )));
+    }
+
+    Ok(method)
+}
+
+fn validate_auth(auth: &WebhookAuth) -> Result<()> {
+    let (field, value) = match auth {
+        WebhookAuth::None => return Ok(()),
+        WebhookAuth::Basic { username, password } => {
+            if username.is_empty() || username.contains(':') {
+                return Err(AppError::BadRequest(
+                    "Basic auth needs a username without ':'".to_string(),
+                ));
+            }
+            ("password", password)
+        }
+        WebhookAuth::Bearer { token } => ("token", token),
+    };
+
+    if value.is_empty() || HeaderValue::from_str(value).is_err() {
+        return Err(AppError::BadRequest(format!("Invalid auth {}", field)));
+    }
+
+    Ok(())
+}
+
+/// Replace masked credentials with the stored ones of the same kind of authentication
+fn keep_stored_credentials(auth: WebhookAuth, stored: Option<&WebhookAuth>) -> WebhookAuth {
+    match (auth, stored) {
+        (WebhookAuth::Basic { username, password }, Some(WebhookAuth::Basic { password: stored, .. }))
+            if password == MASKED =>
+        {
+            WebhookAuth::Basic {
+                username,
+                password: stored.clone(),
+            }
+        }
+        (WebhookAuth::Bearer { token }, Some(WebhookAuth::Bearer { token: stored })) if token == MASKED => {
+            WebhookAuth::Bearer {
+                token: stored.clone(),
+            }
+        }
+        (auth, _) => auth,
+    }
+}
+
+fn validate_url(url: &str) -> Result<()> {
+    match url::Url::parse(url) {
+        Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => Ok(()),
+        _ => Err(AppError::BadRequest(format!(
+            "Invalid webhook URL '{}', expected an http or https URL",
+            url
+        ))),
+    }
+}
+
+fn validate_template(format: &str, template: Option<&str>) -> Result<()> {
+    if format == "custom" && template.is_none() {
+        return Err(AppError::BadRequest("The custom format needs a template".to_string()));
+    }
+
+    Ok(())
+}
+
+fn validate_headers(headers: &BTreeMap<String, String>) -> Result<()> {
+    for (name, value) in headers {
+        if HeaderName::from_bytes(name.as_bytes()).is_err() {
+            return Err(AppError::BadRequest(format!("Invalid header name '{}'", name)));
+        }
+        if HeaderValue::from_str(value).is_err() {
+            return Err(AppError::BadRequest(format!("Invalid value of header '{}'", name)));
+        }
+    }
+
+    Ok(())
+}
+
+/// Check event names, dropping duplicates
+fn validate_events(events: &[String]) -> Result<Vec<String>> {
+    if events.is_empty() {
+        return Err(AppError::BadRequest("A webhook needs at least one event".to_string()));
+    }
+
+    let mut valid: Vec<String> = vec![];
+    for event in events {
+        parse_event(event)?;
+        if !valid.contains(event) {
+            valid.push(event.clone());
+        }
+    }
+
+    Ok(valid)
+}
+
+pub(super) fn parse_event(event: &str) -> Result<WebhookEvent> {
+    WebhookEvent::parse(event).ok_or_else(|| {
+        let known: Vec<_> = WebhookEvent::ALL.iter().map(|event| event.as_str()).collect();
+        AppError::BadRequest(format!(
+            "Unknown event '{}', expected one of: {}",
+            event,
+            known.join(", ")
+        ))
+    })
+}
+
+/// Parse `server:<id>`, `client:<id>`, `server:*` and `client:*`, checking the servers
+/// and clients exist
+async fn parse_targets(state: &AppState, targets: &[String]) -> Result<Vec<WebhookTarget>> {
+    let mut parsed: Vec<WebhookTarget> = vec![];
+
+    for target in targets {
+        let invalid = || AppError::BadRequest(format!(
+            "Invalid target '{}', expected server:<id>, client:<id>, server:* or client:*",
+            target
+        ));
+
+        let (kind, id) = target.split_once(':').ok_or_else(invalid)?;
+        let entity_type = match kind {
+            "server" => SessionType::Server,
+            "client" => SessionType::Client,
+            _ => return Err(invalid()),
+        };
+        let entity_id = match id {
+            "*" => None,
+            id => Some(id.parse::<i64>().map_err(|_| invalid())?),
+        };
+
+        if let Some(id) = entity_id {
+            let exists = match entity_type {
+                SessionType::Server => db::get_server(&state.db, id).await.is_ok(),
+                SessionType::Client => db::get_client(&state.db, id).await.is_ok(),
+            };
+            if !exists {
+                return Err(AppError::BadRequest(format!("Target {} does not exist", target)));
+            }
+        }
+
+        if !parsed.contains(&(entity_type.clone(), entity_id)) {
+            parsed.push((entity_type, entity_id));
+        }
+    }
+
+    Ok(parsed)
+}
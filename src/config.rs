@@ -1,4 +1,5 @@
-use crate::error::Result;
+use crate::error::{AppError, Result};
+use crate::webhook::ssrf::DestinationPolicy;
 use std::env;
 
 #[derive(Debug, Clone)]
@@ -7,6 +8,28 @@ pub struct Config {
     pub bind_addr: String,
     pub jwt_secret: String,
     pub log_level: String,
+    /// Default time in seconds a stopping tunnel waits for in-flight connections
+    pub drain_timeout: u64,
+    /// Days finished connections are kept in the connection log, 0 to keep them forever
+    pub connection_log_retention_days: u64,
+    /// Most connections kept in the connection log, 0 for no limit
+    pub connection_log_max_rows: u64,
+    /// Seconds between traffic samples of running tunnels
+    pub metrics_sample_interval: u64,
+    /// Bearer token Prometheus must present on `/metrics`; open to all when unset
+    pub metrics_token: Option<String>,
+    /// Attempts made at a webhook delivery before it is marked failed
+    pub webhook_max_attempts: u32,
+    /// Seconds before the first webhook retry, doubled on each further one
+    pub webhook_retry_base: u64,
+    /// Longest wait between two webhook attempts, in seconds
+    pub webhook_retry_max: u64,
+    /// Days finished webhook deliveries are kept, 0 to keep them forever
+    pub webhook_delivery_retention_days: u64,
+    /// Internal hosts and CIDRs webhooks may be sent to
+    pub webhook_allowed_hosts: DestinationPolicy,
+    /// Log lines kept in memory for each server and client
+    pub tunnel_log_lines: usize,
 }
 
 impl Config {
@@ -28,11 +51,79 @@ impl Config {
         let log_level = env::var("RUST_LOG")
             .unwrap_or_else(|_| "info,borui=debug".to_string());
 
+        let drain_timeout = env::var("DRAIN_TIMEOUT")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(10);
+
+        let connection_log_retention_days = env::var("CONNECTION_LOG_RETENTION_DAYS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(30);
+
+        let connection_log_max_rows = env::var("CONNECTION_LOG_MAX_ROWS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(100_000);
+
+        let metrics_sample_interval = env::var("METRICS_SAMPLE_INTERVAL")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|interval| *interval > 0)
+            .unwrap_or(10);
+
+        let metrics_token = env::var("METRICS_TOKEN")
+            .ok()
+            .filter(|token| !token.is_empty());
+
+        let webhook_max_attempts = env::var("WEBHOOK_MAX_ATTEMPTS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|attempts| *attempts > 0)
+            .unwrap_or(8);
+
+        let webhook_retry_base = env::var("WEBHOOK_RETRY_BASE")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(10);
+
+        let webhook_retry_max = env::var("WEBHOOK_RETRY_MAX")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(3600);
+
+        let webhook_delivery_retention_days = env::var("WEBHOOK_DELIVERY_RETENTION_DAYS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(30);
+
+        let webhook_allowed_hosts = DestinationPolicy::parse(
+            &env::var("WEBHOOK_ALLOWED_HOSTS").unwrap_or_default(),
+        )
+        .map_err(|e| AppError::Config(format!("WEBHOOK_ALLOWED_HOSTS: {}", e)))?;
+
+        let tunnel_log_lines = env::var("TUNNEL_LOG_LINES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|lines| *lines > 0)
+            .unwrap_or(500);
+
         Ok(Config {
             database_url,
             bind_addr,
             jwt_secret,
             log_level,
+            drain_timeout,
+            connection_log_retention_days,
+            connection_log_max_rows,
+            metrics_sample_interval,
+            metrics_token,
+            webhook_max_attempts,
+            webhook_retry_base,
+            webhook_retry_max,
+            webhook_delivery_retention_days,
+            webhook_allowed_hosts,
+            tunnel_log_lines,
         })
     }
 }
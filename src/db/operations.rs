@@ -1,14 +1,36 @@
 use crate::error::{AppError, Result};
 use crate::models::*;
-use sqlx::SqlitePool;
+use crate::tunnel::TrafficInfo;
+use crate::tunnel::connlog::Transfer;
+use crate::tunnel::stats::MetricsSample;
+use crate::webhook::queue::DeliveryAttempt;
+use sqlx::{QueryBuilder, Sqlite, SqlitePool};
 
 // User operations
-pub async fn create_user(pool: &SqlitePool, username: &str, password_hash: &str) -> Result<User> {
+pub async fn list_users(pool: &SqlitePool) -> Result<Vec<User>> {
+    let users = sqlx::query_as::<_, User>("SELECT * FROM users ORDER BY id")
+        .fetch_all(pool)
+        .await?;
+
+    Ok(users)
+}
+
+pub async fn create_user(pool: &SqlitePool, username: &str, password_hash: &str, role: Role) -> Result<User> {
+    let existing = sqlx::query("SELECT id FROM users WHERE username = ?")
+        .bind(username)
+        .fetch_optional(pool)
+        .await?;
+
+    if existing.is_some() {
+        return Err(AppError::BadRequest("Username already exists".to_string()));
+    }
+
     let user = sqlx::query_as::<_, User>(
-        "INSERT INTO users (username, password_hash) VALUES (?, ?) RETURNING *"
+        "INSERT INTO users (username, password_hash, role) VALUES (?, ?, ?) RETURNING *"
     )
     .bind(username)
     .bind(password_hash)
+    .bind(role)
     .fetch_one(pool)
     .await?;
 
@@ -82,6 +104,52 @@ pub async fn update_display_name(pool: &SqlitePool, user_id: i64, display_name:
     Ok(user)
 }
 
+// Matches users that are not the last admin. Checking within the UPDATE or DELETE keeps
+// concurrent demotions or deletions from all passing and leaving no admin.
+const NOT_LAST_ADMIN: &str = "(role != 'admin' OR (SELECT COUNT(*) FROM users WHERE role = 'admin') > 1)";
+
+/// Change a user's role, refusing to demote the last admin
+pub async fn update_user_role(pool: &SqlitePool, user_id: i64, role: Role) -> Result<User> {
+    let query = format!(
+        "UPDATE users SET role = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ? AND (? = 'admin' OR {}) RETURNING *",
+        NOT_LAST_ADMIN
+    );
+    let user = sqlx::query_as::<_, User>(&query)
+        .bind(role)
+        .bind(user_id)
+        .bind(role)
+        .fetch_optional(pool)
+        .await?;
+
+    match user {
+        Some(user) => Ok(user),
+        None => Err(last_admin_error(pool, user_id).await),
+    }
+}
+
+/// Delete a user, refusing to delete the last admin
+pub async fn delete_user(pool: &SqlitePool, user_id: i64) -> Result<()> {
+    let query = format!("DELETE FROM users WHERE id = ? AND {}", NOT_LAST_ADMIN);
+    let result = sqlx::query(&query)
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(last_admin_error(pool, user_id).await);
+    }
+
+    Ok(())
+}
+
+// Why a user was left alone: it does not exist, or it is the last admin
+async fn last_admin_error(pool: &SqlitePool, user_id: i64) -> AppError {
+    match get_user_by_id(pool, user_id).await {
+        Ok(_) => AppError::BadRequest("At least one admin must remain".to_string()),
+        Err(e) => e,
+    }
+}
+
 // Server operations
 pub async fn list_servers(pool: &SqlitePool) -> Result<Vec<Server>> {
     let servers = sqlx::query_as::<_, Server>("SELECT * FROM servers ORDER BY id DESC")
@@ -104,8 +172,8 @@ pub async fn get_server(pool: &SqlitePool, id: i64) -> Result<Server> {
 pub async fn create_server(pool: &SqlitePool, input: CreateServer) -> Result<Server> {
     let server = sqlx::query_as::<_, Server>(
         r#"
-        INSERT INTO servers (name, description, bind_addr, bind_tunnels, port_range_start, port_range_end, secret, auto_start)
-        VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+        INSERT INTO servers (name, description, bind_addr, bind_tunnels, control_port, port_range_start, port_range_end, secret, allow_cidrs, deny_cidrs, upload_limit, download_limit, webhook_url, webhook_format, webhook_template, webhook_secret, auto_start)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
         RETURNING *
         "#
     )
@@ -113,9 +181,18 @@ pub async fn create_server(pool: &SqlitePool, input: CreateServer) -> Result<Ser
     .bind(&input.description)
     .bind(&input.bind_addr)
     .bind(&input.bind_tunnels)
+    .bind(input.control_port)
     .bind(input.port_range_start)
     .bind(input.port_range_end)
     .bind(&input.secret)
+    .bind(&input.allow_cidrs)
+    .bind(&input.deny_cidrs)
+    .bind(input.upload_limit)
+    .bind(input.download_limit)
+    .bind(&input.webhook_url)
+    .bind(&input.webhook_format)
+    .bind(&input.webhook_template)
+    .bind(&input.webhook_secret)
     .bind(input.auto_start)
     .fetch_one(pool)
     .await?;
@@ -147,6 +224,9 @@ pub async fn update_server(pool: &SqlitePool, id: i64, input: UpdateServer) -> R
         query.push_str(", bind_tunnels = ?");
         params.push(tunnels.clone());
     }
+    if let Some(port) = input.control_port {
+        query.push_str(&format!(", control_port = {}", port));
+    }
     if let Some(start) = input.port_range_start {
         query.push_str(&format!(", port_range_start = {}", start));
     }
@@ -157,6 +237,36 @@ pub async fn update_server(pool: &SqlitePool, id: i64, input: UpdateServer) -> R
         query.push_str(", secret = ?");
         params.push(secret.clone());
     }
+    if let Some(allow) = &input.allow_cidrs {
+        query.push_str(", allow_cidrs = ?");
+        params.push(allow.clone());
+    }
+    if let Some(deny) = &input.deny_cidrs {
+        query.push_str(", deny_cidrs = ?");
+        params.push(deny.clone());
+    }
+    if let Some(limit) = input.upload_limit {
+        query.push_str(&format!(", upload_limit = {}", limit));
+    }
+    if let Some(limit) = input.download_limit {
+        query.push_str(&format!(", download_limit = {}", limit));
+    }
+    if let Some(webhook_url) = &input.webhook_url {
+        query.push_str(", webhook_url = NULLIF(?, '')");
+        params.push(webhook_url.clone());
+    }
+    if let Some(webhook_format) = &input.webhook_format {
+        query.push_str(", webhook_format = ?");
+        params.push(webhook_format.clone());
+    }
+    if let Some(webhook_template) = &input.webhook_template {
+        query.push_str(", webhook_template = NULLIF(?, '')");
+        params.push(webhook_template.clone());
+    }
+    if let Some(webhook_secret) = &input.webhook_secret {
+        query.push_str(", webhook_secret = NULLIF(?, '')");
+        params.push(webhook_secret.clone());
+    }
     if let Some(auto) = input.auto_start {
         query.push_str(&format!(", auto_start = {}", if auto { 1 } else { 0 }));
     }
@@ -184,7 +294,7 @@ pub async fn delete_server(pool: &SqlitePool, id: i64) -> Result<()> {
         return Err(AppError::NotFound(format!("Server {} not found", id)));
     }
 
-    Ok(())
+    delete_webhook_targets(pool, SessionType::Server, id).await
 }
 
 pub async fn update_server_status(
@@ -221,6 +331,265 @@ pub async fn update_server_last_started(pool: &SqlitePool, id: i64) -> Result<()
     Ok(())
 }
 
+// Server credential operations
+pub async fn list_server_credentials(pool: &SqlitePool, server_id: i64) -> Result<Vec<ServerCredential>> {
+    let credentials = sqlx::query_as::<_, ServerCredential>(
+        "SELECT * FROM server_credentials WHERE server_id = ? ORDER BY name"
+    )
+    .bind(server_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(credentials)
+}
+
+pub async fn get_server_credential(pool: &SqlitePool, server_id: i64, id: i64) -> Result<ServerCredential> {
+    let credential = sqlx::query_as::<_, ServerCredential>(
+        "SELECT * FROM server_credentials WHERE id = ? AND server_id = ?"
+    )
+    .bind(id)
+    .bind(server_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| AppError::NotFound(format!("Credential {} not found", id)))?;
+
+    Ok(credential)
+}
+
+pub async fn create_server_credential(
+    pool: &SqlitePool,
+    server_id: i64,
+    input: CreateServerCredential,
+) -> Result<ServerCredential> {
+    let credential = sqlx::query_as::<_, ServerCredential>(
+        r#"
+        INSERT INTO server_credentials (server_id, name, secret, enabled, port_range_start, port_range_end, expires_at)
+        VALUES (?, ?, ?, ?, ?, ?, ?)
+        RETURNING *
+        "#
+    )
+    .bind(server_id)
+    .bind(&input.name)
+    .bind(&input.secret)
+    .bind(input.enabled)
+    .bind(input.port_range_start)
+    .bind(input.port_range_end)
+    .bind(&input.expires_at)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(credential)
+}
+
+pub async fn update_server_credential(
+    pool: &SqlitePool,
+    server_id: i64,
+    id: i64,
+    input: UpdateServerCredential,
+) -> Result<ServerCredential> {
+    // First check if the credential exists on this server
+    let existing = get_server_credential(pool, server_id, id).await?;
+
+    // Build dynamic UPDATE query based on provided fields
+    let mut query = String::from("UPDATE server_credentials SET updated_at = CURRENT_TIMESTAMP");
+    let mut params: Vec<String> = vec![];
+
+    if let Some(name) = &input.name {
+        query.push_str(", name = ?");
+        params.push(name.clone());
+    }
+    if let Some(secret) = &input.secret {
+        query.push_str(", secret = ?");
+        params.push(secret.clone());
+    }
+    if let Some(enabled) = input.enabled {
+        query.push_str(&format!(", enabled = {}", if enabled { 1 } else { 0 }));
+    }
+    // A port of 0 or an empty expiry clears the restriction
+    if let Some(start) = input.port_range_start {
+        query.push_str(&format!(", port_range_start = NULLIF({}, 0)", start));
+    }
+    if let Some(end) = input.port_range_end {
+        query.push_str(&format!(", port_range_end = NULLIF({}, 0)", end));
+    }
+    if let Some(expires_at) = &input.expires_at {
+        query.push_str(", expires_at = NULLIF(?, '')");
+        params.push(expires_at.clone());
+    }
+
+    query.push_str(" WHERE id = ?");
+
+    let mut tx = pool.begin().await?;
+
+    let mut q = sqlx::query(&query);
+    for param in &params {
+        q = q.bind(param);
+    }
+    q = q.bind(id);
+
+    q.execute(&mut *tx).await?;
+
+    // Reservations name their owner, so they follow a renamed credential
+    if let Some(name) = input.name.as_ref().filter(|name| **name != existing.name) {
+        sqlx::query("UPDATE port_reservations SET owner = ? WHERE server_id = ? AND owner = ?")
+            .bind(name)
+            .bind(server_id)
+            .bind(&existing.name)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    tx.commit().await?;
+
+    get_server_credential(pool, server_id, id).await
+}
+
+/// Delete a credential, refusing while ports are reserved for it, since nobody could
+/// open tunnels on them anymore
+pub async fn delete_server_credential(pool: &SqlitePool, server_id: i64, id: i64) -> Result<()> {
+    let result = sqlx::query(
+        r#"
+        DELETE FROM server_credentials
+        WHERE id = ? AND server_id = ?
+          AND NOT EXISTS (
+            SELECT 1 FROM port_reservations
+            WHERE port_reservations.server_id = server_credentials.server_id
+              AND port_reservations.owner = server_credentials.name
+          )
+        "#
+    )
+    .bind(id)
+    .bind(server_id)
+    .execute(pool)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        let credential = get_server_credential(pool, server_id, id).await?;
+        return Err(AppError::BadRequest(format!(
+            "Credential '{}' still owns reserved ports; delete or reassign its reservations first",
+            credential.name
+        )));
+    }
+
+    Ok(())
+}
+
+// Port reservation operations
+pub async fn list_port_reservations(pool: &SqlitePool, server_id: i64) -> Result<Vec<PortReservation>> {
+    let reservations = sqlx::query_as::<_, PortReservation>(
+        "SELECT * FROM port_reservations WHERE server_id = ? ORDER BY port"
+    )
+    .bind(server_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(reservations)
+}
+
+pub async fn list_all_port_reservations(pool: &SqlitePool) -> Result<Vec<PortReservation>> {
+    let reservations = sqlx::query_as::<_, PortReservation>(
+        "SELECT * FROM port_reservations ORDER BY server_id, port"
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(reservations)
+}
+
+pub async fn create_port_reservation(
+    pool: &SqlitePool,
+    server_id: i64,
+    input: CreatePortReservation,
+) -> Result<PortReservation> {
+    let reservation = sqlx::query_as::<_, PortReservation>(
+        r#"
+        INSERT INTO port_reservations (server_id, owner, port, description, allow_cidrs, deny_cidrs)
+        VALUES (?, ?, ?, ?, ?, ?)
+        RETURNING *
+        "#
+    )
+    .bind(server_id)
+    .bind(&input.owner)
+    .bind(input.port)
+    .bind(&input.description)
+    .bind(&input.allow_cidrs)
+    .bind(&input.deny_cidrs)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(reservation)
+}
+
+pub async fn get_port_reservation(pool: &SqlitePool, server_id: i64, id: i64) -> Result<PortReservation> {
+    let reservation = sqlx::query_as::<_, PortReservation>(
+        "SELECT * FROM port_reservations WHERE id = ? AND server_id = ?"
+    )
+    .bind(id)
+    .bind(server_id)
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| AppError::NotFound(format!("Reservation {} not found", id)))?;
+
+    Ok(reservation)
+}
+
+pub async fn update_port_reservation(
+    pool: &SqlitePool,
+    server_id: i64,
+    id: i64,
+    input: UpdatePortReservation,
+) -> Result<PortReservation> {
+    // First check if the reservation exists on this server
+    let _ = get_port_reservation(pool, server_id, id).await?;
+
+    let mut query = String::from("UPDATE port_reservations SET id = id");
+    let mut params: Vec<String> = vec![];
+
+    if let Some(owner) = &input.owner {
+        query.push_str(", owner = ?");
+        params.push(owner.clone());
+    }
+    if let Some(desc) = &input.description {
+        query.push_str(", description = ?");
+        params.push(desc.clone());
+    }
+    // An empty list clears the override so the server's rules apply again
+    if let Some(allow) = &input.allow_cidrs {
+        query.push_str(", allow_cidrs = NULLIF(?, '')");
+        params.push(allow.clone());
+    }
+    if let Some(deny) = &input.deny_cidrs {
+        query.push_str(", deny_cidrs = NULLIF(?, '')");
+        params.push(deny.clone());
+    }
+
+    query.push_str(" WHERE id = ?");
+
+    let mut q = sqlx::query(&query);
+    for param in &params {
+        q = q.bind(param);
+    }
+    q = q.bind(id);
+
+    q.execute(pool).await?;
+
+    get_port_reservation(pool, server_id, id).await
+}
+
+pub async fn delete_port_reservation(pool: &SqlitePool, server_id: i64, id: i64) -> Result<()> {
+    let result = sqlx::query("DELETE FROM port_reservations WHERE id = ? AND server_id = ?")
+        .bind(id)
+        .bind(server_id)
+        .execute(pool)
+        .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound(format!("Reservation {} not found", id)));
+    }
+
+    Ok(())
+}
+
 // Client operations
 pub async fn list_clients(pool: &SqlitePool) -> Result<Vec<Client>> {
     let clients = sqlx::query_as::<_, Client>("SELECT * FROM clients ORDER BY id DESC")
@@ -243,8 +612,8 @@ pub async fn get_client(pool: &SqlitePool, id: i64) -> Result<Client> {
 pub async fn create_client(pool: &SqlitePool, input: CreateClient) -> Result<Client> {
     let client = sqlx::query_as::<_, Client>(
         r#"
-        INSERT INTO clients (name, description, local_host, local_port, remote_server, remote_port, secret, auto_start, webhook_url, webhook_format, webhook_template)
-        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        INSERT INTO clients (name, description, local_host, local_port, remote_server, remote_port, secret, auto_start, auto_reconnect, reconnect_max_attempts, reconnect_max_delay, upload_limit, download_limit, webhook_url, webhook_format, webhook_template, webhook_secret, health_check_enabled, health_check_interval, health_check_path)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
         RETURNING *
         "#
     )
@@ -256,9 +625,18 @@ pub async fn create_client(pool: &SqlitePool, input: CreateClient) -> Result<Cli
     .bind(input.remote_port)
     .bind(&input.secret)
     .bind(input.auto_start)
+    .bind(input.auto_reconnect)
+    .bind(input.reconnect_max_attempts)
+    .bind(input.reconnect_max_delay)
+    .bind(input.upload_limit)
+    .bind(input.download_limit)
     .bind(&input.webhook_url)
     .bind(&input.webhook_format)
     .bind(&input.webhook_template)
+    .bind(&input.webhook_secret)
+    .bind(input.health_check_enabled)
+    .bind(input.health_check_interval)
+    .bind(&input.health_check_path)
     .fetch_one(pool)
     .await?;
 
@@ -302,6 +680,21 @@ pub async fn update_client(pool: &SqlitePool, id: i64, input: UpdateClient) -> R
     if let Some(auto) = input.auto_start {
         query.push_str(&format!(", auto_start = {}", if auto { 1 } else { 0 }));
     }
+    if let Some(reconnect) = input.auto_reconnect {
+        query.push_str(&format!(", auto_reconnect = {}", if reconnect { 1 } else { 0 }));
+    }
+    if let Some(attempts) = input.reconnect_max_attempts {
+        query.push_str(&format!(", reconnect_max_attempts = {}", attempts));
+    }
+    if let Some(delay) = input.reconnect_max_delay {
+        query.push_str(&format!(", reconnect_max_delay = {}", delay));
+    }
+    if let Some(limit) = input.upload_limit {
+        query.push_str(&format!(", upload_limit = {}", limit));
+    }
+    if let Some(limit) = input.download_limit {
+        query.push_str(&format!(", download_limit = {}", limit));
+    }
     if let Some(webhook_url) = &input.webhook_url {
         query.push_str(", webhook_url = ?");
         params.push(webhook_url.clone());
@@ -314,6 +707,20 @@ pub async fn update_client(pool: &SqlitePool, id: i64, input: UpdateClient) -> R
         query.push_str(", webhook_template = ?");
         params.push(webhook_template.clone());
     }
+    if let Some(webhook_secret) = &input.webhook_secret {
+        query.push_str(", webhook_secret = NULLIF(?, '')");
+        params.push(webhook_secret.clone());
+    }
+    if let Some(enabled) = input.health_check_enabled {
+        query.push_str(&format!(", health_check_enabled = {}", if enabled { 1 } else { 0 }));
+    }
+    if let Some(interval) = input.health_check_interval {
+        query.push_str(&format!(", health_check_interval = {}", interval));
+    }
+    if let Some(path) = &input.health_check_path {
+        query.push_str(", health_check_path = NULLIF(?, '')");
+        params.push(path.clone());
+    }
 
     query.push_str(" WHERE id = ?");
 
@@ -338,7 +745,7 @@ pub async fn delete_client(pool: &SqlitePool, id: i64) -> Result<()> {
         return Err(AppError::NotFound(format!("Client {} not found", id)));
     }
 
-    Ok(())
+    delete_webhook_targets(pool, SessionType::Client, id).await
 }
 
 pub async fn update_client_status(
@@ -352,6 +759,7 @@ pub async fn update_client_status(
         ClientStatus::Stopped => "stopped",
         ClientStatus::Starting => "starting",
         ClientStatus::Connected => "connected",
+        ClientStatus::Reconnecting => "reconnecting",
         ClientStatus::Error => "error",
     };
 
@@ -376,3 +784,764 @@ pub async fn update_client_last_connected(pool: &SqlitePool, id: i64) -> Result<
 
     Ok(())
 }
+
+// Client health operations
+pub async fn list_client_health_events(pool: &SqlitePool, client_id: i64, limit: i64) -> Result<Vec<ClientHealthEvent>> {
+    let events = sqlx::query_as::<_, ClientHealthEvent>(
+        "SELECT * FROM client_health_events WHERE client_id = ? ORDER BY id DESC LIMIT ?"
+    )
+    .bind(client_id)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(events)
+}
+
+pub async fn create_client_health_event(
+    pool: &SqlitePool,
+    client_id: i64,
+    healthy: bool,
+    message: Option<&str>,
+) -> Result<ClientHealthEvent> {
+    let event = sqlx::query_as::<_, ClientHealthEvent>(
+        "INSERT INTO client_health_events (client_id, healthy, message) VALUES (?, ?, ?) RETURNING *"
+    )
+    .bind(client_id)
+    .bind(healthy)
+    .bind(message)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(event)
+}
+
+// Session operations
+pub async fn create_session(pool: &SqlitePool, session_type: SessionType, entity_id: i64) -> Result<Session> {
+    let session = sqlx::query_as::<_, Session>(
+        "INSERT INTO sessions (session_type, entity_id) VALUES (?, ?) RETURNING *"
+    )
+    .bind(session_type)
+    .bind(entity_id)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(session)
+}
+
+pub async fn update_session_heartbeat(pool: &SqlitePool, id: i64, traffic: &TrafficInfo) -> Result<()> {
+    sqlx::query(
+        r#"
+        UPDATE sessions
+        SET last_heartbeat = CURRENT_TIMESTAMP, connection_count = ?, bytes_sent = ?, bytes_received = ?
+        WHERE id = ?
+        "#
+    )
+    .bind(traffic.total_connections as i64)
+    .bind(traffic.bytes_sent as i64)
+    .bind(traffic.bytes_received as i64)
+    .bind(id)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn close_session(pool: &SqlitePool, id: i64, traffic: &TrafficInfo) -> Result<()> {
+    sqlx::query(
+        r#"
+        UPDATE sessions
+        SET last_heartbeat = CURRENT_TIMESTAMP, ended_at = CURRENT_TIMESTAMP,
+            connection_count = ?, bytes_sent = ?, bytes_received = ?
+        WHERE id = ?
+        "#
+    )
+    .bind(traffic.total_connections as i64)
+    .bind(traffic.bytes_sent as i64)
+    .bind(traffic.bytes_received as i64)
+    .bind(id)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Close sessions left open by a previous run, using their last heartbeat as end time
+pub async fn close_stale_sessions(pool: &SqlitePool) -> Result<u64> {
+    let result = sqlx::query("UPDATE sessions SET ended_at = last_heartbeat WHERE ended_at IS NULL")
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected())
+}
+
+// Connection log operations
+pub async fn create_connection(
+    pool: &SqlitePool,
+    tunnel_type: SessionType,
+    entity_id: i64,
+    port: u16,
+    peer: Option<&str>,
+) -> Result<i64> {
+    let result = sqlx::query(
+        "INSERT INTO connections (tunnel_type, entity_id, port, peer) VALUES (?, ?, ?, ?)"
+    )
+    .bind(tunnel_type)
+    .bind(entity_id)
+    .bind(port as i64)
+    .bind(peer)
+    .execute(pool)
+    .await?;
+
+    Ok(result.last_insert_rowid())
+}
+
+pub async fn close_connection(pool: &SqlitePool, id: i64, transfer: &Transfer) -> Result<()> {
+    sqlx::query(
+        r#"
+        UPDATE connections
+        SET ended_at = CURRENT_TIMESTAMP, bytes_sent = ?, bytes_received = ?, close_reason = ?, error_message = ?
+        WHERE id = ?
+        "#
+    )
+    .bind(transfer.bytes_sent as i64)
+    .bind(transfer.bytes_received as i64)
+    .bind(transfer.reason)
+    .bind(&transfer.error)
+    .bind(id)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// One page of a tunnel's connections, newest first, along with the number of matches.
+/// `since` and `until` must already be in SQLite's timestamp format.
+pub async fn list_connections(
+    pool: &SqlitePool,
+    tunnel_type: SessionType,
+    entity_id: i64,
+    filter: &ConnectionQuery,
+    page: i64,
+    per_page: i64,
+) -> Result<(Vec<TunnelConnection>, i64)> {
+    let mut count = QueryBuilder::new("SELECT COUNT(*) FROM connections WHERE ");
+    push_connection_filters(&mut count, &tunnel_type, entity_id, filter);
+    let total = count.build_query_scalar::<i64>().fetch_one(pool).await?;
+
+    let mut query = QueryBuilder::new("SELECT * FROM connections WHERE ");
+    push_connection_filters(&mut query, &tunnel_type, entity_id, filter);
+    query
+        .push(" ORDER BY id DESC LIMIT ")
+        .push_bind(per_page)
+        .push(" OFFSET ")
+        .push_bind((page - 1) * per_page);
+    let items = query.build_query_as::<TunnelConnection>().fetch_all(pool).await?;
+
+    Ok((items, total))
+}
+
+/// WHERE clause shared by the count and the page of `list_connections`
+fn push_connection_filters(
+    query: &mut QueryBuilder<'_, Sqlite>,
+    tunnel_type: &SessionType,
+    entity_id: i64,
+    filter: &ConnectionQuery,
+) {
+    query
+        .push("tunnel_type = ")
+        .push_bind(tunnel_type.clone())
+        .push(" AND entity_id = ")
+        .push_bind(entity_id);
+
+    if let Some(peer) = &filter.peer {
+        query.push(" AND peer LIKE ").push_bind(peer.clone()).push(" || '%'");
+    }
+    if let Some(port) = filter.port {
+        query.push(" AND port = ").push_bind(port);
+    }
+    if let Some(reason) = filter.close_reason {
+        query.push(" AND close_reason = ").push_bind(reason.as_str().to_string());
+    }
+    match filter.open {
+        Some(true) => {
+            query.push(" AND close_reason IS NULL");
+        }
+        Some(false) => {
+            query.push(" AND close_reason IS NOT NULL");
+        }
+        None => {}
+    }
+    if let Some(since) = &filter.since {
+        query.push(" AND started_at >= ").push_bind(since.clone());
+    }
+    if let Some(until) = &filter.until {
+        query.push(" AND started_at < ").push_bind(until.clone());
+    }
+}
+
+/// Mark connections left open by a previous run as interrupted; their end time is unknown
+pub async fn close_stale_connections(pool: &SqlitePool) -> Result<u64> {
+    let result = sqlx::query("UPDATE connections SET close_reason = 'interrupted' WHERE close_reason IS NULL")
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// Delete finished connections older than `retention_days`, then the oldest ones beyond
+/// `max_rows`. Either limit is skipped when 0.
+pub async fn prune_connections(pool: &SqlitePool, retention_days: u64, max_rows: u64) -> Result<u64> {
+    let mut deleted = 0;
+
+    if retention_days > 0 {
+        deleted += sqlx::query(
+            "DELETE FROM connections WHERE close_reason IS NOT NULL AND started_at < datetime('now', ?)"
+        )
+        .bind(format!("-{} days", retention_days))
+        .execute(pool)
+        .await?
+        .rows_affected();
+    }
+
+    if max_rows > 0 {
+        deleted += sqlx::query(
+            "DELETE FROM connections WHERE id <= (SELECT id FROM connections ORDER BY id DESC LIMIT 1 OFFSET ?)"
+        )
+        .bind(max_rows as i64)
+        .execute(pool)
+        .await?
+        .rows_affected();
+    }
+
+    Ok(deleted)
+}
+
+// Metrics operations
+/// Store one raw sample per tunnel, stamped with the current time
+pub async fn insert_metric_samples(
+    pool: &SqlitePool,
+    entity_type: SessionType,
+    samples: &[(i64, MetricsSample)],
+) -> Result<()> {
+    for (entity_id, sample) in samples {
+        sqlx::query(
+            r#"
+            INSERT INTO metric_samples (entity_type, entity_id, resolution, bucket_start, bytes_sent, bytes_received, connections, active_connections)
+            VALUES (?, ?, 'raw', CURRENT_TIMESTAMP, ?, ?, ?, ?)
+            ON CONFLICT(entity_type, entity_id, resolution, bucket_start) DO UPDATE SET
+                bytes_sent = bytes_sent + excluded.bytes_sent,
+                bytes_received = bytes_received + excluded.bytes_received,
+                connections = connections + excluded.connections,
+                active_connections = MAX(active_connections, excluded.active_connections)
+            "#
+        )
+        .bind(entity_type.clone())
+        .bind(entity_id)
+        .bind(sample.bytes_sent as i64)
+        .bind(sample.bytes_received as i64)
+        .bind(sample.connections as i64)
+        .bind(sample.active_connections as i64)
+        .execute(pool)
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Aggregate `source` samples into `target` buckets, where `bucket_format` is the
+/// strftime pattern truncating a timestamp to the start of its target bucket.
+/// Buckets from the latest one already rolled up onwards are recomputed, so a
+/// partially filled bucket is completed on the next run.
+pub async fn roll_up_metrics(
+    pool: &SqlitePool,
+    source: Resolution,
+    target: Resolution,
+    bucket_format: &str,
+) -> Result<u64> {
+    let result = sqlx::query(
+        r#"
+        INSERT INTO metric_samples (entity_type, entity_id, resolution, bucket_start, bytes_sent, bytes_received, connections, active_connections)
+        SELECT entity_type, entity_id, ?, strftime(?, bucket_start) AS bucket,
+               SUM(bytes_sent), SUM(bytes_received), SUM(connections), MAX(active_connections)
+        FROM metric_samples
+        WHERE resolution = ?
+          AND bucket_start >= (SELECT COALESCE(MAX(bucket_start), '') FROM metric_samples WHERE resolution = ?)
+        GROUP BY entity_type, entity_id, bucket
+        ON CONFLICT(entity_type, entity_id, resolution, bucket_start) DO UPDATE SET
+            bytes_sent = excluded.bytes_sent,
+            bytes_received = excluded.bytes_received,
+            connections = excluded.connections,
+            active_connections = excluded.active_connections
+        "#
+    )
+    .bind(target)
+    .bind(bucket_format)
+    .bind(source)
+    .bind(target)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// Delete samples of one resolution older than `keep_seconds`
+pub async fn prune_metric_samples(pool: &SqlitePool, resolution: Resolution, keep_seconds: i64) -> Result<u64> {
+    let result = sqlx::query(
+        "DELETE FROM metric_samples WHERE resolution = ? AND bucket_start < datetime('now', ?)"
+    )
+    .bind(resolution)
+    .bind(format!("-{} seconds", keep_seconds))
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// Traffic of one tunnel in `step` second buckets aligned to the Unix epoch.
+/// `from` and `to` must be in SQLite's timestamp format; buckets without samples are omitted.
+pub async fn query_metrics(
+    pool: &SqlitePool,
+    entity_type: SessionType,
+    entity_id: i64,
+    resolution: Resolution,
+    from: &str,
+    to: &str,
+    step: i64,
+) -> Result<Vec<MetricPoint>> {
+    let query = format!(
+        r#"
+        SELECT datetime((CAST(strftime('%s', bucket_start) AS INTEGER) / {step}) * {step}, 'unixepoch') AS time,
+               SUM(bytes_sent) AS bytes_sent, SUM(bytes_received) AS bytes_received,
+               SUM(connections) AS connections, MAX(active_connections) AS active_connections
+        FROM metric_samples
+        WHERE entity_type = ? AND entity_id = ? AND resolution = ? AND bucket_start >= ? AND bucket_start < ?
+        GROUP BY time
+        ORDER BY time
+        "#,
+        step = step
+    );
+
+    let points = sqlx::query_as::<_, MetricPoint>(&query)
+        .bind(entity_type)
+        .bind(entity_id)
+        .bind(resolution)
+        .bind(from)
+        .bind(to)
+        .fetch_all(pool)
+        .await?;
+
+    Ok(points)
+}
+
+// Webhook subscription operations
+pub async fn list_webhooks(pool: &SqlitePool) -> Result<Vec<Webhook>> {
+    let mut webhooks = sqlx::query_as::<_, Webhook>("SELECT * FROM webhooks ORDER BY id")
+        .fetch_all(pool)
+        .await?;
+
+    load_webhook_targets(pool, &mut webhooks).await?;
+    Ok(webhooks)
+}
+
+pub async fn get_webhook(pool: &SqlitePool, id: i64) -> Result<Webhook> {
+    let webhook = sqlx::query_as::<_, Webhook>("SELECT * FROM webhooks WHERE id = ?")
+        .bind(id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Webhook {} not found", id)))?;
+
+    let mut webhooks = vec![webhook];
+    load_webhook_targets(pool, &mut webhooks).await?;
+    Ok(webhooks.remove(0))
+}
+
+/// Enabled webhooks subscribed to an event of the given server or client
+pub async fn list_webhooks_for(
+    pool: &SqlitePool,
+    entity_type: SessionType,
+    entity_id: i64,
+    event: &str,
+) -> Result<Vec<Webhook>> {
+    let mut webhooks = sqlx::query_as::<_, Webhook>(
+        r#"
+        SELECT * FROM webhooks w
+        WHERE enabled = 1
+          AND EXISTS (SELECT 1 FROM json_each(w.events) WHERE value = ?)
+          AND EXISTS (
+              SELECT 1 FROM webhook_targets t
+              WHERE t.webhook_id = w.id AND t.entity_type = ? AND (t.entity_id IS NULL OR t.entity_id = ?)
+          )
+        ORDER BY id
+        "#
+    )
+    .bind(event)
+    .bind(entity_type)
+    .bind(entity_id)
+    .fetch_all(pool)
+    .await?;
+
+    load_webhook_targets(pool, &mut webhooks).await?;
+    Ok(webhooks)
+}
+
+pub async fn create_webhook(
+    pool: &SqlitePool,
+    input: &CreateWebhook,
+    events: &[String],
+    targets: &[WebhookTarget],
+) -> Result<Webhook> {
+    let mut tx = pool.begin().await?;
+
+    let id = sqlx::query_scalar::<_, i64>(
+        r#"
+        INSERT INTO webhooks (name, url, method, format, template, headers, secret, events, enabled, auth)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        RETURNING id
+        "#
+    )
+    .bind(&input.name)
+    .bind(&input.url)
+    .bind(&input.method)
+    .bind(&input.format)
+    .bind(&input.template)
+    .bind(sqlx::types::Json(&input.headers))
+    .bind(&input.secret)
+    .bind(sqlx::types::Json(events))
+    .bind(input.enabled)
+    .bind(input.auth.as_ref().filter(|auth| **auth != WebhookAuth::None).map(sqlx::types::Json))
+    .fetch_one(&mut *tx)
+    .await?;
+
+    insert_webhook_targets(&mut tx, id, targets).await?;
+    tx.commit().await?;
+
+    get_webhook(pool, id).await
+}
+
+pub async fn update_webhook(
+    pool: &SqlitePool,
+    id: i64,
+    input: &UpdateWebhook,
+    targets: Option<&[WebhookTarget]>,
+) -> Result<Webhook> {
+    // First check if webhook exists
+    let _ = get_webhook(pool, id).await?;
+
+    // Build dynamic UPDATE query based on provided fields
+    let mut query = String::from("UPDATE webhooks SET updated_at = CURRENT_TIMESTAMP");
+    let mut params: Vec<String> = vec![];
+
+    if let Some(name) = &input.name {
+        query.push_str(", name = ?");
+        params.push(name.clone());
+    }
+    if let Some(url) = &input.url {
+        query.push_str(", url = ?");
+        params.push(url.clone());
+    }
+    if let Some(method) = &input.method {
+        query.push_str(", method = ?");
+        params.push(method.clone());
+    }
+    if let Some(format) = &input.format {
+        query.push_str(", format = ?");
+        params.push(format.clone());
+    }
+    if let Some(template) = &input.template {
+        query.push_str(", template = NULLIF(?, '')");
+        params.push(template.clone());
+    }
+    if let Some(headers) = &input.headers {
+        query.push_str(", headers = ?");
+        params.push(serde_json::to_string(headers).unwrap_or_default());
+    }
+    if let Some(secret) = &input.secret {
+        query.push_str(", secret = NULLIF(?, '')");
+        params.push(secret.clone());
+    }
+    if let Some(events) = &input.events {
+        query.push_str(", events = ?");
+        params.push(serde_json::to_string(events).unwrap_or_default());
+    }
+    if let Some(enabled) = input.enabled {
+        query.push_str(&format!(", enabled = {}", if enabled { 1 } else { 0 }));
+    }
+    match &input.auth {
+        Some(WebhookAuth::None) => query.push_str(", auth = NULL"),
+        Some(auth) => {
+            query.push_str(", auth = ?");
+            params.push(serde_json::to_string(auth).unwrap_or_default());
+        }
+        None => {}
+    }
+
+    query.push_str(" WHERE id = ?");
+
+    let mut tx = pool.begin().await?;
+
+    let mut q = sqlx::query(&query);
+    for param in &params {
+        q = q.bind(param);
+    }
+    q = q.bind(id);
+    q.execute(&mut *tx).await?;
+
+    if let Some(targets) = targets {
+        sqlx::query("DELETE FROM webhook_targets WHERE webhook_id = ?")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        insert_webhook_targets(&mut tx, id, targets).await?;
+    }
+
+    tx.commit().await?;
+
+    get_webhook(pool, id).await
+}
+
+pub async fn delete_webhook(pool: &SqlitePool, id: i64) -> Result<()> {
+    let result = sqlx::query("DELETE FROM webhooks WHERE id = ?")
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(AppError::NotFound(format!("Webhook {} not found", id)));
+    }
+
+    Ok(())
+}
+
+/// Detach a deleted server or client from the webhooks naming it
+async fn delete_webhook_targets(pool: &SqlitePool, entity_type: SessionType, entity_id: i64) -> Result<()> {
+    sqlx::query("DELETE FROM webhook_targets WHERE entity_type = ? AND entity_id = ?")
+        .bind(entity_type)
+        .bind(entity_id)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+async fn insert_webhook_targets(
+    tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
+    webhook_id: i64,
+    targets: &[WebhookTarget],
+) -> Result<()> {
+    for (entity_type, entity_id) in targets {
+        sqlx::query("INSERT INTO webhook_targets (webhook_id, entity_type, entity_id) VALUES (?, ?, ?)")
+            .bind(webhook_id)
+            .bind(entity_type.clone())
+            .bind(entity_id)
+            .execute(&mut **tx)
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// Fill in the targets of webhooks, as `server:<id>` or `client:*` strings
+async fn load_webhook_targets(pool: &SqlitePool, webhooks: &mut [Webhook]) -> Result<()> {
+    if webhooks.is_empty() {
+        return Ok(());
+    }
+
+    let ids: Vec<String> = webhooks.iter().map(|webhook| webhook.id.to_string()).collect();
+    let rows = sqlx::query_as::<_, (i64, SessionType, Option<i64>)>(&format!(
+        "SELECT webhook_id, entity_type, entity_id FROM webhook_targets WHERE webhook_id IN ({}) ORDER BY entity_type DESC, entity_id",
+        ids.join(", ")
+    ))
+    .fetch_all(pool)
+    .await?;
+
+    for webhook in webhooks.iter_mut() {
+        webhook.targets = rows
+            .iter()
+            .filter(|(webhook_id, _, _)| *webhook_id == webhook.id)
+            .map(|(_, entity_type, entity_id)| match entity_id {
+                Some(id) => format!("{}:{}", entity_type.as_str(), id),
+                None => format!("{}:*", entity_type.as_str()),
+            })
+            .collect();
+    }
+
+    Ok(())
+}
+
+// Webhook delivery operations
+pub async fn create_webhook_delivery(pool: &SqlitePool, input: &NewWebhookDelivery) -> Result<WebhookDelivery> {
+    let delivery = sqlx::query_as::<_, WebhookDelivery>(
+        r#"
+        INSERT INTO webhook_deliveries (entity_type, entity_id, event, method, url, content_type, body, signing_secret, webhook_id, headers)
+        VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+        RETURNING *
+        "#
+    )
+    .bind(input.entity_type.clone())
+    .bind(input.entity_id)
+    .bind(&input.event)
+    .bind(&input.method)
+    .bind(&input.url)
+    .bind(&input.content_type)
+    .bind(&input.body)
+    .bind(&input.signing_secret)
+    .bind(input.webhook_id)
+    .bind(sqlx::types::Json(&input.headers))
+    .fetch_one(pool)
+    .await?;
+
+    Ok(delivery)
+}
+
+pub async fn get_webhook_delivery(pool: &SqlitePool, id: i64) -> Result<WebhookDelivery> {
+    let delivery = sqlx::query_as::<_, WebhookDelivery>("SELECT * FROM webhook_deliveries WHERE id = ?")
+        .bind(id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Webhook delivery {} not found", id)))?;
+
+    Ok(delivery)
+}
+
+/// Pending deliveries whose next attempt is due, oldest first
+pub async fn list_due_webhook_deliveries(pool: &SqlitePool, limit: i64) -> Result<Vec<WebhookDelivery>> {
+    let deliveries = sqlx::query_as::<_, WebhookDelivery>(
+        r#"
+        SELECT * FROM webhook_deliveries
+        WHERE status = 'pending' AND next_attempt_at <= CURRENT_TIMESTAMP
+        ORDER BY id
+        LIMIT ?
+        "#
+    )
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(deliveries)
+}
+
+/// Seconds until the earliest pending delivery is due, if any is pending
+pub async fn seconds_until_next_webhook_attempt(pool: &SqlitePool) -> Result<Option<i64>> {
+    let seconds = sqlx::query_scalar::<_, Option<i64>>(
+        r#"
+        SELECT MIN(CAST(strftime('%s', next_attempt_at) AS INTEGER)) - CAST(strftime('%s', 'now') AS INTEGER)
+        FROM webhook_deliveries
+        WHERE status = 'pending'
+        "#
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(seconds)
+}
+
+pub async fn list_webhook_deliveries(
+    pool: &SqlitePool,
+    entity: Option<(SessionType, i64)>,
+    filter: &DeliveryQuery,
+    page: i64,
+    per_page: i64,
+) -> Result<(Vec<WebhookDelivery>, i64)> {
+    let mut count = QueryBuilder::new("SELECT COUNT(*) FROM webhook_deliveries WHERE 1 = 1");
+    push_delivery_filters(&mut count, entity.as_ref(), filter);
+    let total = count.build_query_scalar::<i64>().fetch_one(pool).await?;
+
+    let mut query = QueryBuilder::new("SELECT * FROM webhook_deliveries WHERE 1 = 1");
+    push_delivery_filters(&mut query, entity.as_ref(), filter);
+    query
+        .push(" ORDER BY id DESC LIMIT ")
+        .push_bind(per_page)
+        .push(" OFFSET ")
+        .push_bind((page - 1) * per_page);
+    let items = query.build_query_as::<WebhookDelivery>().fetch_all(pool).await?;
+
+    Ok((items, total))
+}
+
+/// Conditions shared by the count and the page of `list_webhook_deliveries`
+fn push_delivery_filters(
+    query: &mut QueryBuilder<'_, Sqlite>,
+    entity: Option<&(SessionType, i64)>,
+    filter: &DeliveryQuery,
+) {
+    if let Some((entity_type, entity_id)) = entity {
+        query
+            .push(" AND entity_type = ")
+            .push_bind(entity_type.as_str().to_string())
+            .push(" AND entity_id = ")
+            .push_bind(*entity_id);
+    }
+    if let Some(status) = filter.status {
+        query.push(" AND status = ").push_bind(status.as_str().to_string());
+    }
+    if let Some(event) = &filter.event {
+        query.push(" AND event = ").push_bind(event.clone());
+    }
+    if let Some(webhook_id) = filter.webhook_id {
+        query.push(" AND webhook_id = ").push_bind(webhook_id);
+    }
+}
+
+/// Record the outcome of an attempt and schedule the next one if still pending
+pub async fn record_webhook_attempt(pool: &SqlitePool, id: i64, attempt: &DeliveryAttempt) -> Result<()> {
+    sqlx::query(
+        r#"
+        UPDATE webhook_deliveries SET
+            status = ?,
+            attempts = attempts + 1,
+            last_attempt_at = CURRENT_TIMESTAMP,
+            next_attempt_at = datetime('now', ?),
+            response_status = ?,
+            response_body = ?,
+            error = ?,
+            delivered_at = CASE WHEN ? = 'delivered' THEN CURRENT_TIMESTAMP ELSE delivered_at END
+        WHERE id = ?
+        "#
+    )
+    .bind(attempt.status)
+    .bind(format!("+{} seconds", attempt.retry_in))
+    .bind(attempt.response_status)
+    .bind(&attempt.response_body)
+    .bind(&attempt.error)
+    .bind(attempt.status.as_str())
+    .bind(id)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Queue the payload of an earlier delivery again as a new delivery
+pub async fn copy_webhook_delivery(pool: &SqlitePool, id: i64) -> Result<WebhookDelivery> {
+    let _ = get_webhook_delivery(pool, id).await?;
+
+    let delivery = sqlx::query_as::<_, WebhookDelivery>(
+        r#"
+        INSERT INTO webhook_deliveries (entity_type, entity_id, event, method, url, content_type, body, signing_secret, webhook_id, headers)
+        SELECT entity_type, entity_id, event, method, url, content_type, body, signing_secret, webhook_id, headers FROM webhook_deliveries WHERE id = ?
+        RETURNING *
+        "#
+    )
+    .bind(id)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(delivery)
+}
+
+/// Delete finished deliveries created more than `retention_days` ago; skipped when 0
+pub async fn prune_webhook_deliveries(pool: &SqlitePool, retention_days: u64) -> Result<u64> {
+    if retention_days == 0 {
+        return Ok(0);
+    }
+
+    let result = sqlx::query(
+        "DELETE FROM webhook_deliveries WHERE status != 'pending' AND created_at < datetime('now', ?)"
+    )
+    .bind(format!("-{} days", retention_days))
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
@@ -9,6 +9,10 @@ pub mod ws;
 pub mod web;
 pub mod middleware;
 pub mod webhook;
+pub mod metrics;
+pub mod logs;
+pub mod telemetry;
+pub mod time;
 
 pub use config::Config;
 pub use error::AppError;
@@ -0,0 +1,212 @@
+//! Recent log lines of each server and client.
+//!
+//! Tunnel work runs inside a `server` or `client` span carrying a `server_id` or
+//! `client_id` field. [`TunnelLogLayer`] copies events from within such spans, or
+//! carrying such a field themselves, into a bounded buffer per entity, served by
+//! `/api/v1/{servers,clients}/{id}/logs`, and hands each line to a channel streamed
+//! to `logs:` WebSocket subscribers.
+
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fmt::{self, Write};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::models::SessionType;
+use crate::ws::{WsBroadcaster, WsMessage};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogEntry {
+    /// Position in the entity's log, starting at 1; pass the last one seen as `since`
+    pub seq: u64,
+    pub timestamp: DateTime<Utc>,
+    pub level: String,
+    pub target: String,
+    pub message: String,
+}
+
+/// A log line of a server or client, as streamed over WebSocket
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TunnelLogLine {
+    pub entity_type: SessionType,
+    pub entity_id: i64,
+    #[serde(flatten)]
+    pub entry: LogEntry,
+}
+
+#[derive(Default)]
+struct Buffer {
+    next_seq: u64,
+    lines: VecDeque<LogEntry>,
+}
+
+/// Log buffers of all servers and clients
+pub struct TunnelLogs {
+    capacity: usize,
+    buffers: DashMap<(SessionType, i64), Buffer>,
+    tx: mpsc::UnboundedSender<TunnelLogLine>,
+}
+
+impl TunnelLogs {
+    /// Create buffers keeping the last `capacity` lines of each entity, along with
+    /// the receiving end of the stream of new lines
+    pub fn new(capacity: usize) -> (Arc<Self>, mpsc::UnboundedReceiver<TunnelLogLine>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let logs = Arc::new(Self {
+            capacity: capacity.max(1),
+            buffers: DashMap::new(),
+            tx,
+        });
+        (logs, rx)
+    }
+
+    /// Tracing layer feeding these buffers
+    pub fn layer(self: &Arc<Self>) -> TunnelLogLayer {
+        TunnelLogLayer { logs: self.clone() }
+    }
+
+    /// Lines of an entity after `since`, oldest first
+    pub fn since(&self, entity_type: SessionType, entity_id: i64, since: u64) -> Vec<LogEntry> {
+        self.buffers
+            .get(&(entity_type, entity_id))
+            .map(|buffer| buffer.lines.iter().filter(|entry| entry.seq > since).cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Drop the log of a deleted entity
+    pub fn remove(&self, entity_type: SessionType, entity_id: i64) {
+        self.buffers.remove(&(entity_type, entity_id));
+    }
+
+    fn push(&self, entity_type: SessionType, entity_id: i64, level: String, target: String, message: String) {
+        let entry = {
+            let mut buffer = self.buffers.entry((entity_type.clone(), entity_id)).or_default();
+            buffer.next_seq += 1;
+            let entry = LogEntry {
+                seq: buffer.next_seq,
+                timestamp: Utc::now(),
+                level,
+                target,
+                message,
+            };
+            if buffer.lines.len() == self.capacity {
+                buffer.lines.pop_front();
+            }
+            buffer.lines.push_back(entry.clone());
+            entry
+        };
+
+        let _ = self.tx.send(TunnelLogLine {
+            entity_type,
+            entity_id,
+            entry,
+        });
+    }
+}
+
+/// Stream new log lines to WebSocket subscribers
+pub async fn forward_to_websocket(mut rx: mpsc::UnboundedReceiver<TunnelLogLine>, ws_broadcaster: Arc<WsBroadcaster>) {
+    while let Some(line) = rx.recv().await {
+        ws_broadcaster.broadcast(WsMessage::Log(line));
+    }
+}
+
+/// Tracing layer recording events of tunnel spans into [`TunnelLogs`]
+pub struct TunnelLogLayer {
+    logs: Arc<TunnelLogs>,
+}
+
+/// Entity a span belongs to, kept in its extensions
+#[derive(Clone)]
+struct SpanEntity(SessionType, i64);
+
+impl<S> Layer<S> for TunnelLogLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let mut visitor = EntityVisitor::default();
+        attrs.record(&mut visitor);
+        if let Some(entity) = visitor.entity
+            && let Some(span) = ctx.span(id)
+        {
+            span.extensions_mut().insert(entity);
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+
+        let Some(SpanEntity(entity_type, entity_id)) = visitor.entity.entity.or_else(|| {
+            ctx.event_scope(event)
+                .and_then(|mut scope| scope.find_map(|span| span.extensions().get::<SpanEntity>().cloned()))
+        }) else {
+            return;
+        };
+
+        let metadata = event.metadata();
+        self.logs.push(
+            entity_type,
+            entity_id,
+            metadata.level().to_string(),
+            metadata.target().to_string(),
+            visitor.message,
+        );
+    }
+}
+
+#[derive(Default)]
+struct EntityVisitor {
+    entity: Option<SpanEntity>,
+}
+
+impl Visit for EntityVisitor {
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        match field.name() {
+            "server_id" => self.entity = Some(SpanEntity(SessionType::Server, value)),
+            "client_id" => self.entity = Some(SpanEntity(SessionType::Client, value)),
+            _ => {}
+        }
+    }
+
+    fn record_debug(&mut self, _field: &Field, _value: &dyn fmt::Debug) {}
+}
+
+/// Renders an event as its message followed by its other fields as `key=value`,
+/// noting the entity it names
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+    entity: EntityVisitor,
+}
+
+impl Visit for MessageVisitor {
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.entity.record_i64(field, value);
+        self.record_debug(field, &value);
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message.insert_str(0, value);
+        } else {
+            let _ = write!(self.message, " {}={}", field.name(), value);
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.message.insert_str(0, &format!("{:?}", value));
+        } else {
+            let _ = write!(self.message, " {}={:?}", field.name(), value);
+        }
+    }
+}
@@ -10,20 +10,25 @@ use tower_http::cors::CorsLayer;
 use tower_http::trace::TraceLayer;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
-use borui::{api, config::Config, db, models, state::AppState, web, ws};
+use borui::{api, config::Config, db, logs, metrics, models, state::AppState, telemetry, web, ws};
+use borui::logs::TunnelLogs;
+use borui::tunnel::TaskExit;
+use borui::webhook::{self, RetryPolicy, WebhookEntity, WebhookEvent, send_webhook};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     // Load configuration
     let config = Config::from_env()?;
 
-    // Initialize tracing
+    // Initialize tracing, keeping recent lines of each tunnel in memory
+    let (tunnel_logs, tunnel_log_lines) = TunnelLogs::new(config.tunnel_log_lines);
     tracing_subscriber::registry()
         .with(
             tracing_subscriber::EnvFilter::try_from_default_env()
                 .unwrap_or_else(|_| config.log_level.clone().into()),
         )
         .with(tracing_subscriber::fmt::layer())
+        .with(tunnel_logs.layer())
         .init();
 
     tracing::info!("Starting Borui v{}", env!("CARGO_PKG_VERSION"));
@@ -87,7 +92,7 @@ async fn main() -> anyhow::Result<()> {
             .map_err(|e| anyhow::anyhow!("Failed to hash password: {}", e))?
             .to_string();
 
-        sqlx::query("INSERT INTO users (username, password_hash) VALUES (?, ?)")
+        sqlx::query("INSERT INTO users (username, password_hash, role) VALUES (?, ?, 'admin')")
             .bind(&admin_username)
             .bind(&password_hash)
             .execute(&db)
@@ -100,7 +105,10 @@ async fn main() -> anyhow::Result<()> {
     }
 
     // Create application state
-    let state = AppState::new(db);
+    let state = AppState::new(db, config.clone(), tunnel_logs)?;
+
+    // Stream tunnel log lines to WebSocket subscribers
+    tokio::spawn(logs::forward_to_websocket(tunnel_log_lines, state.ws_broadcaster.clone()));
 
     // Sync database state with actual runtime state (handles restart scenarios)
     sync_database_state(&state).await?;
@@ -108,13 +116,32 @@ async fn main() -> anyhow::Result<()> {
     // Start background task monitor to detect crashed/finished servers and clients
     start_task_monitor(state.clone());
 
+    // Start periodic session heartbeats for traffic accounting
+    start_session_heartbeat(state.clone());
+
+    // Keep the connection log within its retention limits
+    start_connection_log_retention(state.clone());
+
+    // Start sampling traffic history and rolling it up
+    start_metrics(state.clone());
+
+    // Start delivering queued webhooks, including those left over from the previous run
+    start_webhook_delivery(state.clone());
+
     // Start auto-start servers and clients
     start_auto_start_entities(&state).await?;
 
     // Build router
     let app = Router::new()
-        .nest("/api/v1", api::api_router())
+        .nest(
+            "/api/v1",
+            api::api_router(state.clone()).layer(axum::middleware::from_fn_with_state(
+                state.clone(),
+                telemetry::track_http_metrics,
+            )),
+        )
         .route("/ws", get(ws::ws_handler))
+        .route("/metrics", get(telemetry::metrics_handler))
         .fallback(|uri: axum::http::Uri| async move {
             web::serve_static(uri.to_string()).await
         })
@@ -129,6 +156,7 @@ async fn main() -> anyhow::Result<()> {
     tracing::info!("Web UI available at http://{}", addr);
     tracing::info!("API available at http://{}/api/v1", addr);
     tracing::info!("WebSocket available at ws://{}/ws", addr);
+    tracing::info!("Prometheus metrics available at http://{}/metrics", addr);
 
     // Start server with graceful shutdown
     let listener = tokio::net::TcpListener::bind(&addr).await?;
@@ -152,7 +180,7 @@ async fn shutdown_signal() {
     #[cfg(unix)]
     let terminate = async {
         use futures_util::StreamExt;
-        let mut signals = signal_hook_tokio::Signals::new(&[signal_hook::consts::SIGTERM])
+        let mut signals = signal_hook_tokio::Signals::new([signal_hook::consts::SIGTERM])
             .expect("failed to install SIGTERM handler");
 
         signals.next().await;
@@ -183,34 +211,45 @@ fn start_task_monitor(state: AppState) {
             let finished_servers = state.server_manager.get_finished_servers();
             for server_id in finished_servers {
                 tracing::warn!(
+                    server_id,
                     "Server {} task has finished unexpectedly. Cleaning up and updating database.",
                     server_id
                 );
 
                 // Remove from manager
-                state.server_manager.remove_finished_server(server_id);
+                let Some(exit) = state.server_manager.remove_finished_server(server_id).await else {
+                    continue;
+                };
 
                 // Update database status
                 if let Err(e) = db::update_server_status(
                     &state.db,
                     server_id,
                     models::ServerStatus::Error,
-                    Some("Server task stopped unexpectedly".to_string())
+                    Some(exit.error.clone())
                 ).await {
                     tracing::error!("Failed to update server {} status in database: {}", server_id, e);
                 }
+
+                match db::get_server(&state.db, server_id).await {
+                    Ok(server) => report_exit(&state, server.into(), &exit),
+                    Err(e) => tracing::error!("Failed to load server {}: {}", server_id, e),
+                }
             }
 
             // Check for finished clients
             let finished_clients = state.client_manager.get_finished_clients();
             for client_id in finished_clients {
                 tracing::warn!(
+                    client_id,
                     "Client {} task has finished unexpectedly. Cleaning up and updating database.",
                     client_id
                 );
 
                 // Remove from manager
-                state.client_manager.remove_finished_client(client_id);
+                let Some(exit) = state.client_manager.remove_finished_client(client_id).await else {
+                    continue;
+                };
 
                 // Update database status
                 if let Err(e) = db::update_client_status(
@@ -218,10 +257,15 @@ fn start_task_monitor(state: AppState) {
                     client_id,
                     models::ClientStatus::Error,
                     None,
-                    Some("Client task stopped unexpectedly".to_string())
+                    Some(exit.error.clone())
                 ).await {
                     tracing::error!("Failed to update client {} status in database: {}", client_id, e);
                 }
+
+                match db::get_client(&state.db, client_id).await {
+                    Ok(client) => report_exit(&state, client.into(), &exit),
+                    Err(e) => tracing::error!("Failed to load client {}: {}", client_id, e),
+                }
             }
         }
     });
@@ -229,11 +273,161 @@ fn start_task_monitor(state: AppState) {
     tracing::info!("Task monitor started (checking every 5 seconds)");
 }
 
+// Report a task that ended on its own, unless its webhook was already sent by the task
+fn report_exit(state: &AppState, entity: WebhookEntity, exit: &TaskExit) {
+    if exit.webhook_sent {
+        broadcast_error(state, &entity, &exit.error, Some(exit.uptime_seconds));
+    } else {
+        report_error(state, entity, &exit.error, Some(exit.uptime_seconds));
+    }
+}
+
+// Tell WebSocket subscribers and the entity's webhooks that it entered the error state
+fn report_error(state: &AppState, entity: WebhookEntity, error: &str, uptime_seconds: Option<u64>) {
+    broadcast_error(state, &entity, error, uptime_seconds);
+
+    let mut extra_data = serde_json::json!({ "error": error });
+    if let Some(uptime) = uptime_seconds {
+        extra_data["uptime_seconds"] = uptime.into();
+    }
+    send_webhook(&state.db, WebhookEvent::Error, entity, extra_data);
+}
+
+fn broadcast_error(state: &AppState, entity: &WebhookEntity, error: &str, uptime_seconds: Option<u64>) {
+    let id = entity.id();
+    let error_message = Some(error.to_string());
+    state.ws_broadcaster.broadcast(match entity {
+        WebhookEntity::Server(_) => ws::WsMessage::ServerStatus(
+            ws::StatusUpdate::new(id, models::ServerStatus::Error, None, error_message)
+                .with("uptime_seconds", uptime_seconds),
+        ),
+        WebhookEntity::Client(_) => ws::WsMessage::ClientStatus(
+            ws::StatusUpdate::new(id, models::ClientStatus::Error, None, error_message)
+                .with("uptime_seconds", uptime_seconds),
+        ),
+    });
+}
+
+// Periodically persist traffic counters of running tunnels into their sessions
+fn start_session_heartbeat(state: AppState) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(10));
+
+        loop {
+            interval.tick().await;
+
+            state.server_manager.record_sessions().await;
+            state.client_manager.record_sessions().await;
+        }
+    });
+
+    tracing::info!("Session heartbeat started (recording every 10 seconds)");
+}
+
+// Periodically delete connection log entries beyond the configured retention
+fn start_connection_log_retention(state: AppState) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(3600));
+
+        loop {
+            interval.tick().await;
+
+            match db::prune_connections(
+                &state.db,
+                state.config.connection_log_retention_days,
+                state.config.connection_log_max_rows,
+            ).await {
+                Ok(0) => {}
+                Ok(deleted) => tracing::info!("Pruned {} entries from the connection log", deleted),
+                Err(e) => tracing::error!("Failed to prune the connection log: {}", e),
+            }
+        }
+    });
+
+    tracing::info!("Connection log retention started (pruning every hour)");
+}
+
+// Send queued webhooks in the background and periodically delete old deliveries
+fn start_webhook_delivery(state: AppState) {
+    webhook::queue::start_worker(
+        state.db.clone(),
+        state.webhook_sender.clone(),
+        RetryPolicy {
+            max_attempts: state.config.webhook_max_attempts,
+            base_delay: state.config.webhook_retry_base,
+            max_delay: state.config.webhook_retry_max,
+        },
+    );
+    tracing::info!(
+        "Webhook delivery started (up to {} attempts, retrying after {}s up to {}s)",
+        state.config.webhook_max_attempts,
+        state.config.webhook_retry_base,
+        state.config.webhook_retry_max
+    );
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(3600));
+
+        loop {
+            interval.tick().await;
+
+            match db::prune_webhook_deliveries(&state.db, state.config.webhook_delivery_retention_days).await {
+                Ok(0) => {}
+                Ok(deleted) => tracing::info!("Pruned {} webhook deliveries", deleted),
+                Err(e) => tracing::error!("Failed to prune webhook deliveries: {}", e),
+            }
+        }
+    });
+
+}
+
+// Periodically sample the traffic of running tunnels and roll samples up every minute
+fn start_metrics(state: AppState) {
+    let sample_interval = state.config.metrics_sample_interval;
+
+    let sampler_state = state.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(sample_interval));
+
+        loop {
+            interval.tick().await;
+
+            if let Err(e) = metrics::record_samples(&sampler_state).await {
+                tracing::error!("Failed to record traffic samples: {}", e);
+            }
+        }
+    });
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(60));
+
+        loop {
+            interval.tick().await;
+
+            if let Err(e) = metrics::roll_up(&state.db).await {
+                tracing::error!("Failed to roll up traffic samples: {}", e);
+            }
+        }
+    });
+
+    tracing::info!("Metrics started (sampling every {} seconds)", sample_interval);
+}
+
 // Synchronize database state with actual runtime state
 // This handles cases where the application restarted and memory state was lost
 async fn sync_database_state(state: &AppState) -> anyhow::Result<()> {
     tracing::info!("Synchronizing database state with runtime state...");
 
+    // Sessions still open belong to tunnels that died with the previous process
+    let stale_sessions = db::close_stale_sessions(&state.db).await?;
+    if stale_sessions > 0 {
+        tracing::warn!("Closed {} sessions left open by a previous run", stale_sessions);
+    }
+    let stale_connections = db::close_stale_connections(&state.db).await?;
+    if stale_connections > 0 {
+        tracing::warn!("Marked {} connections left open by a previous run as interrupted", stale_connections);
+    }
+
     // Reset all servers that are marked as running/starting but aren't actually running
     let servers = db::list_servers(&state.db).await?;
     for server in servers {
@@ -259,6 +453,7 @@ async fn sync_database_state(state: &AppState) -> anyhow::Result<()> {
     let clients = db::list_clients(&state.db).await?;
     for client in clients {
         if client.status == models::ClientStatus::Connected
+            || client.status == models::ClientStatus::Reconnecting
             || client.status == models::ClientStatus::Starting {
             // Check if client is actually connected in memory
             if state.client_manager.get_status(client.id).is_none() {
@@ -302,17 +497,25 @@ async fn start_auto_start_entities(state: &AppState) -> anyhow::Result<()> {
             // Update status to starting
             db::update_server_status(&state.db, server.id, models::ServerStatus::Starting, None).await?;
             db::update_server_last_started(&state.db, server.id).await?;
+            state.ws_broadcaster.server_status(server.id, models::ServerStatus::Starting, None, None);
 
             // Start the server
             match state.server_manager.start_server(server.clone()).await {
                 Ok(_) => {
                     db::update_server_status(&state.db, server.id, models::ServerStatus::Running, None).await?;
+                    state.ws_broadcaster.server_status(
+                        server.id,
+                        models::ServerStatus::Running,
+                        Some(server.control_port),
+                        None,
+                    );
                     tracing::info!("Successfully started server: {}", server.name);
                 }
                 Err(e) => {
                     let error_msg = e.to_string();
                     db::update_server_status(&state.db, server.id, models::ServerStatus::Error, Some(error_msg.clone())).await?;
-                    tracing::error!("Failed to start server {}: {}", server.name, error_msg);
+                    tracing::error!(server_id = server.id, "Failed to start server {}: {}", server.name, error_msg);
+                    report_error(state, server.into(), &error_msg, None);
                 }
             }
         }
@@ -335,17 +538,21 @@ async fn start_auto_start_entities(state: &AppState) -> anyhow::Result<()> {
             // Update status to starting
             db::update_client_status(&state.db, client.id, models::ClientStatus::Starting, None, None).await?;
             db::update_client_last_connected(&state.db, client.id).await?;
+            state.ws_broadcaster.client_status(client.id, models::ClientStatus::Starting, None, None);
 
             // Start the client
             match state.client_manager.start_client(client.clone()).await {
-                Ok(_) => {
-                    db::update_client_status(&state.db, client.id, models::ClientStatus::Connected, None, None).await?;
+                Ok(assigned_port) => {
+                    let assigned_port = Some(assigned_port as i64);
+                    db::update_client_status(&state.db, client.id, models::ClientStatus::Connected, assigned_port, None).await?;
+                    state.ws_broadcaster.client_status(client.id, models::ClientStatus::Connected, assigned_port, None);
                     tracing::info!("Successfully started client: {}", client.name);
                 }
                 Err(e) => {
                     let error_msg = e.to_string();
                     db::update_client_status(&state.db, client.id, models::ClientStatus::Error, None, Some(error_msg.clone())).await?;
-                    tracing::error!("Failed to start client {}: {}", client.name, error_msg);
+                    tracing::error!(client_id = client.id, "Failed to start client {}: {}", client.name, error_msg);
+                    report_error(state, client.into(), &error_msg, None);
                 }
             }
         }
@@ -0,0 +1,111 @@
+//! Traffic history of servers and clients.
+//!
+//! Running tunnels are sampled every `METRICS_SAMPLE_INTERVAL` seconds into raw samples,
+//! which a background job rolls up into minute, hour and day buckets. Each resolution is
+//! kept for a limited time, so long ranges are answered from coarser data.
+
+use crate::db;
+use crate::error::Result;
+use crate::models::{Resolution, SessionType};
+use crate::state::AppState;
+use crate::tunnel::stats::MetricsSample;
+use crate::ws::{Topic, WsMessage};
+use chrono::{DateTime, Utc};
+use sqlx::SqlitePool;
+
+const MINUTE: i64 = 60;
+const HOUR: i64 = 60 * MINUTE;
+const DAY: i64 = 24 * HOUR;
+
+/// Rollup levels: resolution, the resolution it is computed from, and the strftime
+/// pattern giving the start of a bucket
+const ROLLUPS: [(Resolution, Resolution, &str); 3] = [
+    (Resolution::Minute, Resolution::Raw, "%Y-%m-%d %H:%M:00"),
+    (Resolution::Hour, Resolution::Minute, "%Y-%m-%d %H:00:00"),
+    (Resolution::Day, Resolution::Hour, "%Y-%m-%d 00:00:00"),
+];
+
+/// How long samples of a resolution are kept, in seconds
+pub fn retention(resolution: Resolution) -> i64 {
+    match resolution {
+        Resolution::Raw => DAY,
+        Resolution::Minute => 7 * DAY,
+        Resolution::Hour => 90 * DAY,
+        Resolution::Day => 5 * 365 * DAY,
+    }
+}
+
+/// Length of a bucket of a resolution, in seconds
+pub fn bucket_size(resolution: Resolution, sample_interval: u64) -> i64 {
+    match resolution {
+        Resolution::Raw => sample_interval.max(1) as i64,
+        Resolution::Minute => MINUTE,
+        Resolution::Hour => HOUR,
+        Resolution::Day => DAY,
+    }
+}
+
+/// Record the traffic of every running tunnel since the previous sample.
+/// Idle tunnels are skipped, so gaps in the history mean no traffic.
+pub async fn record_samples(state: &AppState) -> Result<()> {
+    let is_active = |(_, sample): &(i64, MetricsSample)| {
+        sample.bytes_sent > 0 || sample.bytes_received > 0 || sample.connections > 0 || sample.active_connections > 0
+    };
+
+    let servers: Vec<_> = state.server_manager.take_metrics().into_iter().filter(is_active).collect();
+    let clients: Vec<_> = state.client_manager.take_metrics().into_iter().filter(is_active).collect();
+
+    db::insert_metric_samples(&state.db, SessionType::Server, &servers).await?;
+    db::insert_metric_samples(&state.db, SessionType::Client, &clients).await?;
+
+    if state.ws_broadcaster.has_subscribers(&Topic::Metrics) {
+        let samples: Vec<_> = [(SessionType::Server, &servers), (SessionType::Client, &clients)]
+            .into_iter()
+            .flat_map(|(session_type, samples)| {
+                samples.iter().map(move |(id, sample)| {
+                    serde_json::json!({
+                        "entity": format!("{}:{}", session_type.as_str(), id),
+                        "bytes_sent": sample.bytes_sent,
+                        "bytes_received": sample.bytes_received,
+                        "connections": sample.connections,
+                        "active_connections": sample.active_connections,
+                    })
+                })
+            })
+            .collect();
+
+        state.ws_broadcaster.broadcast(WsMessage::Metrics(serde_json::json!({
+            "timestamp": Utc::now().to_rfc3339(),
+            "samples": samples,
+        })));
+    }
+
+    Ok(())
+}
+
+/// Roll samples up into coarser buckets and drop those past their retention
+pub async fn roll_up(pool: &SqlitePool) -> Result<()> {
+    for (target, source, bucket_format) in ROLLUPS {
+        db::roll_up_metrics(pool, source, target, bucket_format).await?;
+    }
+
+    for resolution in [Resolution::Raw, Resolution::Minute, Resolution::Hour, Resolution::Day] {
+        db::prune_metric_samples(pool, resolution, retention(resolution)).await?;
+    }
+
+    Ok(())
+}
+
+/// Pick the stored resolution to answer a query from: the finest one still kept for
+/// `from`, so recent ranges include samples not rolled up yet. Returns it along with
+/// the step actually used, rounded up to a whole number of its buckets.
+pub fn choose_resolution(from: DateTime<Utc>, step: i64, sample_interval: u64) -> (Resolution, i64) {
+    let age = (Utc::now() - from).num_seconds();
+    let resolution = [Resolution::Raw, Resolution::Minute, Resolution::Hour]
+        .into_iter()
+        .find(|level| retention(*level) >= age)
+        .unwrap_or(Resolution::Day);
+
+    let size = bucket_size(resolution, sample_interval);
+    (resolution, (step.max(size) + size - 1) / size * size)
+}
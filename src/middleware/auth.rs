@@ -1,18 +1,24 @@
 use axum::{
-    extract::Request,
-    http::{header, StatusCode},
+    extract::{MatchedPath, Request, State},
+    http::{header, Method, StatusCode},
     middleware::Next,
     response::Response,
 };
 use jsonwebtoken::{decode, DecodingKey, Validation};
 use serde::{Deserialize, Serialize};
 
+use crate::db;
 use crate::error::AppError;
+use crate::models::Role;
+use crate::state::AppState;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: i64, // user id
     pub username: String,
+    /// Role when the token was issued; requests are authorized with the stored one
+    #[serde(default)]
+    pub role: Role,
     pub exp: usize,
 }
 
@@ -21,6 +27,7 @@ pub struct Claims {
 pub struct AuthUser {
     pub id: i64,
     pub username: String,
+    pub role: Role,
 }
 
 /// JWT authentication middleware
@@ -73,6 +80,7 @@ pub async fn auth_middleware(
     request.extensions_mut().insert(AuthUser {
         id: claims.sub,
         username: claims.username.clone(),
+        role: claims.role,
     });
 
     tracing::debug!("Authenticated user: {} (id: {})", claims.username, claims.sub);
@@ -80,6 +88,106 @@ pub async fn auth_middleware(
     Ok(next.run(request).await)
 }
 
+/// Role middleware, run after [`auth_middleware`]: rejects users whose role is below
+/// what [`required_role`] asks for the matched route. The role is loaded from the
+/// database, so that demoting or deleting a user applies to tokens already issued.
+pub async fn authorize(
+    State(state): State<AppState>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let id = request
+        .extensions()
+        .get::<AuthUser>()
+        .ok_or(AppError::Unauthorized)?
+        .id;
+    let user = match db::get_user_by_id(&state.db, id).await {
+        Ok(user) => AuthUser {
+            id: user.id,
+            username: user.username,
+            role: user.role,
+        },
+        Err(AppError::NotFound(_)) => {
+            tracing::warn!("Token of deleted user {} rejected", id);
+            return Err(AppError::Unauthorized);
+        }
+        Err(e) => return Err(e),
+    };
+    request.extensions_mut().insert(user.clone());
+
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str())
+        .unwrap_or_default();
+
+    let required = required_role(request.method(), route);
+    if user.role < required {
+        tracing::warn!(
+            "User {} ({}) denied {} {}",
+            user.username,
+            user.role.as_str(),
+            request.method(),
+            route
+        );
+        return Err(AppError::Forbidden(format!(
+            "This action requires the {} role",
+            required.as_str()
+        )));
+    }
+
+    Ok(next.run(request).await)
+}
+
+/// Least role allowed to call an API route, given as matched by the router. Everyone
+/// manages their own account; reading needs a viewer, starting, stopping and resending
+/// an operator. Every other route, including those of users and server credentials
+/// (which hold secrets), needs an admin, so routes missing here fail closed.
+pub fn required_role(method: &Method, route: &str) -> Role {
+    let route = route.strip_prefix("/api/v1").unwrap_or(route);
+    let read = method == Method::GET || method == Method::HEAD;
+
+    match route {
+        "/auth/me"
+        | "/auth/refresh"
+        | "/auth/update-username"
+        | "/auth/update-display-name"
+        | "/auth/update-password" => Role::Viewer,
+
+        "/servers/{id}/start"
+        | "/servers/{id}/stop"
+        | "/clients/{id}/start"
+        | "/clients/{id}/stop"
+        | "/clients/{id}/webhook/test"
+        | "/webhook-deliveries/{id}/redeliver" => Role::Operator,
+
+        "/servers"
+        | "/servers/{id}"
+        | "/servers/{id}/status"
+        | "/servers/{id}/connections"
+        | "/servers/{id}/logs"
+        | "/servers/{id}/reservations"
+        | "/servers/{id}/reservations/{reservation_id}"
+        | "/clients"
+        | "/clients/{id}"
+        | "/clients/{id}/status"
+        | "/clients/{id}/connections"
+        | "/clients/{id}/logs"
+        | "/clients/{id}/health"
+        | "/metrics"
+        | "/webhooks"
+        | "/webhooks/{id}"
+        | "/webhook-deliveries"
+        | "/webhook-deliveries/{id}"
+        | "/system/health"
+        | "/system/version"
+        | "/system/stats"
+            if read => Role::Viewer,
+
+        _ => Role::Admin,
+    }
+}
+
 /// Helper function to verify token (can be used in other places)
 pub fn verify_token(token: &str) -> Result<Claims, AppError> {
     let jwt_secret = std::env::var("JWT_SECRET")
@@ -93,3 +201,79 @@ pub fn verify_token(token: &str) -> Result<Claims, AppError> {
 
     Ok(token_data.claims)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::WebhookTestResult;
+
+    fn role(method: Method, route: &str) -> Role {
+        required_role(&method, &format!("/api/v1{}", route))
+    }
+
+    #[test]
+    fn own_account_needs_a_viewer() {
+        assert_eq!(role(Method::GET, "/auth/me"), Role::Viewer);
+        assert_eq!(role(Method::POST, "/auth/refresh"), Role::Viewer);
+        assert_eq!(role(Method::PUT, "/auth/update-password"), Role::Viewer);
+    }
+
+    #[test]
+    fn reading_needs_a_viewer() {
+        assert_eq!(role(Method::GET, "/servers"), Role::Viewer);
+        assert_eq!(role(Method::HEAD, "/servers/{id}"), Role::Viewer);
+        assert_eq!(role(Method::GET, "/clients/{id}/logs"), Role::Viewer);
+        assert_eq!(role(Method::GET, "/webhook-deliveries/{id}"), Role::Viewer);
+        assert_eq!(role(Method::GET, "/system/stats"), Role::Viewer);
+    }
+
+    #[test]
+    fn lifecycle_actions_need_an_operator() {
+        assert_eq!(role(Method::POST, "/servers/{id}/start"), Role::Operator);
+        assert_eq!(role(Method::POST, "/clients/{id}/stop"), Role::Operator);
+        assert_eq!(role(Method::POST, "/clients/{id}/webhook/test"), Role::Operator);
+        assert_eq!(role(Method::POST, "/webhook-deliveries/{id}/redeliver"), Role::Operator);
+    }
+
+    #[test]
+    fn webhook_test_masks_the_url_for_operators() {
+        let result = WebhookTestResult {
+            url: "https://hooks.example.com/services/T000/B000/token".to_string(),
+            event: "client.connected".to_string(),
+            content_type: "application/json".to_string(),
+            body: "{}".to_string(),
+            delivered: true,
+            response_status: Some(200),
+            response_body: None,
+            error: None,
+        };
+
+        let operator = role(Method::POST, "/clients/{id}/webhook/test");
+        assert_eq!(result.clone().masked_for(operator).url, "https://hooks.example.com/********");
+        assert_eq!(result.clone().masked_for(Role::Admin).url, result.url);
+    }
+
+    #[test]
+    fn changes_need_an_admin() {
+        assert_eq!(role(Method::POST, "/servers"), Role::Admin);
+        assert_eq!(role(Method::PUT, "/clients/{id}"), Role::Admin);
+        assert_eq!(role(Method::DELETE, "/webhooks/{id}"), Role::Admin);
+        assert_eq!(role(Method::POST, "/webhooks/preview"), Role::Admin);
+        assert_eq!(role(Method::PUT, "/servers/{id}/reservations/{reservation_id}"), Role::Admin);
+    }
+
+    #[test]
+    fn secrets_and_accounts_need_an_admin() {
+        assert_eq!(role(Method::GET, "/users"), Role::Admin);
+        assert_eq!(role(Method::GET, "/users/{id}"), Role::Admin);
+        assert_eq!(role(Method::GET, "/servers/{id}/credentials"), Role::Admin);
+        assert_eq!(role(Method::GET, "/servers/{id}/credentials/{credential_id}"), Role::Admin);
+    }
+
+    #[test]
+    fn unknown_routes_fail_closed() {
+        assert_eq!(role(Method::GET, "/servers/{id}/secrets"), Role::Admin);
+        assert_eq!(role(Method::GET, "/auth/me/users"), Role::Admin);
+        assert_eq!(role(Method::POST, "/servers/{id}/restart"), Role::Admin);
+    }
+}
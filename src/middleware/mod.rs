@@ -1,3 +1,3 @@
 pub mod auth;
 
-pub use auth::{auth_middleware, verify_token, AuthUser, Claims};
+pub use auth::{auth_middleware, authorize, required_role, verify_token, AuthUser, Claims};
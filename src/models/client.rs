@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 
+use super::{mask_url, Role, MASKED};
+
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct Client {
     pub id: i64,
@@ -14,15 +16,42 @@ pub struct Client {
     pub secret: Option<String>,
     pub status: ClientStatus,
     pub auto_start: bool,
+    pub auto_reconnect: bool,
+    pub reconnect_max_attempts: i64,
+    pub reconnect_max_delay: i64,
+    /// Bytes per second from the local service to public peers, 0 for unlimited
+    pub upload_limit: i64,
+    /// Bytes per second from public peers to the local service, 0 for unlimited
+    pub download_limit: i64,
     pub webhook_url: Option<String>,
     pub webhook_format: String,
     pub webhook_template: Option<String>,
+    /// Signs payloads with HMAC-SHA256 when set
+    pub webhook_secret: Option<String>,
+    pub health_check_enabled: bool,
+    /// Seconds between probes of the local target
+    pub health_check_interval: i64,
+    /// When set, the probe is an HTTP GET of this path rather than a TCP connect
+    pub health_check_path: Option<String>,
     pub created_at: String,
     pub updated_at: String,
     pub last_connected_at: Option<String>,
     pub error_message: Option<String>,
 }
 
+impl Client {
+    /// The client as shown to a user of `role`. Below admin, its shared secret, its
+    /// webhook signing secret and its webhook URL, which may carry a token, are masked.
+    pub fn masked_for(mut self, role: Role) -> Self {
+        if role < Role::Admin {
+            self.secret = self.secret.map(|_| MASKED.to_string());
+            self.webhook_secret = self.webhook_secret.map(|_| MASKED.to_string());
+            self.webhook_url = self.webhook_url.map(|url| mask_url(&url));
+        }
+        self
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, sqlx::Type)]
 #[sqlx(rename_all = "lowercase")]
 #[serde(rename_all = "lowercase")]
@@ -30,6 +59,7 @@ pub enum ClientStatus {
     Stopped,
     Starting,
     Connected,
+    Reconnecting,
     Error,
 }
 
@@ -46,10 +76,27 @@ pub struct CreateClient {
     pub secret: Option<String>,
     #[serde(default)]
     pub auto_start: bool,
+    #[serde(default = "default_auto_reconnect")]
+    pub auto_reconnect: bool,
+    #[serde(default)]
+    pub reconnect_max_attempts: i64,
+    #[serde(default = "default_reconnect_max_delay")]
+    pub reconnect_max_delay: i64,
+    #[serde(default)]
+    pub upload_limit: i64,
+    #[serde(default)]
+    pub download_limit: i64,
     pub webhook_url: Option<String>,
     #[serde(default = "default_webhook_format")]
     pub webhook_format: String,
     pub webhook_template: Option<String>,
+    /// Signs payloads with HMAC-SHA256 when set
+    pub webhook_secret: Option<String>,
+    #[serde(default)]
+    pub health_check_enabled: bool,
+    #[serde(default = "default_health_check_interval")]
+    pub health_check_interval: i64,
+    pub health_check_path: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -62,15 +109,48 @@ pub struct UpdateClient {
     pub remote_port: Option<i64>,
     pub secret: Option<String>,
     pub auto_start: Option<bool>,
+    pub auto_reconnect: Option<bool>,
+    pub reconnect_max_attempts: Option<i64>,
+    pub reconnect_max_delay: Option<i64>,
+    pub upload_limit: Option<i64>,
+    pub download_limit: Option<i64>,
     pub webhook_url: Option<String>,
     pub webhook_format: Option<String>,
     pub webhook_template: Option<String>,
+    /// Signs payloads with HMAC-SHA256 when set
+    pub webhook_secret: Option<String>,
+    pub health_check_enabled: Option<bool>,
+    pub health_check_interval: Option<i64>,
+    /// Empty string switches back to a TCP probe
+    pub health_check_path: Option<String>,
+}
+
+/// A change of a client's local target between healthy and down
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ClientHealthEvent {
+    pub id: i64,
+    pub client_id: i64,
+    pub healthy: bool,
+    pub message: Option<String>,
+    pub checked_at: String,
 }
 
 fn default_local_host() -> String {
     "localhost".to_string()
 }
 
+fn default_auto_reconnect() -> bool {
+    true
+}
+
+fn default_reconnect_max_delay() -> i64 {
+    60
+}
+
+fn default_health_check_interval() -> i64 {
+    30
+}
+
 fn default_webhook_format() -> String {
     "json".to_string()
 }
@@ -0,0 +1,77 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+use super::SessionType;
+
+/// A public connection carried by a server's or client's tunnel
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct TunnelConnection {
+    pub id: i64,
+    pub tunnel_type: SessionType,
+    pub entity_id: i64,
+    /// Public port of the tunnel
+    pub port: i64,
+    /// Address of the public peer; only servers see it
+    pub peer: Option<String>,
+    pub started_at: String,
+    /// Unknown for connections cut off by a restart
+    pub ended_at: Option<String>,
+    pub bytes_sent: i64,
+    pub bytes_received: i64,
+    /// `None` while the connection is open
+    pub close_reason: Option<CloseReason>,
+    pub error_message: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, sqlx::Type)]
+#[sqlx(rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum CloseReason {
+    /// Both sides finished normally
+    Closed,
+    /// Cut off when the tunnel was stopped
+    Shutdown,
+    /// Failed with an I/O error
+    Error,
+    /// Never picked up by the client
+    Unclaimed,
+    /// Still open when the previous run of the application exited
+    Interrupted,
+}
+
+impl CloseReason {
+    pub fn as_str(&self) -> &str {
+        match self {
+            CloseReason::Closed => "closed",
+            CloseReason::Shutdown => "shutdown",
+            CloseReason::Error => "error",
+            CloseReason::Unclaimed => "unclaimed",
+            CloseReason::Interrupted => "interrupted",
+        }
+    }
+}
+
+/// Filters and paging of the connection log endpoints
+#[derive(Debug, Default, Deserialize)]
+pub struct ConnectionQuery {
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+    /// Matches peers starting with this address
+    pub peer: Option<String>,
+    pub port: Option<i64>,
+    pub close_reason: Option<CloseReason>,
+    /// Only open (`true`) or only finished (`false`) connections
+    pub open: Option<bool>,
+    /// Started at or after this time
+    pub since: Option<String>,
+    /// Started before this time
+    pub until: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConnectionPage {
+    pub items: Vec<TunnelConnection>,
+    pub total: i64,
+    pub page: i64,
+    pub per_page: i64,
+}
@@ -0,0 +1,42 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+/// A named secret that lets one client open tunnels on a bore server
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ServerCredential {
+    pub id: i64,
+    pub server_id: i64,
+    pub name: String,
+    pub secret: String,
+    pub enabled: bool,
+    pub port_range_start: Option<i64>,
+    pub port_range_end: Option<i64>,
+    pub expires_at: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateServerCredential {
+    pub name: String,
+    pub secret: String,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    pub port_range_start: Option<i64>,
+    pub port_range_end: Option<i64>,
+    pub expires_at: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateServerCredential {
+    pub name: Option<String>,
+    pub secret: Option<String>,
+    pub enabled: Option<bool>,
+    pub port_range_start: Option<i64>,
+    pub port_range_end: Option<i64>,
+    pub expires_at: Option<String>,
+}
+
+fn default_enabled() -> bool {
+    true
+}
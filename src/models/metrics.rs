@@ -0,0 +1,63 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+use super::SessionType;
+
+/// Granularity of stored traffic samples
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, sqlx::Type)]
+#[sqlx(rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum Resolution {
+    /// One sample per sampling interval
+    Raw,
+    Minute,
+    Hour,
+    Day,
+}
+
+impl Resolution {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Resolution::Raw => "raw",
+            Resolution::Minute => "minute",
+            Resolution::Hour => "hour",
+            Resolution::Day => "day",
+        }
+    }
+}
+
+/// Traffic of one tunnel in one time bucket
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct MetricPoint {
+    /// Start of the bucket, UTC
+    pub time: String,
+    pub bytes_sent: i64,
+    pub bytes_received: i64,
+    /// Connections opened in the bucket
+    pub connections: i64,
+    /// Peak of concurrently open connections
+    pub active_connections: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MetricsQuery {
+    /// `server:<id>` or `client:<id>`
+    pub entity: String,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    /// Bucket size in seconds
+    pub step: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MetricsResponse {
+    pub entity_type: SessionType,
+    pub entity_id: i64,
+    pub from: String,
+    pub to: String,
+    /// Bucket size actually used, at least the size of `resolution`
+    pub step: i64,
+    /// Stored samples the buckets were computed from
+    pub resolution: Resolution,
+    pub points: Vec<MetricPoint>,
+}
@@ -1,9 +1,19 @@
 pub mod server;
 pub mod client;
+pub mod credential;
 pub mod session;
+pub mod connection;
+pub mod metrics;
+pub mod reservation;
 pub mod user;
+pub mod webhook;
 
-pub use server::{Server, CreateServer, UpdateServer, ServerStatus};
-pub use client::{Client, CreateClient, UpdateClient, ClientStatus};
+pub use server::{Server, ServerDetail, CreateServer, UpdateServer, ServerStatus};
+pub use client::{Client, CreateClient, UpdateClient, ClientStatus, ClientHealthEvent};
+pub use credential::{ServerCredential, CreateServerCredential, UpdateServerCredential};
+pub use reservation::{PortReservation, CreatePortReservation, UpdatePortReservation};
 pub use session::{Session, SessionType, SessionStats};
-pub use user::{User, CreateUser, LoginRequest, LoginResponse, TokenRefreshResponse, UserInfo, UpdateUsernameRequest, UpdateDisplayNameRequest, UpdatePasswordRequest};
+pub use connection::{TunnelConnection, CloseReason, ConnectionQuery, ConnectionPage};
+pub use metrics::{Resolution, MetricPoint, MetricsQuery, MetricsResponse};
+pub use webhook::{MASKED, mask_url, WebhookAuth, Webhook, CreateWebhook, UpdateWebhook, WebhookTarget, WebhookPreviewRequest, WebhookPreview, WebhookTestRequest, WebhookTestResult, WebhookDelivery, NewWebhookDelivery, DeliveryStatus, DeliveryQuery, DeliveryPage};
+pub use user::{Role, User, CreateUser, UpdateUser, LoginRequest, LoginResponse, TokenRefreshResponse, UserInfo, UpdateUsernameRequest, UpdateDisplayNameRequest, UpdatePasswordRequest};
@@ -0,0 +1,34 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+/// A port on a bore server that only its owner may open a tunnel on
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct PortReservation {
+    pub id: i64,
+    pub server_id: i64,
+    /// Name of the server credential that owns the port
+    pub owner: String,
+    pub port: i64,
+    pub description: Option<String>,
+    /// Overrides the server's allow list for this port when either list is set
+    pub allow_cidrs: Option<String>,
+    pub deny_cidrs: Option<String>,
+    pub created_at: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreatePortReservation {
+    pub owner: String,
+    pub port: i64,
+    pub description: Option<String>,
+    pub allow_cidrs: Option<String>,
+    pub deny_cidrs: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdatePortReservation {
+    pub owner: Option<String>,
+    pub description: Option<String>,
+    pub allow_cidrs: Option<String>,
+    pub deny_cidrs: Option<String>,
+}
@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 
+use super::{mask_url, PortReservation, Role, MASKED};
+
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct Server {
     pub id: i64,
@@ -8,9 +10,23 @@ pub struct Server {
     pub description: Option<String>,
     pub bind_addr: String,
     pub bind_tunnels: String,
+    pub control_port: i64,
     pub port_range_start: i64,
     pub port_range_end: i64,
     pub secret: Option<String>,
+    /// Comma separated CIDRs allowed to reach tunnel ports; empty allows everyone
+    pub allow_cidrs: String,
+    /// Comma separated CIDRs refused on tunnel ports, taking precedence over the allow list
+    pub deny_cidrs: String,
+    /// Default bytes per second each tunnel may send to public peers, 0 for unlimited
+    pub upload_limit: i64,
+    /// Default bytes per second each tunnel may receive from public peers, 0 for unlimited
+    pub download_limit: i64,
+    pub webhook_url: Option<String>,
+    pub webhook_format: String,
+    pub webhook_template: Option<String>,
+    /// Signs payloads with HMAC-SHA256 when set
+    pub webhook_secret: Option<String>,
     pub status: ServerStatus,
     pub auto_start: bool,
     pub created_at: String,
@@ -19,6 +35,27 @@ pub struct Server {
     pub error_message: Option<String>,
 }
 
+impl Server {
+    /// The server as shown to a user of `role`. Below admin, its shared secret, its
+    /// webhook signing secret and its webhook URL, which may carry a token, are masked.
+    pub fn masked_for(mut self, role: Role) -> Self {
+        if role < Role::Admin {
+            self.secret = self.secret.map(|_| MASKED.to_string());
+            self.webhook_secret = self.webhook_secret.map(|_| MASKED.to_string());
+            self.webhook_url = self.webhook_url.map(|url| mask_url(&url));
+        }
+        self
+    }
+}
+
+/// Server along with the ports reserved on it
+#[derive(Debug, Serialize)]
+pub struct ServerDetail {
+    #[serde(flatten)]
+    pub server: Server,
+    pub reservations: Vec<PortReservation>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, sqlx::Type)]
 #[sqlx(rename_all = "lowercase")]
 #[serde(rename_all = "lowercase")]
@@ -37,12 +74,28 @@ pub struct CreateServer {
     pub bind_addr: String,
     #[serde(default = "default_bind_tunnels")]
     pub bind_tunnels: String,
+    #[serde(default = "default_control_port")]
+    pub control_port: i64,
     #[serde(default = "default_port_start")]
     pub port_range_start: i64,
     #[serde(default = "default_port_end")]
     pub port_range_end: i64,
     pub secret: Option<String>,
     #[serde(default)]
+    pub allow_cidrs: String,
+    #[serde(default)]
+    pub deny_cidrs: String,
+    #[serde(default)]
+    pub upload_limit: i64,
+    #[serde(default)]
+    pub download_limit: i64,
+    pub webhook_url: Option<String>,
+    #[serde(default = "default_webhook_format")]
+    pub webhook_format: String,
+    pub webhook_template: Option<String>,
+    /// Signs payloads with HMAC-SHA256 when set
+    pub webhook_secret: Option<String>,
+    #[serde(default)]
     pub auto_start: bool,
 }
 
@@ -52,9 +105,20 @@ pub struct UpdateServer {
     pub description: Option<String>,
     pub bind_addr: Option<String>,
     pub bind_tunnels: Option<String>,
+    pub control_port: Option<i64>,
     pub port_range_start: Option<i64>,
     pub port_range_end: Option<i64>,
     pub secret: Option<String>,
+    pub allow_cidrs: Option<String>,
+    pub deny_cidrs: Option<String>,
+    pub upload_limit: Option<i64>,
+    pub download_limit: Option<i64>,
+    /// An empty string removes the webhook
+    pub webhook_url: Option<String>,
+    pub webhook_format: Option<String>,
+    pub webhook_template: Option<String>,
+    /// Signs payloads with HMAC-SHA256 when set
+    pub webhook_secret: Option<String>,
     pub auto_start: Option<bool>,
 }
 
@@ -66,6 +130,10 @@ fn default_bind_tunnels() -> String {
     "0.0.0.0".to_string()
 }
 
+fn default_control_port() -> i64 {
+    bore_cli::shared::CONTROL_PORT as i64
+}
+
 fn default_port_start() -> i64 {
     1024
 }
@@ -73,3 +141,7 @@ fn default_port_start() -> i64 {
 fn default_port_end() -> i64 {
     65535
 }
+
+fn default_webhook_format() -> String {
+    "json".to_string()
+}
@@ -11,9 +11,10 @@ pub struct Session {
     pub connection_count: i64,
     pub bytes_sent: i64,
     pub bytes_received: i64,
+    pub ended_at: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, sqlx::Type)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash, sqlx::Type)]
 #[sqlx(rename_all = "lowercase")]
 #[serde(rename_all = "lowercase")]
 pub enum SessionType {
@@ -21,6 +22,15 @@ pub enum SessionType {
     Client,
 }
 
+impl SessionType {
+    pub fn as_str(&self) -> &str {
+        match self {
+            SessionType::Server => "server",
+            SessionType::Client => "client",
+        }
+    }
+}
+
 #[derive(Debug, Serialize)]
 pub struct SessionStats {
     pub connection_count: i64,
@@ -1,6 +1,31 @@
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 
+/// What a user may do, each role including the ones below it
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, sqlx::Type)]
+#[sqlx(rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Reads everything except secrets
+    #[default]
+    Viewer,
+    /// Also starts and stops servers and clients
+    Operator,
+    /// Also creates, changes and deletes servers, clients, webhooks and users, and
+    /// sees their secrets
+    Admin,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Role::Viewer => "viewer",
+            Role::Operator => "operator",
+            Role::Admin => "admin",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct User {
     pub id: i64,
@@ -8,6 +33,7 @@ pub struct User {
     #[serde(skip_serializing)]
     pub password_hash: String,
     pub display_name: Option<String>,
+    pub role: Role,
     pub created_at: String,
     pub updated_at: String,
 }
@@ -16,6 +42,15 @@ pub struct User {
 pub struct CreateUser {
     pub username: String,
     pub password: String,
+    #[serde(default)]
+    pub role: Role,
+}
+
+/// Changes an admin makes to another user; omitted fields are kept
+#[derive(Debug, Deserialize)]
+pub struct UpdateUser {
+    pub role: Option<Role>,
+    pub password: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -40,6 +75,18 @@ pub struct UserInfo {
     pub id: i64,
     pub username: String,
     pub display_name: Option<String>,
+    pub role: Role,
+}
+
+impl From<User> for UserInfo {
+    fn from(user: User) -> Self {
+        Self {
+            id: user.id,
+            username: user.username,
+            display_name: user.display_name,
+            role: user.role,
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
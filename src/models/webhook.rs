@@ -0,0 +1,308 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use std::collections::BTreeMap;
+
+use super::{Role, SessionType};
+
+/// Shown in place of header values and secrets; sending it back on update keeps the
+/// stored value
+pub const MASKED: &str = "********";
+
+/// A webhook subscription: where to send which events of which servers and clients
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Webhook {
+    pub id: i64,
+    pub name: String,
+    pub url: String,
+    /// `POST`, `PUT` or `PATCH`
+    pub method: String,
+    pub format: String,
+    pub template: Option<String>,
+    /// Extra request headers
+    #[sqlx(json)]
+    pub headers: BTreeMap<String, String>,
+    /// Signs payloads with HMAC-SHA256 when set
+    pub secret: Option<String>,
+    /// Events subscribed to, such as `connected` or `target_down`
+    #[sqlx(json)]
+    pub events: Vec<String>,
+    pub enabled: bool,
+    #[sqlx(json(nullable))]
+    pub auth: Option<WebhookAuth>,
+    /// `server:<id>` and `client:<id>`, or `server:*` and `client:*` for all of them
+    #[sqlx(skip)]
+    pub targets: Vec<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+impl Webhook {
+    /// The webhook as shown by the API, with header values and secrets masked
+    pub fn masked(mut self) -> Self {
+        for value in self.headers.values_mut() {
+            *value = MASKED.to_string();
+        }
+        if self.secret.is_some() {
+            self.secret = Some(MASKED.to_string());
+        }
+        self.auth = self.auth.map(|auth| match auth {
+            WebhookAuth::Basic { username, .. } => WebhookAuth::Basic {
+                username,
+                password: MASKED.to_string(),
+            },
+            WebhookAuth::Bearer { .. } => WebhookAuth::Bearer {
+                token: MASKED.to_string(),
+            },
+            WebhookAuth::None => WebhookAuth::None,
+        });
+        self
+    }
+
+    /// The webhook as shown to a user of `role`; below admin, its URL is masked too
+    pub fn masked_for(self, role: Role) -> Self {
+        let mut webhook = self.masked();
+        if role < Role::Admin {
+            webhook.url = mask_url(&webhook.url);
+        }
+        webhook
+    }
+}
+
+/// Credentials sent with each request of a webhook
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum WebhookAuth {
+    /// Only used to remove authentication on update
+    None,
+    Basic { username: String, password: String },
+    Bearer { token: String },
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateWebhook {
+    pub name: String,
+    pub url: String,
+    #[serde(default = "default_method")]
+    pub method: String,
+    #[serde(default = "default_format")]
+    pub format: String,
+    pub template: Option<String>,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    pub secret: Option<String>,
+    /// Every event when omitted
+    pub events: Option<Vec<String>>,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    pub auth: Option<WebhookAuth>,
+    /// Every server and client when omitted
+    pub targets: Option<Vec<String>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateWebhook {
+    pub name: Option<String>,
+    pub url: Option<String>,
+    pub method: Option<String>,
+    pub format: Option<String>,
+    /// An empty string removes the template
+    pub template: Option<String>,
+    /// Replaces all extra headers
+    pub headers: Option<BTreeMap<String, String>>,
+    /// An empty string stops signing
+    pub secret: Option<String>,
+    pub events: Option<Vec<String>>,
+    pub enabled: Option<bool>,
+    /// `{"type": "none"}` removes authentication
+    pub auth: Option<WebhookAuth>,
+    pub targets: Option<Vec<String>>,
+}
+
+/// Webhook settings and event to render a preview of
+#[derive(Debug, Deserialize)]
+pub struct WebhookPreviewRequest {
+    #[serde(default = "default_format")]
+    pub format: String,
+    pub template: Option<String>,
+    /// Needed by formats taking details from the URL, such as Telegram's `chat_id`
+    pub url: Option<String>,
+    #[serde(default = "default_event")]
+    pub event: String,
+    /// `server:<id>` or `client:<id>` to render with, a sample client when omitted
+    pub entity: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WebhookPreview {
+    pub content_type: String,
+    pub body: String,
+}
+
+/// Event sent by a webhook test
+#[derive(Debug, Default, Deserialize)]
+pub struct WebhookTestRequest {
+    pub event: Option<String>,
+}
+
+/// What a test webhook sent and how the receiver answered
+#[derive(Debug, Clone, Serialize)]
+pub struct WebhookTestResult {
+    pub url: String,
+    pub event: String,
+    pub content_type: String,
+    pub body: String,
+    pub delivered: bool,
+    pub response_status: Option<u16>,
+    /// Start of the receiver's response
+    pub response_body: Option<String>,
+    pub error: Option<String>,
+}
+
+impl WebhookTestResult {
+    /// The result as shown to a user of `role`; below admin, the URL is masked
+    pub fn masked_for(mut self, role: Role) -> Self {
+        if role < Role::Admin {
+            self.url = mask_url(&self.url);
+        }
+        self
+    }
+}
+
+/// Server or client a webhook reports on; no id stands for all of its type
+pub type WebhookTarget = (SessionType, Option<i64>);
+
+fn default_format() -> String {
+    "json".to_string()
+}
+
+fn default_method() -> String {
+    "POST".to_string()
+}
+
+fn default_event() -> String {
+    "connected".to_string()
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// A webhook rendered for an event, along with the state of its delivery
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct WebhookDelivery {
+    pub id: i64,
+    pub entity_type: SessionType,
+    pub entity_id: i64,
+    /// Event name such as `client.connected`
+    pub event: String,
+    pub method: String,
+    pub url: String,
+    pub content_type: String,
+    pub body: String,
+    pub status: DeliveryStatus,
+    pub attempts: i64,
+    /// When a pending delivery is tried next
+    pub next_attempt_at: String,
+    pub last_attempt_at: Option<String>,
+    /// HTTP status the receiver answered the last attempt with
+    pub response_status: Option<i64>,
+    /// Start of the receiver's last response
+    pub response_body: Option<String>,
+    /// Why the last attempt failed
+    pub error: Option<String>,
+    pub created_at: String,
+    pub delivered_at: Option<String>,
+    /// Secret the payload is signed with on each attempt
+    #[serde(skip_serializing)]
+    pub signing_secret: Option<String>,
+    /// Subscription the delivery was queued for, none for the entity's own webhook
+    pub webhook_id: Option<i64>,
+    /// Extra request headers sent on each attempt, including credentials
+    #[serde(skip_serializing)]
+    #[sqlx(json)]
+    pub headers: BTreeMap<String, String>,
+}
+
+impl WebhookDelivery {
+    /// The delivery as shown by the API, with its URL masked
+    pub fn masked(mut self) -> Self {
+        self.url = mask_url(&self.url);
+        self
+    }
+}
+
+/// A webhook URL with only its scheme, host and port kept, since chat services take
+/// their token in the path or query
+pub fn mask_url(url: &str) -> String {
+    match url::Url::parse(url) {
+        Ok(url) if url.has_host() => {
+            let origin = url.origin().ascii_serialization();
+            let bare = url.username().is_empty()
+                && url.password().is_none()
+                && url.path() == "/"
+                && url.query().is_none();
+            if bare {
+                format!("{}/", origin)
+            } else {
+                format!("{}/{}", origin, MASKED)
+            }
+        }
+        _ => MASKED.to_string(),
+    }
+}
+
+/// A rendered webhook to queue
+#[derive(Debug, Clone)]
+pub struct NewWebhookDelivery {
+    pub entity_type: SessionType,
+    pub entity_id: i64,
+    pub event: String,
+    pub method: String,
+    pub url: String,
+    pub content_type: String,
+    pub body: String,
+    pub signing_secret: Option<String>,
+    pub webhook_id: Option<i64>,
+    pub headers: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, sqlx::Type)]
+#[sqlx(rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum DeliveryStatus {
+    /// Waiting for its first or next attempt
+    Pending,
+    Delivered,
+    /// Given up on, after a permanent error or running out of attempts
+    Failed,
+}
+
+impl DeliveryStatus {
+    pub fn as_str(&self) -> &str {
+        match self {
+            DeliveryStatus::Pending => "pending",
+            DeliveryStatus::Delivered => "delivered",
+            DeliveryStatus::Failed => "failed",
+        }
+    }
+}
+
+/// Filters and paging of `GET /webhook-deliveries`
+#[derive(Debug, Default, Deserialize)]
+pub struct DeliveryQuery {
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+    pub status: Option<DeliveryStatus>,
+    /// `server:<id>` or `client:<id>`
+    pub entity: Option<String>,
+    pub event: Option<String>,
+    pub webhook_id: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DeliveryPage {
+    pub items: Vec<WebhookDelivery>,
+    pub total: i64,
+    pub page: i64,
+    pub per_page: i64,
+}
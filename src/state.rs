@@ -1,23 +1,41 @@
+use crate::config::Config;
+use crate::logs::TunnelLogs;
+use crate::telemetry::HttpMetrics;
 use crate::tunnel::{ServerManager, ClientManager};
+use crate::webhook::WebhookSender;
 use crate::ws::WsBroadcaster;
+use anyhow::Context;
 use sqlx::SqlitePool;
 use std::sync::Arc;
 
 #[derive(Clone)]
 pub struct AppState {
     pub db: SqlitePool,
+    pub config: Arc<Config>,
     pub server_manager: Arc<ServerManager>,
     pub client_manager: Arc<ClientManager>,
     pub ws_broadcaster: Arc<WsBroadcaster>,
+    pub http_metrics: Arc<HttpMetrics>,
+    pub tunnel_logs: Arc<TunnelLogs>,
+    /// Sends webhooks to the destinations `WEBHOOK_ALLOWED_HOSTS` permits
+    pub webhook_sender: WebhookSender,
 }
 
 impl AppState {
-    pub fn new(db: SqlitePool) -> Self {
-        Self {
+    pub fn new(db: SqlitePool, config: Config, tunnel_logs: Arc<TunnelLogs>) -> anyhow::Result<Self> {
+        let ws_broadcaster = Arc::new(WsBroadcaster::new());
+        let webhook_sender = WebhookSender::new(Arc::new(config.webhook_allowed_hosts.clone()))
+            .context("Failed to build the webhook HTTP client")?;
+
+        Ok(Self {
+            server_manager: Arc::new(ServerManager::new(db.clone(), ws_broadcaster.clone())),
+            client_manager: Arc::new(ClientManager::new(db.clone(), ws_broadcaster.clone())),
+            ws_broadcaster,
+            http_metrics: Arc::new(HttpMetrics::new()),
+            tunnel_logs,
+            webhook_sender,
+            config: Arc::new(config),
             db,
-            server_manager: Arc::new(ServerManager::new()),
-            client_manager: Arc::new(ClientManager::new()),
-            ws_broadcaster: Arc::new(WsBroadcaster::new()),
-        }
+        })
     }
 }
@@ -0,0 +1,223 @@
+//! Prometheus metrics in the text exposition format, served on `/metrics`.
+
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use crate::db;
+use crate::error::{AppError, Result};
+use crate::state::AppState;
+use crate::tunnel::TrafficInfo;
+use crate::webhook::delivery_counts;
+
+/// Upper bounds of the request latency buckets, in seconds
+const LATENCY_BUCKETS: [f64; 11] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+/// Name, type, help text and value of a per-tunnel traffic metric
+type TrafficMetric = (&'static str, &'static str, &'static str, fn(&TrafficInfo) -> u64);
+
+const TRAFFIC_METRICS: [TrafficMetric; 5] = [
+    ("borui_tunnel_active_connections", "gauge", "Connections currently open", |t| t.active_connections),
+    ("borui_tunnel_connections_total", "counter", "Connections accepted since the tunnel started", |t| t.total_connections),
+    ("borui_tunnel_rejected_connections_total", "counter", "Connections refused by source rules since the tunnel started", |t| t.rejected_connections),
+    ("borui_tunnel_sent_bytes_total", "counter", "Bytes sent to public peers since the tunnel started", |t| t.bytes_sent),
+    ("borui_tunnel_received_bytes_total", "counter", "Bytes received from public peers since the tunnel started", |t| t.bytes_received),
+];
+
+/// Latency histograms of API requests, keyed by method, route and status
+#[derive(Debug, Default)]
+pub struct HttpMetrics {
+    requests: Mutex<BTreeMap<(String, String, u16), Histogram>>,
+}
+
+#[derive(Debug, Default)]
+struct Histogram {
+    /// Observations per bucket, not cumulative
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+impl HttpMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn observe(&self, method: &str, route: &str, status: u16, seconds: f64) {
+        let mut requests = self.requests.lock().unwrap_or_else(|e| e.into_inner());
+        let histogram = requests
+            .entry((method.to_string(), route.to_string(), status))
+            .or_default();
+
+        if let Some(bucket) = LATENCY_BUCKETS.iter().position(|bound| seconds <= *bound) {
+            histogram.buckets[bucket] += 1;
+        }
+        histogram.count += 1;
+        histogram.sum += seconds;
+    }
+
+    fn render(&self, out: &mut String) {
+        header(out, "borui_http_request_duration_seconds", "histogram", "Latency of API requests");
+
+        let requests = self.requests.lock().unwrap_or_else(|e| e.into_inner());
+        for ((method, route, status), histogram) in requests.iter() {
+            let labels = format!(
+                "method=\"{}\",route=\"{}\",status=\"{}\"",
+                escape(method),
+                escape(route),
+                status
+            );
+
+            let mut cumulative = 0;
+            for (bound, count) in LATENCY_BUCKETS.iter().zip(histogram.buckets) {
+                cumulative += count;
+                let _ = writeln!(out, "borui_http_request_duration_seconds_bucket{{{},le=\"{}\"}} {}", labels, bound, cumulative);
+            }
+            let _ = writeln!(out, "borui_http_request_duration_seconds_bucket{{{},le=\"+Inf\"}} {}", labels, histogram.count);
+            let _ = writeln!(out, "borui_http_request_duration_seconds_sum{{{}}} {}", labels, histogram.sum);
+            let _ = writeln!(out, "borui_http_request_duration_seconds_count{{{}}} {}", labels, histogram.count);
+        }
+    }
+}
+
+/// Middleware timing API requests. Requests are labelled with their route template,
+/// so ids in paths do not create new series.
+pub async fn track_http_metrics(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let method = request.method().to_string();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| "unmatched".to_string());
+
+    let started = Instant::now();
+    let response = next.run(request).await;
+
+    state.http_metrics.observe(
+        &method,
+        &route,
+        response.status().as_u16(),
+        started.elapsed().as_secs_f64(),
+    );
+
+    response
+}
+
+/// `GET /metrics`, protected by `METRICS_TOKEN` as a bearer token when it is set
+pub async fn metrics_handler(State(state): State<AppState>, headers: HeaderMap) -> Result<Response> {
+    if let Some(token) = &state.config.metrics_token {
+        let provided = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+        if !provided.is_some_and(|provided| constant_time_eq(provided.as_bytes(), token.as_bytes())) {
+            return Err(AppError::Unauthorized);
+        }
+    }
+
+    let body = render(&state).await?;
+    Ok((
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4; charset=utf-8")],
+        body,
+    )
+        .into_response())
+}
+
+async fn render(state: &AppState) -> Result<String> {
+    let mut out = String::new();
+
+    let servers = db::list_servers(&state.db).await?;
+    let clients = db::list_clients(&state.db).await?;
+    let server_names: HashMap<i64, &str> = servers.iter().map(|s| (s.id, s.name.as_str())).collect();
+    let client_names: HashMap<i64, &str> = clients.iter().map(|c| (c.id, c.name.as_str())).collect();
+
+    let server_statuses = state.server_manager.list_statuses();
+    let client_statuses = state.client_manager.list_statuses();
+
+    header(&mut out, "borui_servers", "gauge", "Configured servers");
+    let _ = writeln!(out, "borui_servers {}", servers.len());
+    header(&mut out, "borui_clients", "gauge", "Configured clients");
+    let _ = writeln!(out, "borui_clients {}", clients.len());
+    header(&mut out, "borui_servers_running", "gauge", "Servers currently running");
+    let _ = writeln!(out, "borui_servers_running {}", server_statuses.len());
+    header(&mut out, "borui_clients_connected", "gauge", "Clients currently connected to their server");
+    let _ = writeln!(
+        out,
+        "borui_clients_connected {}",
+        client_statuses.iter().filter(|status| status.status == "connected").count()
+    );
+
+    // Per-tunnel traffic, labelled by kind, id and name
+    let tunnels: Vec<(String, &TrafficInfo)> = server_statuses
+        .iter()
+        .map(|status| (tunnel_labels("server", status.id, server_names.get(&status.id)), &status.traffic))
+        .chain(client_statuses.iter().map(|status| {
+            (tunnel_labels("client", status.id, client_names.get(&status.id)), &status.traffic)
+        }))
+        .collect();
+
+    for (name, kind, help, value) in TRAFFIC_METRICS {
+        header(&mut out, name, kind, help);
+        for (labels, traffic) in &tunnels {
+            let _ = writeln!(out, "{}{{{}}} {}", name, labels, value(traffic));
+        }
+    }
+
+    header(&mut out, "borui_client_reconnect_attempts", "gauge", "Reconnect attempts in the client's current outage");
+    for status in &client_statuses {
+        let labels = tunnel_labels("client", status.id, client_names.get(&status.id));
+        let _ = writeln!(out, "borui_client_reconnect_attempts{{{}}} {}", labels, status.reconnect_attempts);
+    }
+
+    header(&mut out, "borui_client_target_healthy", "gauge", "Whether the client's local target passed its last health check");
+    for status in &client_statuses {
+        if let Some(healthy) = status.target_healthy {
+            let labels = tunnel_labels("client", status.id, client_names.get(&status.id));
+            let _ = writeln!(out, "borui_client_target_healthy{{{}}} {}", labels, healthy as u8);
+        }
+    }
+
+    let (delivered, failed) = delivery_counts();
+    header(&mut out, "borui_webhook_deliveries_total", "counter", "Webhook deliveries since startup");
+    let _ = writeln!(out, "borui_webhook_deliveries_total{{result=\"success\"}} {}", delivered);
+    let _ = writeln!(out, "borui_webhook_deliveries_total{{result=\"failure\"}} {}", failed);
+
+    state.http_metrics.render(&mut out);
+
+    Ok(out)
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn tunnel_labels(kind: &str, id: i64, name: Option<&&str>) -> String {
+    format!(
+        "type=\"{}\",id=\"{}\",name=\"{}\"",
+        kind,
+        id,
+        escape(name.copied().unwrap_or_default())
+    )
+}
+
+/// Escape a label value as the exposition format requires
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
@@ -0,0 +1,29 @@
+//! Timestamps as accepted by the API and stored in SQLite.
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+
+use crate::error::{AppError, Result};
+
+/// Format SQLite uses for `CURRENT_TIMESTAMP`, in UTC
+pub const DB_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Parse a timestamp given either as RFC 3339 or in SQLite's UTC format
+pub fn parse(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|dt| dt.with_timezone(&Utc))
+        .or_else(|_| NaiveDateTime::parse_from_str(value, DB_FORMAT).map(|dt| dt.and_utc()))
+        .ok()
+}
+
+/// Format a timestamp like SQLite stores them, so that they compare as strings
+pub fn to_db(time: DateTime<Utc>) -> String {
+    time.format(DB_FORMAT).to_string()
+}
+
+/// Parse a timestamp field of a request
+pub fn parse_field(field: &str, value: &str) -> Result<DateTime<Utc>> {
+    parse(value).ok_or_else(|| AppError::BadRequest(format!(
+        "Invalid {} '{}', expected RFC 3339 or YYYY-MM-DD HH:MM:SS (UTC)",
+        field, value
+    )))
+}
@@ -0,0 +1,143 @@
+//! Source address rules for public connections to tunnel ports.
+
+use ipnet::IpNet;
+use std::net::IpAddr;
+use std::sync::RwLock;
+
+/// Allow and deny lists applied to the peer of every accepted public connection.
+///
+/// Deny rules win over allow rules; an empty allow list admits every address that is
+/// not denied.
+#[derive(Debug, Clone, Default)]
+pub struct AccessRules {
+    allow: Vec<IpNet>,
+    deny: Vec<IpNet>,
+}
+
+impl AccessRules {
+    /// Build rules from the comma separated lists stored on servers and reservations
+    pub fn parse(allow: &str, deny: &str) -> Result<Self, String> {
+        Ok(Self {
+            allow: parse_cidrs(allow)?,
+            deny: parse_cidrs(deny)?,
+        })
+    }
+
+    /// Rules that refuse every address
+    pub fn deny_all() -> Self {
+        Self {
+            allow: Vec::new(),
+            deny: vec![IpNet::V4(Default::default()), IpNet::V6(Default::default())],
+        }
+    }
+
+    /// Check a peer address, returning why it was rejected
+    pub fn check(&self, ip: IpAddr) -> Result<(), String> {
+        // Treat IPv4-mapped IPv6 peers of dual-stack listeners as IPv4
+        let ip = ip.to_canonical();
+
+        if let Some(rule) = self.deny.iter().find(|net| net.contains(&ip)) {
+            return Err(format!("denied by {}", rule));
+        }
+        if !self.allow.is_empty() && !self.allow.iter().any(|net| net.contains(&ip)) {
+            return Err("not in allow list".to_string());
+        }
+
+        Ok(())
+    }
+}
+
+/// Access rules of one server, replaceable while it runs
+#[derive(Debug, Default)]
+pub struct AccessStore {
+    rules: RwLock<AccessRules>,
+}
+
+impl AccessStore {
+    pub fn new(rules: AccessRules) -> Self {
+        Self {
+            rules: RwLock::new(rules),
+        }
+    }
+
+    /// Swap in new rules; connections already accepted are unaffected
+    pub fn replace(&self, rules: AccessRules) {
+        *self.rules.write().unwrap_or_else(|e| e.into_inner()) = rules;
+    }
+
+    pub fn check(&self, ip: IpAddr) -> Result<(), String> {
+        self.rules.read().unwrap_or_else(|e| e.into_inner()).check(ip)
+    }
+}
+
+/// Parse a comma or whitespace separated list of CIDRs; bare addresses match a single host
+pub fn parse_cidrs(list: &str) -> Result<Vec<IpNet>, String> {
+    list.split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            entry
+                .parse::<IpNet>()
+                .or_else(|_| entry.parse::<IpAddr>().map(IpNet::from))
+                .map_err(|_| format!("invalid CIDR '{}'", entry))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(addr: &str) -> IpAddr {
+        addr.parse().unwrap()
+    }
+
+    #[test]
+    fn empty_allow_list_admits_everyone_not_denied() {
+        let rules = AccessRules::parse("", "203.0.113.0/24").unwrap();
+
+        assert!(rules.check(ip("198.51.100.7")).is_ok());
+        assert!(rules.check(ip("2001:db8::1")).is_ok());
+        assert_eq!(rules.check(ip("203.0.113.9")), Err("denied by 203.0.113.0/24".to_string()));
+        assert!(AccessRules::default().check(ip("203.0.113.9")).is_ok());
+    }
+
+    #[test]
+    fn allow_list_admits_only_its_members() {
+        let rules = AccessRules::parse("10.0.0.0/8, 192.0.2.1", "").unwrap();
+
+        assert!(rules.check(ip("10.1.2.3")).is_ok());
+        assert!(rules.check(ip("192.0.2.1")).is_ok());
+        assert_eq!(rules.check(ip("192.0.2.2")), Err("not in allow list".to_string()));
+    }
+
+    #[test]
+    fn deny_wins_over_allow() {
+        let rules = AccessRules::parse("10.0.0.0/8", "10.9.0.0/16").unwrap();
+
+        assert!(rules.check(ip("10.1.0.1")).is_ok());
+        assert_eq!(rules.check(ip("10.9.0.1")), Err("denied by 10.9.0.0/16".to_string()));
+    }
+
+    #[test]
+    fn ipv4_mapped_peers_match_ipv4_rules() {
+        let rules = AccessRules::parse("10.0.0.0/8", "10.9.0.0/16").unwrap();
+
+        assert!(rules.check(ip("::ffff:10.1.0.1")).is_ok());
+        assert!(rules.check(ip("::ffff:10.9.0.1")).is_err());
+        assert!(rules.check(ip("::ffff:192.0.2.1")).is_err());
+    }
+
+    #[test]
+    fn deny_all_refuses_both_families() {
+        let rules = AccessRules::deny_all();
+
+        assert!(rules.check(ip("192.0.2.1")).is_err());
+        assert!(rules.check(ip("2001:db8::1")).is_err());
+    }
+
+    #[test]
+    fn invalid_entries_are_rejected() {
+        assert_eq!(parse_cidrs("10.0.0.0/8 nope"), Err("invalid CIDR 'nope'".to_string()));
+        assert_eq!(parse_cidrs(" ,, ").unwrap(), Vec::new());
+    }
+}
@@ -0,0 +1,214 @@
+//! Embedded bore client.
+//!
+//! Compatible with any bore server, but proxies connections in-crate so that
+//! traffic through the tunnel can be measured and controlled.
+
+use crate::models::CloseReason;
+use crate::tunnel::connlog::{ConnectionLog, Transfer};
+use crate::tunnel::limiter::Bandwidth;
+use crate::tunnel::proxy::proxy;
+use crate::tunnel::shutdown::Shutdown;
+use crate::tunnel::stats::TunnelStats;
+use anyhow::{Context, Result, bail};
+use bore_cli::auth::Authenticator;
+use bore_cli::shared::{CONTROL_PORT, ClientMessage, Delimited, NETWORK_TIMEOUT, ServerMessage};
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+use tracing::Instrument;
+use uuid::Uuid;
+
+pub struct BoreClient {
+    /// Control connection to the server
+    conn: Option<Delimited<TcpStream>>,
+
+    /// Destination address of the server
+    to: String,
+
+    /// Control port of the server
+    control_port: u16,
+
+    /// Local host that is forwarded
+    local_host: String,
+
+    /// Local port that is forwarded
+    local_port: u16,
+
+    /// Port that is publicly available on the remote
+    remote_port: u16,
+
+    /// Optional secret used to authenticate with the server
+    auth: Option<Authenticator>,
+
+    /// Traffic counters shared with the client manager
+    stats: Arc<TunnelStats>,
+
+    /// Stop protocol driven by the client manager
+    shutdown: Arc<Shutdown>,
+
+    /// Upload and download limits shared by all forwarded connections
+    bandwidth: Arc<Bandwidth>,
+
+    /// Records every forwarded connection
+    log: ConnectionLog,
+}
+
+impl BoreClient {
+    /// Connect to the server and request a tunnel for `port` (0 lets the server choose).
+    ///
+    /// `to` may carry an explicit control port (`host:port` or `[v6]:port`) for servers
+    /// not listening on bore's default.
+    pub async fn new(
+        local_host: &str,
+        local_port: u16,
+        to: &str,
+        port: u16,
+        secret: Option<&str>,
+    ) -> Result<Self> {
+        let (to, control_port) = split_control_addr(to)?;
+        let mut stream = Delimited::new(connect_with_timeout(to, control_port).await?);
+        let auth = secret.map(Authenticator::new);
+        if let Some(auth) = &auth {
+            auth.client_handshake(&mut stream).await?;
+        }
+
+        stream.send(ClientMessage::Hello(port)).await?;
+        let remote_port = match stream.recv_timeout().await? {
+            Some(ServerMessage::Hello(remote_port)) => remote_port,
+            Some(ServerMessage::Error(message)) => bail!("server error: {message}"),
+            Some(ServerMessage::Challenge(_)) => {
+                bail!("server requires authentication, but no client secret was provided");
+            }
+            Some(_) => bail!("unexpected initial non-hello message"),
+            None => bail!("unexpected EOF"),
+        };
+
+        Ok(Self {
+            conn: Some(stream),
+            to: to.to_string(),
+            control_port,
+            local_host: local_host.to_string(),
+            local_port,
+            remote_port,
+            auth,
+            stats: Arc::new(TunnelStats::new()),
+            shutdown: Arc::new(Shutdown::new()),
+            bandwidth: Arc::new(Bandwidth::unlimited()),
+            log: ConnectionLog::default(),
+        })
+    }
+
+    /// Returns the port publicly available on the remote
+    pub fn remote_port(&self) -> u16 {
+        self.remote_port
+    }
+
+    pub fn set_stats(&mut self, stats: Arc<TunnelStats>) {
+        self.stats = stats;
+    }
+
+    pub fn set_shutdown(&mut self, shutdown: Arc<Shutdown>) {
+        self.shutdown = shutdown;
+    }
+
+    pub fn set_bandwidth(&mut self, bandwidth: Arc<Bandwidth>) {
+        self.bandwidth = bandwidth;
+    }
+
+    pub fn set_connection_log(&mut self, log: ConnectionLog) {
+        self.log = log;
+    }
+
+    /// Serve forwarded connections until the control connection is closed.
+    ///
+    /// Dropping the returned future closes the control connection, so the server stops
+    /// offering new connections; forwarded ones keep running until the shared
+    /// [`Shutdown`] drains them.
+    pub async fn listen(mut self) -> Result<()> {
+        let mut conn = self.conn.take().context("control connection already consumed")?;
+        let this = Arc::new(self);
+
+        loop {
+            match conn.recv().await? {
+                Some(ServerMessage::Hello(_)) => tracing::warn!("Unexpected hello from bore server"),
+                Some(ServerMessage::Challenge(_)) => tracing::warn!("Unexpected challenge from bore server"),
+                Some(ServerMessage::Heartbeat) => (),
+                Some(ServerMessage::Connection(id)) => {
+                    let this = Arc::clone(&this);
+                    tokio::spawn(async move {
+                        if let Err(e) = this.handle_connection(id).await {
+                            tracing::warn!("Connection {} exited with error: {}", id, e);
+                        }
+                    }.in_current_span());
+                }
+                Some(ServerMessage::Error(e)) => tracing::error!("Bore server error: {}", e),
+                None => return Ok(()),
+            }
+        }
+    }
+
+    async fn handle_connection(&self, id: Uuid) -> Result<()> {
+        // The server does not tell clients who the public peer is
+        self.log.opened(id, self.remote_port, None);
+
+        let transfer = match self.forward(id).await {
+            Ok(transfer) => transfer,
+            Err(e) => {
+                self.log.closed(id, Transfer::ended(CloseReason::Error, Some(format!("{:#}", e))));
+                return Err(e);
+            }
+        };
+
+        let error = transfer.error.clone();
+        self.log.closed(id, transfer);
+        if let Some(error) = error {
+            bail!(error);
+        }
+        Ok(())
+    }
+
+    async fn forward(&self, id: Uuid) -> Result<Transfer> {
+        let mut remote_conn = Delimited::new(connect_with_timeout(&self.to, self.control_port).await?);
+        if let Some(auth) = &self.auth {
+            auth.client_handshake(&mut remote_conn).await?;
+        }
+        remote_conn.send(ClientMessage::Accept(id)).await?;
+
+        let mut local_conn = connect_with_timeout(&self.local_host, self.local_port).await?;
+        let parts = remote_conn.into_parts();
+        local_conn.write_all(&parts.read_buf).await?;
+        self.stats.add_received(parts.read_buf.len() as u64);
+
+        let mut transfer = proxy(parts.io, local_conn, &self.stats, &self.bandwidth, self.shutdown.subscribe()).await;
+        transfer.bytes_received += parts.read_buf.len() as u64;
+        Ok(transfer)
+    }
+}
+
+fn split_control_addr(to: &str) -> Result<(&str, u16)> {
+    let (host, port) = if let Some(rest) = to.strip_prefix('[') {
+        // Bracketed IPv6 literal, optionally followed by a port
+        let (host, rest) = rest.split_once(']').context("unterminated IPv6 address")?;
+        (host, rest.strip_prefix(':'))
+    } else if to.matches(':').count() == 1 {
+        let (host, port) = to.split_once(':').unwrap_or((to, ""));
+        (host, Some(port))
+    } else {
+        (to, None)
+    };
+
+    let port = match port {
+        Some(port) => port.parse().with_context(|| format!("invalid control port in {to}"))?,
+        None => CONTROL_PORT,
+    };
+
+    Ok((host, port))
+}
+
+async fn connect_with_timeout(to: &str, port: u16) -> Result<TcpStream> {
+    match tokio::time::timeout(NETWORK_TIMEOUT, TcpStream::connect((to, port))).await {
+        Ok(res) => res,
+        Err(e) => Err(e.into()),
+    }
+    .with_context(|| format!("could not connect to {to}:{port}"))
+}
@@ -0,0 +1,399 @@
+//! Embedded bore server.
+//!
+//! Speaks the bore wire protocol from `bore_cli::shared`, so stock bore clients can
+//! connect, but runs the proxy loop in-crate so tunnels can be measured and controlled.
+
+use crate::models::CloseReason;
+use crate::tunnel::access::AccessStore;
+use crate::tunnel::connlog::{ConnectionLog, Transfer};
+use crate::tunnel::credentials::{CredentialStore, Identity};
+use crate::tunnel::limiter::Bandwidth;
+use crate::tunnel::proxy::proxy;
+use crate::tunnel::reservations::PortReservations;
+use crate::tunnel::shutdown::Shutdown;
+use crate::tunnel::stats::TunnelStats;
+use crate::tunnel::status::{ConnectionEvent, ConnectionEventKind};
+use anyhow::{Result, bail};
+use bore_cli::shared::{CONTROL_PORT, ClientMessage, Delimited, ServerMessage};
+use dashmap::DashMap;
+use rand_core::{OsRng, RngCore};
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::ops::RangeInclusive;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tracing::Instrument;
+use uuid::Uuid;
+
+/// How long an accepted public connection waits for the client to pick it up
+const STALE_CONNECTION_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often the control connection is probed while waiting for public connections
+const HEARTBEAT_INTERVAL: Duration = Duration::from_millis(500);
+
+pub struct BoreServer {
+    /// Range of TCP ports that can be forwarded
+    port_range: RangeInclusive<u16>,
+
+    /// Secrets clients may authenticate with
+    credentials: Arc<CredentialStore>,
+
+    /// Ports set aside for specific credentials
+    reservations: Arc<PortReservations>,
+
+    /// Source address rules for public connections, unless a reservation overrides them
+    access: Arc<AccessStore>,
+
+    /// Receives notable events on public connections
+    events: Option<mpsc::UnboundedSender<ConnectionEvent>>,
+
+    /// Records every accepted public connection
+    log: ConnectionLog,
+
+    /// Public connections waiting for a client to accept them, with their tunnel's limits
+    conns: Arc<DashMap<Uuid, (TcpStream, Arc<Bandwidth>)>>,
+
+    /// Default upload and download limits of each tunnel, in bytes per second
+    tunnel_limits: (u64, u64),
+
+    /// Bandwidth of every open tunnel, keyed by its public port
+    tunnels: Arc<DashMap<u16, Arc<Bandwidth>>>,
+
+    /// IP address where the control server will bind to
+    bind_addr: IpAddr,
+
+    /// TCP port for control connections from clients
+    control_port: u16,
+
+    /// IP address where tunnels will listen on
+    bind_tunnels: IpAddr,
+
+    /// Traffic counters shared with the server manager
+    stats: Arc<TunnelStats>,
+
+    /// Stop protocol driven by the server manager
+    shutdown: Arc<Shutdown>,
+}
+
+impl BoreServer {
+    pub fn new(port_range: RangeInclusive<u16>, secret: Option<&str>) -> Self {
+        Self {
+            port_range,
+            credentials: Arc::new(CredentialStore::new(secret)),
+            reservations: Arc::new(PortReservations::new()),
+            access: Arc::new(AccessStore::default()),
+            events: None,
+            log: ConnectionLog::default(),
+            conns: Arc::new(DashMap::new()),
+            tunnel_limits: (0, 0),
+            tunnels: Arc::new(DashMap::new()),
+            bind_addr: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            control_port: CONTROL_PORT,
+            bind_tunnels: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            stats: Arc::new(TunnelStats::new()),
+            shutdown: Arc::new(Shutdown::new()),
+        }
+    }
+
+    pub fn set_bind_addr(&mut self, bind_addr: IpAddr) {
+        self.bind_addr = bind_addr;
+    }
+
+    pub fn set_control_port(&mut self, control_port: u16) {
+        self.control_port = control_port;
+    }
+
+    pub fn set_bind_tunnels(&mut self, bind_tunnels: IpAddr) {
+        self.bind_tunnels = bind_tunnels;
+    }
+
+    pub fn set_credentials(&mut self, credentials: Arc<CredentialStore>) {
+        self.credentials = credentials;
+    }
+
+    pub fn set_reservations(&mut self, reservations: Arc<PortReservations>) {
+        self.reservations = reservations;
+    }
+
+    pub fn set_access(&mut self, access: Arc<AccessStore>) {
+        self.access = access;
+    }
+
+    pub fn set_events(&mut self, events: mpsc::UnboundedSender<ConnectionEvent>) {
+        self.events = Some(events);
+    }
+
+    pub fn set_connection_log(&mut self, log: ConnectionLog) {
+        self.log = log;
+    }
+
+    pub fn set_tunnel_limits(&mut self, upload_limit: u64, download_limit: u64) {
+        self.tunnel_limits = (upload_limit, download_limit);
+    }
+
+    /// Bandwidth of the open tunnels, for status reporting
+    pub fn tunnels(&self) -> Arc<DashMap<u16, Arc<Bandwidth>>> {
+        Arc::clone(&self.tunnels)
+    }
+
+    pub fn set_stats(&mut self, stats: Arc<TunnelStats>) {
+        self.stats = stats;
+    }
+
+    pub fn set_shutdown(&mut self, shutdown: Arc<Shutdown>) {
+        self.shutdown = shutdown;
+    }
+
+    /// Bind the control port, so that address conflicts surface before the server is spawned
+    pub async fn bind(&self) -> io::Result<TcpListener> {
+        TcpListener::bind((self.bind_addr, self.control_port)).await
+    }
+
+    /// Serve control connections accepted on a listener obtained from [`BoreServer::bind`].
+    ///
+    /// Dropping the returned future closes the control port; tunnels already open keep
+    /// running until the shared [`Shutdown`] drains them.
+    pub async fn listen(self, listener: TcpListener) -> Result<()> {
+        let this = Arc::new(self);
+        tracing::info!("Bore server listening on {}:{}", this.bind_addr, this.control_port);
+
+        loop {
+            let (stream, addr) = listener.accept().await?;
+            let this = Arc::clone(&this);
+            tokio::spawn(async move {
+                tracing::debug!("Incoming control connection from {}", addr);
+                if let Err(e) = this.handle_connection(stream, addr).await {
+                    tracing::warn!("Control connection from {} exited with error: {}", addr, e);
+                } else {
+                    tracing::debug!("Control connection from {} exited", addr);
+                }
+            }.in_current_span());
+        }
+    }
+
+    async fn create_listener(
+        &self,
+        port: u16,
+        identity: &Identity,
+    ) -> std::result::Result<TcpListener, &'static str> {
+        let try_bind = |port: u16| async move {
+            TcpListener::bind((self.bind_tunnels, port))
+                .await
+                .map_err(|err| match err.kind() {
+                    io::ErrorKind::AddrInUse => "port already in use",
+                    io::ErrorKind::PermissionDenied => "permission denied",
+                    _ => "failed to bind to port",
+                })
+        };
+
+        if port > 0
+            && let Some(owner) = self.reservations.owner(port)
+        {
+            // Reserved ports bypass the credential's range, but only for their owner
+            if identity.name() != Some(owner.as_str()) {
+                return Err("port is reserved for another client");
+            }
+            if !self.port_range.contains(&port) {
+                return Err("client port number not in allowed range");
+            }
+            return try_bind(port).await;
+        }
+
+        // A credential may narrow the server's range, never widen it
+        let port_range = match identity.ports() {
+            Some(ports) => {
+                let start = *ports.start().max(self.port_range.start());
+                let end = *ports.end().min(self.port_range.end());
+                if start > end {
+                    return Err("credential port range is outside the server's range");
+                }
+                start..=end
+            }
+            None => self.port_range.clone(),
+        };
+
+        if port > 0 {
+            // Client requests a specific port number
+            if !port_range.contains(&port) {
+                return Err("client port number not in allowed range");
+            }
+            try_bind(port).await
+        } else {
+            // Owners get their reserved ports first, so they keep them across restarts
+            if let Some(owner) = identity.name() {
+                for port in self.reservations.ports_of(owner) {
+                    if let Ok(listener) = try_bind(port).await {
+                        return Ok(listener);
+                    }
+                }
+            }
+
+            // Otherwise any available unreserved port in range; probe random ports the same
+            // way upstream bore does (150 tries gives 99.999% success at 85% utilisation)
+            let start = *port_range.start() as u32;
+            let len = *port_range.end() as u32 - start + 1;
+            for _ in 0..150 {
+                let port = (start + OsRng.next_u32() % len) as u16;
+                if self.reservations.owner(port).is_some() {
+                    continue;
+                }
+                if let Ok(listener) = try_bind(port).await {
+                    return Ok(listener);
+                }
+            }
+            Err("failed to find an available port")
+        }
+    }
+
+    /// Apply the port's access rules to a public peer, recording rejections
+    fn check_access(&self, port: u16, peer: SocketAddr) -> std::result::Result<(), String> {
+        let result = match self.reservations.access(port) {
+            Some(rules) => rules.check(peer.ip()),
+            None => self.access.check(peer.ip()),
+        };
+
+        if let Err(reason) = &result {
+            let rejected = self.stats.connection_rejected();
+            tracing::info!(
+                "Rejected connection from {} on port {}: {} ({} rejected so far)",
+                peer, port, reason, rejected
+            );
+            if let Some(events) = &self.events {
+                let _ = events.send(ConnectionEvent {
+                    event: ConnectionEventKind::Rejected,
+                    port,
+                    peer: peer.to_string(),
+                    reason: Some(reason.clone()),
+                });
+            }
+        }
+
+        result
+    }
+
+    async fn handle_connection(&self, stream: TcpStream, addr: SocketAddr) -> Result<()> {
+        let mut stream = Delimited::new(stream);
+        let identity = match self.credentials.handshake(&mut stream).await {
+            Ok(identity) => identity,
+            Err(e) => {
+                tracing::warn!("Bore server handshake with {} failed: {}", addr, e);
+                stream.send(ServerMessage::Error(e.to_string())).await?;
+                return Ok(());
+            }
+        };
+
+        match stream.recv_timeout().await? {
+            Some(ClientMessage::Authenticate(_)) => {
+                tracing::warn!("Unexpected authenticate message from {}", addr);
+                Ok(())
+            }
+            Some(ClientMessage::Hello(port)) => {
+                let listener = match self.create_listener(port, &identity).await {
+                    Ok(listener) => listener,
+                    Err(e) => {
+                        tracing::warn!("Refused tunnel for {} ({}): {}", addr, identity, e);
+                        stream.send(ServerMessage::Error(e.into())).await?;
+                        return Ok(());
+                    }
+                };
+                let port = listener.local_addr()?.port();
+                tracing::info!("New bore client {} on port {} using {}", addr, port, identity);
+                stream.send(ServerMessage::Hello(port)).await?;
+
+                let (upload_limit, download_limit) = self.tunnel_limits;
+                let bandwidth = Arc::new(Bandwidth::new(upload_limit, download_limit));
+                let _tunnel = TunnelEntry::register(&self.tunnels, port, bandwidth.clone());
+
+                let mut shutdown = self.shutdown.subscribe();
+                loop {
+                    if stream.send(ServerMessage::Heartbeat).await.is_err() {
+                        // Assume that the control connection has been dropped
+                        return Ok(());
+                    }
+
+                    let accepted = tokio::select! {
+                        _ = shutdown.draining() => {
+                            // Closing the tunnel listener stops new public connections
+                            tracing::info!("Closing tunnel on port {} for shutdown", port);
+                            stream.send(ServerMessage::Error("server is shutting down".to_string())).await?;
+                            return Ok(());
+                        }
+                        accepted = tokio::time::timeout(HEARTBEAT_INTERVAL, listener.accept()) => accepted,
+                    };
+
+                    if let Ok(result) = accepted {
+                        let (public, peer) = result?;
+                        if self.check_access(port, peer).is_err() {
+                            // Dropping the stream closes the connection before any data flows
+                            drop(public);
+                            continue;
+                        }
+                        tracing::debug!("New connection from {} on port {}", peer, port);
+
+                        let id = Uuid::new_v4();
+                        self.log.opened(id, port, Some(peer));
+                        let conns = Arc::clone(&self.conns);
+                        let log = self.log.clone();
+                        conns.insert(id, (public, bandwidth.clone()));
+                        tokio::spawn(async move {
+                            // Remove stale entries to avoid memory leaks
+                            tokio::time::sleep(STALE_CONNECTION_TIMEOUT).await;
+                            if conns.remove(&id).is_some() {
+                                tracing::warn!("Removed stale connection {}", id);
+                                log.closed(id, Transfer::ended(CloseReason::Unclaimed, None));
+                            }
+                        }.in_current_span());
+                        stream.send(ServerMessage::Connection(id)).await?;
+                    }
+                }
+            }
+            Some(ClientMessage::Accept(id)) => {
+                tracing::debug!("Forwarding connection {} using {}", id, identity);
+                match self.conns.remove(&id) {
+                    Some((_, (mut public, bandwidth))) => {
+                        let parts = stream.into_parts();
+                        if let Err(e) = public.write_all(&parts.read_buf).await {
+                            self.log.closed(id, Transfer::ended(CloseReason::Error, Some(e.to_string())));
+                            return Err(e.into());
+                        }
+                        self.stats.add_sent(parts.read_buf.len() as u64);
+
+                        let mut transfer =
+                            proxy(public, parts.io, &self.stats, &bandwidth, self.shutdown.subscribe()).await;
+                        transfer.bytes_sent += parts.read_buf.len() as u64;
+                        let error = transfer.error.clone();
+                        self.log.closed(id, transfer);
+                        if let Some(error) = error {
+                            bail!(error);
+                        }
+                    }
+                    None => tracing::warn!("Missing connection {}", id),
+                }
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+/// Keeps a tunnel listed in [`BoreServer::tunnels`] while its control connection lives
+struct TunnelEntry<'a> {
+    tunnels: &'a DashMap<u16, Arc<Bandwidth>>,
+    port: u16,
+}
+
+impl<'a> TunnelEntry<'a> {
+    fn register(tunnels: &'a DashMap<u16, Arc<Bandwidth>>, port: u16, bandwidth: Arc<Bandwidth>) -> Self {
+        tunnels.insert(port, bandwidth);
+        Self { tunnels, port }
+    }
+}
+
+impl Drop for TunnelEntry<'_> {
+    fn drop(&mut self) {
+        self.tunnels.remove(&self.port);
+    }
+}
@@ -1,37 +1,84 @@
+use crate::db;
 use crate::error::{AppError, Result};
-use crate::models::Client;
-use crate::tunnel::status::ClientStatusInfo;
-use bore_cli::client::Client as BoreClient;
+use crate::models::{Client, ClientStatus, SessionType};
+use crate::tunnel::bore_client::BoreClient;
+use crate::tunnel::connlog::ConnectionLog;
+use crate::tunnel::health::{Probe, TargetHealth};
+use crate::tunnel::shutdown::{DrainReport, Shutdown};
+use crate::tunnel::limiter::Bandwidth;
+use crate::tunnel::stats::{MetricsSample, TunnelStats};
+use crate::tunnel::status::{ClientStatusInfo, TaskExit};
+use crate::webhook::{WebhookEvent, send_webhook};
+use crate::ws::{StatusUpdate, WsBroadcaster, WsMessage};
 use dashmap::DashMap;
+use rand_core::{OsRng, RngCore};
+use sqlx::SqlitePool;
 use std::sync::Arc;
-use std::time::{Duration, SystemTime};
-use tokio::sync::mpsc;
+use std::sync::atomic::{AtomicBool, AtomicU16, AtomicU32, Ordering};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::{mpsc, oneshot};
 use tokio::task::JoinHandle;
+use tracing::Instrument;
+
+/// Delay before the first reconnect attempt; doubled on every further attempt.
+const RECONNECT_BASE_DELAY: Duration = Duration::from_secs(1);
+
+/// Extra time given to a client task to acknowledge a stop after its drain timeout
+const STOP_GRACE_PERIOD: Duration = Duration::from_secs(2);
 
 pub struct ClientManager {
     clients: Arc<DashMap<i64, ClientHandle>>,
+    db: SqlitePool,
+    ws_broadcaster: Arc<WsBroadcaster>,
 }
 
 struct ClientHandle {
-    assigned_port: u16,
+    runtime: Arc<ClientRuntime>,
     handle: JoinHandle<anyhow::Result<()>>,
     started_at: SystemTime,
-    _tx: mpsc::Sender<ClientCommand>,
+    stats: Arc<TunnelStats>,
+    shutdown: Arc<Shutdown>,
+    bandwidth: Arc<Bandwidth>,
+    session_id: Option<i64>,
+    tx: mpsc::Sender<ClientCommand>,
+    /// Probe task of the local target, if health checks are enabled
+    health_check: Option<JoinHandle<()>>,
+}
+
+/// Connection state shared between the supervisor task and the manager
+#[derive(Default)]
+struct ClientRuntime {
+    assigned_port: AtomicU16,
+    reconnecting: AtomicBool,
+    reconnect_attempts: AtomicU32,
+    target_healthy: TargetHealth,
 }
 
-#[allow(dead_code)]
 enum ClientCommand {
-    Stop,
+    /// Close the control connection, drain forwarded connections for up to
+    /// `drain_timeout`, then exit
+    Stop {
+        drain_timeout: Duration,
+        reply: oneshot::Sender<DrainReport>,
+    },
 }
 
 impl ClientManager {
-    pub fn new() -> Self {
+    pub fn new(db: SqlitePool, ws_broadcaster: Arc<WsBroadcaster>) -> Self {
         Self {
             clients: Arc::new(DashMap::new()),
+            db,
+            ws_broadcaster,
         }
     }
 
     pub async fn start_client(&self, client: Client) -> Result<u16> {
+        // Tasks of the client log into its span, see `crate::logs`
+        let span = tracing::info_span!("client", client_id = client.id);
+        self.start(client).instrument(span).await
+    }
+
+    async fn start(&self, client: Client) -> Result<u16> {
         tracing::info!("Starting bore client: {} (id: {})", client.name, client.id);
 
         // Check if already running
@@ -43,15 +90,15 @@ impl ClientManager {
         }
 
         // Create bore client
-        let bore_client = BoreClient::new(
-            &client.local_host,
-            client.local_port as u16,
-            &client.remote_server,
-            client.remote_port as u16, // 0 means auto-assign
-            client.secret.as_deref(),
-        )
-        .await
-        .map_err(|e| {
+        let stats = Arc::new(TunnelStats::new());
+        let shutdown = Arc::new(Shutdown::new());
+        let bandwidth = Arc::new(Bandwidth::new(
+            u64::try_from(client.upload_limit).unwrap_or(0),
+            u64::try_from(client.download_limit).unwrap_or(0),
+        ));
+        let log = ConnectionLog::spawn(self.db.clone(), SessionType::Client, client.id);
+        let bore_client = connect(&client, &stats, &shutdown, &bandwidth, &log).await.map_err(|e| {
+            tracing::error!("Bore client {} failed to connect: {:#}", client.name, e);
             AppError::Tunnel(anyhow::anyhow!("Failed to create bore client: {}", e))
         })?;
 
@@ -64,66 +111,112 @@ impl ClientManager {
             assigned_port
         );
 
-        // Create command channel (currently unused, but available for future control)
-        let (_tx, mut _rx) = mpsc::channel::<ClientCommand>(10);
+        // Create command channel used by stop_client
+        let (tx, rx) = mpsc::channel::<ClientCommand>(10);
+
+        let runtime = Arc::new(ClientRuntime::default());
+        runtime.assigned_port.store(assigned_port, Ordering::Relaxed);
 
-        // Spawn client task
+        let health_check = client.health_check_enabled.then(|| {
+            tokio::spawn(
+                HealthCheck {
+                    client: client.clone(),
+                    runtime: runtime.clone(),
+                    db: self.db.clone(),
+                    ws_broadcaster: self.ws_broadcaster.clone(),
+                }
+                .run()
+                .in_current_span(),
+            )
+        });
+
+        // Spawn supervisor task
         let client_id = client.id;
         let client_name = client.name.clone();
-        let handle = tokio::spawn(async move {
-            tracing::info!(
-                "Bore client {} forwarding {}:{} -> {}:{}",
-                client_name,
-                client.local_host,
-                client.local_port,
-                client.remote_server,
-                assigned_port
-            );
+        let supervisor = Supervisor {
+            client,
+            runtime: runtime.clone(),
+            stats: stats.clone(),
+            shutdown: shutdown.clone(),
+            bandwidth: bandwidth.clone(),
+            log,
+            db: self.db.clone(),
+            ws_broadcaster: self.ws_broadcaster.clone(),
+        };
+        let handle = tokio::spawn(supervisor.run(bore_client, rx).in_current_span());
 
-            match bore_client.listen().await {
-                Ok(_) => {
-                    tracing::info!("Bore client {} stopped normally", client_name);
-                    Ok(())
-                }
-                Err(e) => {
-                    tracing::error!("Bore client {} error: {}", client_name, e);
-                    Err(e)
-                }
+        let session_id = match db::create_session(&self.db, SessionType::Client, client_id).await {
+            Ok(session) => Some(session.id),
+            Err(e) => {
+                tracing::error!("Failed to open session for client {}: {}", client_id, e);
+                None
             }
-        });
+        };
 
         // Store handle
         self.clients.insert(
             client_id,
             ClientHandle {
-                assigned_port,
+                runtime,
                 handle,
                 started_at: SystemTime::now(),
-                _tx,
+                stats,
+                shutdown,
+                bandwidth,
+                session_id,
+                tx,
+                health_check,
             },
         );
 
         tracing::info!(
             "Bore client {} started successfully, port: {}",
-            client.name,
+            client_name,
             assigned_port
         );
 
         Ok(assigned_port)
     }
 
-    pub async fn stop_client(&self, id: i64) -> Result<()> {
+    /// Stop a client gracefully: the tunnel is closed to new connections at once,
+    /// forwarded ones get up to `drain_timeout` to finish before they are cut off.
+    pub async fn stop_client(&self, id: i64, drain_timeout: Duration) -> Result<DrainReport> {
         tracing::info!("Stopping bore client id: {}", id);
 
         if let Some((_, handle)) = self.clients.remove(&id) {
-            // Abort the client task
-            handle.handle.abort();
+            if let Some(health_check) = &handle.health_check {
+                health_check.abort();
+            }
+
+            let (reply_tx, reply_rx) = oneshot::channel();
+            let command = ClientCommand::Stop {
+                drain_timeout,
+                reply: reply_tx,
+            };
 
-            // Wait a bit for cleanup
-            tokio::time::sleep(Duration::from_millis(100)).await;
+            let report = if handle.tx.send(command).await.is_ok() {
+                tokio::time::timeout(drain_timeout + STOP_GRACE_PERIOD, reply_rx)
+                    .await
+                    .ok()
+                    .and_then(|reply| reply.ok())
+            } else {
+                None
+            };
+
+            let report = match report {
+                Some(report) => report,
+                None => {
+                    // Task is gone or unresponsive, tear everything down
+                    tracing::warn!("Bore client {} did not acknowledge stop, aborting", id);
+                    handle.handle.abort();
+                    handle.shutdown.drain(&handle.stats, Duration::ZERO).await
+                }
+            };
+
+            self.close_session(&handle).await;
 
             tracing::info!("Bore client {} stopped", id);
-            Ok(())
+            Ok(report)
         } else {
             Err(AppError::NotFound(format!("Client {} not running", id)))
         }
@@ -142,16 +235,33 @@ impl ClientManager {
                     .unwrap_or(Duration::from_secs(0))
                     .as_secs();
 
+                let reconnecting = entry.runtime.reconnecting.load(Ordering::Relaxed);
+
                 Some(ClientStatusInfo {
                     id,
-                    status: "connected".to_string(),
-                    assigned_port: Some(entry.assigned_port),
+                    status: if reconnecting { "reconnecting" } else { "connected" }.to_string(),
+                    assigned_port: if reconnecting {
+                        None
+                    } else {
+                        Some(entry.runtime.assigned_port.load(Ordering::Relaxed))
+                    },
                     uptime_seconds: uptime,
+                    reconnect_attempts: entry.runtime.reconnect_attempts.load(Ordering::Relaxed),
+                    target_healthy: entry.runtime.target_healthy.get(),
+                    traffic: entry.stats.snapshot(),
+                    bandwidth: entry.bandwidth.info(),
                 })
             }
         })
     }
 
+    /// Status of every running client
+    pub fn list_statuses(&self) -> Vec<ClientStatusInfo> {
+        // Collect ids first; looking entries up while iterating could deadlock
+        let ids: Vec<i64> = self.clients.iter().map(|entry| *entry.key()).collect();
+        ids.into_iter().filter_map(|id| self.get_status(id)).collect()
+    }
+
     /// Check all running clients and return IDs of clients whose tasks have finished
     pub fn get_finished_clients(&self) -> Vec<i64> {
         self.clients
@@ -161,14 +271,348 @@ impl ClientManager {
             .collect()
     }
 
-    /// Remove a client from the manager without stopping it (for cleanup of finished tasks)
-    pub fn remove_finished_client(&self, id: i64) -> Option<()> {
-        self.clients.remove(&id).map(|_| ())
+    /// Remove a client from the manager without stopping it (for cleanup of finished tasks),
+    /// returning why its task ended
+    pub async fn remove_finished_client(&self, id: i64) -> Option<TaskExit> {
+        let (_, handle) = self.clients.remove(&id)?;
+        if let Some(health_check) = &handle.health_check {
+            health_check.abort();
+        }
+        self.close_session(&handle).await;
+        Some(TaskExit::new(handle.handle.await, handle.started_at, "Client"))
+    }
+
+    /// Refresh throughput figures and write the counters of every running client to its session
+    pub async fn record_sessions(&self) {
+        let sessions: Vec<_> = self
+            .clients
+            .iter()
+            .filter_map(|entry| {
+                entry.stats.sample_rates();
+                entry.session_id.map(|session_id| (session_id, entry.stats.snapshot()))
+            })
+            .collect();
+
+        for (session_id, traffic) in sessions {
+            if let Err(e) = db::update_session_heartbeat(&self.db, session_id, &traffic).await {
+                tracing::error!("Failed to record session {} heartbeat: {}", session_id, e);
+            }
+        }
+    }
+
+    /// Traffic of every running client since the previous call
+    pub fn take_metrics(&self) -> Vec<(i64, MetricsSample)> {
+        self.clients
+            .iter()
+            .map(|entry| (*entry.key(), entry.stats.take_metrics()))
+            .collect()
+    }
+
+    async fn close_session(&self, handle: &ClientHandle) {
+        if let Some(session_id) = handle.session_id
+            && let Err(e) = db::close_session(&self.db, session_id, &handle.stats.snapshot()).await
+        {
+            tracing::error!("Failed to close session {}: {}", session_id, e);
+        }
     }
 }
 
-impl Default for ClientManager {
-    fn default() -> Self {
-        Self::new()
+/// Keeps a single client's tunnel alive, re-establishing it after connection loss
+struct Supervisor {
+    client: Client,
+    runtime: Arc<ClientRuntime>,
+    stats: Arc<TunnelStats>,
+    shutdown: Arc<Shutdown>,
+    bandwidth: Arc<Bandwidth>,
+    log: ConnectionLog,
+    db: SqlitePool,
+    ws_broadcaster: Arc<WsBroadcaster>,
+}
+
+impl Supervisor {
+    async fn run(
+        self,
+        mut bore_client: BoreClient,
+        mut commands: mpsc::Receiver<ClientCommand>,
+    ) -> anyhow::Result<()> {
+        loop {
+            tracing::info!(
+                "Bore client {} forwarding {}:{} -> {}:{}",
+                self.client.name,
+                self.client.local_host,
+                self.client.local_port,
+                self.client.remote_server,
+                bore_client.remote_port()
+            );
+
+            let error = tokio::select! {
+                result = bore_client.listen() => match result {
+                    Ok(_) => anyhow::anyhow!("Connection closed by server"),
+                    Err(e) => e,
+                },
+                Some(command) = commands.recv() => return self.handle_command(command).await,
+            };
+
+            if !self.client.auto_reconnect {
+                tracing::error!("Bore client {} error: {}", self.client.name, error);
+                self.shutdown.drain(&self.stats, Duration::ZERO).await;
+                return Err(error);
+            }
+
+            tracing::warn!(
+                "Bore client {} lost connection: {}. Reconnecting...",
+                self.client.name,
+                error
+            );
+
+            // Connections already forwarded survive the reconnect; only a stop or giving up ends them
+            bore_client = tokio::select! {
+                result = self.reconnect(error) => match result {
+                    Ok(bore_client) => bore_client,
+                    Err(e) => {
+                        self.shutdown.drain(&self.stats, Duration::ZERO).await;
+                        return Err(e);
+                    }
+                },
+                Some(command) = commands.recv() => return self.handle_command(command).await,
+            };
+        }
     }
+
+    async fn handle_command(&self, command: ClientCommand) -> anyhow::Result<()> {
+        match command {
+            ClientCommand::Stop { drain_timeout, reply } => {
+                // The control connection was closed by dropping `listen`
+                tracing::info!(
+                    "Bore client {} draining {} connections (timeout {:?})",
+                    self.client.name,
+                    self.stats.active_connections(),
+                    drain_timeout
+                );
+                let report = self.shutdown.drain(&self.stats, drain_timeout).await;
+                tracing::info!(
+                    "Bore client {} drained {} connections, killed {}",
+                    self.client.name,
+                    report.drained,
+                    report.killed
+                );
+                let _ = reply.send(report);
+                Ok(())
+            }
+        }
+    }
+
+    async fn reconnect(&self, error: anyhow::Error) -> anyhow::Result<BoreClient> {
+        let max_attempts = u32::try_from(self.client.reconnect_max_attempts.max(0)).unwrap_or(u32::MAX);
+        let max_delay = Duration::from_secs(self.client.reconnect_max_delay.max(1) as u64);
+        let mut last_error = error.to_string();
+
+        self.runtime.reconnecting.store(true, Ordering::Relaxed);
+        self.runtime.reconnect_attempts.store(0, Ordering::Relaxed);
+        self.notify(ClientStatus::Reconnecting, None, Some(last_error.clone())).await;
+
+        let mut attempt: u32 = 0;
+        loop {
+            if max_attempts > 0 && attempt >= max_attempts {
+                tracing::error!(
+                    "Bore client {} giving up after {} reconnect attempts",
+                    self.client.name,
+                    attempt
+                );
+                anyhow::bail!(
+                    "Gave up reconnecting after {} attempts: {}",
+                    attempt,
+                    last_error
+                );
+            }
+
+            attempt += 1;
+            self.runtime.reconnect_attempts.store(attempt, Ordering::Relaxed);
+
+            let delay = backoff_delay(attempt, max_delay);
+            tracing::info!(
+                "Bore client {} reconnect attempt {} in {:?}",
+                self.client.name,
+                attempt,
+                delay
+            );
+            tokio::time::sleep(delay).await;
+
+            match connect(&self.client, &self.stats, &self.shutdown, &self.bandwidth, &self.log).await {
+                Ok(bore_client) => {
+                    let port = bore_client.remote_port();
+                    tracing::info!(
+                        "Bore client {} reconnected after {} attempts, assigned port: {}",
+                        self.client.name,
+                        attempt,
+                        port
+                    );
+
+                    self.runtime.assigned_port.store(port, Ordering::Relaxed);
+                    self.runtime.reconnecting.store(false, Ordering::Relaxed);
+                    if let Err(e) = db::update_client_last_connected(&self.db, self.client.id).await {
+                        tracing::error!("Failed to update client {} last connected time: {}", self.client.id, e);
+                    }
+                    self.notify(ClientStatus::Connected, Some(port), None).await;
+
+                    return Ok(bore_client);
+                }
+                Err(e) => {
+                    tracing::warn!(
+                        "Bore client {} reconnect attempt {} failed: {}",
+                        self.client.name,
+                        attempt,
+                        e
+                    );
+                    last_error = e.to_string();
+                }
+            }
+        }
+    }
+
+    /// Persist a status transition and announce it over WebSocket and webhook
+    async fn notify(&self, status: ClientStatus, assigned_port: Option<u16>, error_message: Option<String>) {
+        let assigned_port = assigned_port.map(|port| port as i64);
+
+        if let Err(e) = db::update_client_status(
+            &self.db,
+            self.client.id,
+            status.clone(),
+            assigned_port,
+            error_message.clone(),
+        ).await {
+            tracing::error!("Failed to update client {} status in database: {}", self.client.id, e);
+        }
+
+        let attempts = self.runtime.reconnect_attempts.load(Ordering::Relaxed);
+
+        self.ws_broadcaster.broadcast(WsMessage::ClientStatus(
+            StatusUpdate::new(self.client.id, status.clone(), assigned_port, error_message.clone())
+                .with("reconnect_attempts", attempts)
+                .with("target_healthy", self.runtime.target_healthy.get()),
+        ));
+
+        let mut client = self.client.clone();
+        client.status = status.clone();
+        client.assigned_port = assigned_port;
+
+        let (event, extra_data) = match status {
+            ClientStatus::Reconnecting => (
+                WebhookEvent::Reconnecting,
+                serde_json::json!({ "error": error_message }),
+            ),
+            _ => (
+                WebhookEvent::Connected,
+                serde_json::json!({ "reconnect_attempts": attempts }),
+            ),
+        };
+
+        send_webhook(&self.db, event, client, extra_data);
+    }
+}
+
+/// Periodically probes a client's local target and reports when it goes down or recovers
+struct HealthCheck {
+    client: Client,
+    runtime: Arc<ClientRuntime>,
+    db: SqlitePool,
+    ws_broadcaster: Arc<WsBroadcaster>,
+}
+
+impl HealthCheck {
+    async fn run(self) {
+        let probe = Probe::new(
+            &self.client.local_host,
+            self.client.local_port as u16,
+            self.client.health_check_path.as_deref(),
+        );
+        let mut interval = tokio::time::interval(Duration::from_secs(
+            self.client.health_check_interval.max(1) as u64,
+        ));
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        let mut down_since: Option<Instant> = None;
+
+        loop {
+            interval.tick().await;
+
+            let error = probe.check().await.err().map(|e| format!("{:#}", e));
+            let healthy = error.is_none();
+            let previous = self.runtime.target_healthy.swap(healthy);
+            if previous == Some(healthy) {
+                continue;
+            }
+
+            match &error {
+                Some(error) => tracing::warn!("Bore client {} local target is down: {}", self.client.name, error),
+                None => tracing::info!("Bore client {} local target is healthy", self.client.name),
+            }
+
+            if let Err(e) = db::create_client_health_event(&self.db, self.client.id, healthy, error.as_deref()).await {
+                tracing::error!("Failed to record client {} health event: {}", self.client.id, e);
+            }
+
+            let reconnecting = self.runtime.reconnecting.load(Ordering::Relaxed);
+            let status = if reconnecting { ClientStatus::Reconnecting } else { ClientStatus::Connected };
+            let port = match self.runtime.assigned_port.load(Ordering::Relaxed) {
+                0 => None,
+                port => Some(port as i64),
+            };
+            self.ws_broadcaster.broadcast(WsMessage::ClientStatus(
+                StatusUpdate::new(self.client.id, status, port, None)
+                    .with("target_healthy", healthy)
+                    .with("health_message", error.clone()),
+            ));
+
+            // A target that is up from the start is not news, one that never came up is
+            let webhook = if healthy {
+                down_since.take().map(|since| (
+                    WebhookEvent::TargetRecovered,
+                    serde_json::json!({ "down_seconds": since.elapsed().as_secs() }),
+                ))
+            } else {
+                down_since = Some(Instant::now());
+                Some((WebhookEvent::TargetDown, serde_json::json!({ "error": error })))
+            };
+
+            if let Some((event, extra_data)) = webhook {
+                send_webhook(&self.db, event, self.client.clone(), extra_data);
+            }
+        }
+    }
+}
+
+async fn connect(
+    client: &Client,
+    stats: &Arc<TunnelStats>,
+    shutdown: &Arc<Shutdown>,
+    bandwidth: &Arc<Bandwidth>,
+    log: &ConnectionLog,
+) -> anyhow::Result<BoreClient> {
+    let mut bore_client = BoreClient::new(
+        &client.local_host,
+        client.local_port as u16,
+        &client.remote_server,
+        client.remote_port as u16, // 0 means auto-assign
+        client.secret.as_deref(),
+    )
+    .await?;
+
+    // Counters, shutdown, limits and the log are shared across reconnects so they cover the whole session
+    bore_client.set_stats(stats.clone());
+    bore_client.set_shutdown(shutdown.clone());
+    bore_client.set_bandwidth(bandwidth.clone());
+    bore_client.set_connection_log(log.clone());
+    Ok(bore_client)
+}
+
+/// Exponential backoff capped at `max_delay`, with jitter in the upper half of the window
+/// so that many clients losing the same server don't reconnect in lockstep.
+fn backoff_delay(attempt: u32, max_delay: Duration) -> Duration {
+    let exponent = attempt.saturating_sub(1).min(16);
+    let delay = RECONNECT_BASE_DELAY
+        .saturating_mul(1 << exponent)
+        .min(max_delay);
+
+    let jitter = (OsRng.next_u32() as f64) / (u32::MAX as f64);
+    delay.mul_f64(0.5 + jitter * 0.5)
 }
@@ -0,0 +1,109 @@
+//! Audit log of the public connections carried by a tunnel.
+//!
+//! Tunnels report connections through a channel so the proxy path never waits on the
+//! database; a writer task per server or client turns them into `connections` rows.
+
+use crate::db;
+use crate::models::{CloseReason, SessionType};
+use sqlx::SqlitePool;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use tokio::sync::mpsc;
+use tracing::Instrument;
+use uuid::Uuid;
+
+/// How a connection ended and what it carried
+#[derive(Debug, Clone)]
+pub struct Transfer {
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub reason: CloseReason,
+    pub error: Option<String>,
+}
+
+impl Transfer {
+    /// A connection that ended before any data was proxied
+    pub fn ended(reason: CloseReason, error: Option<String>) -> Self {
+        Self {
+            bytes_sent: 0,
+            bytes_received: 0,
+            reason,
+            error,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConnectionLogEntry {
+    Opened {
+        id: Uuid,
+        port: u16,
+        peer: Option<SocketAddr>,
+    },
+    Closed {
+        id: Uuid,
+        transfer: Transfer,
+    },
+}
+
+/// Sending side of a tunnel's connection log; the default logs nothing
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionLog {
+    tx: Option<mpsc::UnboundedSender<ConnectionLogEntry>>,
+}
+
+impl ConnectionLog {
+    /// Start a writer task recording connections of the given server or client.
+    /// It exits once every clone of the returned log is dropped.
+    pub fn spawn(db: SqlitePool, tunnel_type: SessionType, entity_id: i64) -> Self {
+        let (tx, rx) = mpsc::unbounded_channel();
+        tokio::spawn(write_connections(db, tunnel_type, entity_id, rx).in_current_span());
+        Self { tx: Some(tx) }
+    }
+
+    pub fn opened(&self, id: Uuid, port: u16, peer: Option<SocketAddr>) {
+        self.send(ConnectionLogEntry::Opened { id, port, peer });
+    }
+
+    pub fn closed(&self, id: Uuid, transfer: Transfer) {
+        self.send(ConnectionLogEntry::Closed { id, transfer });
+    }
+
+    fn send(&self, entry: ConnectionLogEntry) {
+        if let Some(tx) = &self.tx {
+            let _ = tx.send(entry);
+        }
+    }
+}
+
+async fn write_connections(
+    db: SqlitePool,
+    tunnel_type: SessionType,
+    entity_id: i64,
+    mut entries: mpsc::UnboundedReceiver<ConnectionLogEntry>,
+) {
+    // Row ids of connections still open
+    let mut rows: HashMap<Uuid, i64> = HashMap::new();
+
+    while let Some(entry) = entries.recv().await {
+        match entry {
+            ConnectionLogEntry::Opened { id, port, peer } => {
+                let peer = peer.map(|peer| peer.to_string());
+                match db::create_connection(&db, tunnel_type.clone(), entity_id, port, peer.as_deref()).await {
+                    Ok(row_id) => {
+                        rows.insert(id, row_id);
+                    }
+                    Err(e) => tracing::error!("Failed to record connection {}: {}", id, e),
+                }
+            }
+            ConnectionLogEntry::Closed { id, transfer } => {
+                let Some(row_id) = rows.remove(&id) else {
+                    continue;
+                };
+                if let Err(e) = db::close_connection(&db, row_id, &transfer).await {
+                    tracing::error!("Failed to record end of connection {}: {}", id, e);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,162 @@
+//! Secrets accepted by an embedded bore server.
+//!
+//! Clients authenticate with bore's challenge/response handshake; the server tries the
+//! answer against its shared secret and every named credential to find out who connected.
+
+use crate::models::ServerCredential;
+use crate::time;
+use anyhow::{Result, bail};
+use bore_cli::auth::Authenticator;
+use bore_cli::shared::{ClientMessage, Delimited, ServerMessage};
+use chrono::{DateTime, Utc};
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::sync::RwLock;
+use tokio::io::{AsyncRead, AsyncWrite};
+use uuid::Uuid;
+
+/// Who opened a control connection
+#[derive(Debug, Clone)]
+pub enum Identity {
+    /// The server has no secrets configured
+    Anonymous,
+    /// Authenticated with the server's shared secret
+    SharedSecret,
+    /// Authenticated with a named credential
+    Credential {
+        name: String,
+        ports: Option<RangeInclusive<u16>>,
+    },
+}
+
+impl Identity {
+    /// Name of the credential, which is what port reservations are keyed by
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            Identity::Credential { name, .. } => Some(name),
+            _ => None,
+        }
+    }
+
+    /// Ports this identity may open tunnels on, if narrower than the server's range
+    pub fn ports(&self) -> Option<&RangeInclusive<u16>> {
+        match self {
+            Identity::Credential { ports, .. } => ports.as_ref(),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Identity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Identity::Anonymous => write!(f, "no authentication"),
+            Identity::SharedSecret => write!(f, "shared secret"),
+            Identity::Credential { name, .. } => write!(f, "credential '{}'", name),
+        }
+    }
+}
+
+struct Credential {
+    name: String,
+    auth: Authenticator,
+    enabled: bool,
+    ports: Option<RangeInclusive<u16>>,
+    expires_at: Option<DateTime<Utc>>,
+}
+
+impl Credential {
+    fn is_usable(&self, now: DateTime<Utc>) -> bool {
+        self.enabled && self.expires_at.is_none_or(|expires_at| expires_at > now)
+    }
+}
+
+/// Shared secret plus named credentials of one server, replaceable while it runs
+pub struct CredentialStore {
+    shared: Option<Authenticator>,
+    credentials: RwLock<Vec<Credential>>,
+}
+
+impl CredentialStore {
+    pub fn new(shared_secret: Option<&str>) -> Self {
+        Self {
+            shared: shared_secret.map(Authenticator::new),
+            credentials: RwLock::new(Vec::new()),
+        }
+    }
+
+    /// Swap in the current credential rows; connections already open are unaffected
+    pub fn replace(&self, rows: &[ServerCredential]) {
+        let credentials = rows
+            .iter()
+            .map(|row| Credential {
+                name: row.name.clone(),
+                auth: Authenticator::new(&row.secret),
+                enabled: row.enabled,
+                ports: port_range(row.port_range_start, row.port_range_end),
+                expires_at: row.expires_at.as_deref().and_then(time::parse),
+            })
+            .collect();
+
+        *self.credentials.write().unwrap_or_else(|e| e.into_inner()) = credentials;
+    }
+
+    /// Whether clients must authenticate. Disabled credentials still count, so that
+    /// disabling the last one locks the server rather than opening it up.
+    fn requires_auth(&self) -> bool {
+        self.shared.is_some()
+            || !self.credentials.read().unwrap_or_else(|e| e.into_inner()).is_empty()
+    }
+
+    /// Run the server side of the handshake and identify the client
+    pub async fn handshake<T: AsyncRead + AsyncWrite + Unpin>(
+        &self,
+        stream: &mut Delimited<T>,
+    ) -> Result<Identity> {
+        if !self.requires_auth() {
+            return Ok(Identity::Anonymous);
+        }
+
+        let challenge = Uuid::new_v4();
+        stream.send(ServerMessage::Challenge(challenge)).await?;
+        match stream.recv_timeout().await? {
+            Some(ClientMessage::Authenticate(tag)) => self.identify(&challenge, &tag),
+            _ => bail!("server requires secret, but no secret was provided"),
+        }
+    }
+
+    fn identify(&self, challenge: &Uuid, tag: &str) -> Result<Identity> {
+        if let Some(shared) = &self.shared
+            && shared.validate(challenge, tag)
+        {
+            return Ok(Identity::SharedSecret);
+        }
+
+        // Several credentials may share a secret; a disabled or expired one must not
+        // shadow one that is usable
+        let now = Utc::now();
+        let credentials = self.credentials.read().unwrap_or_else(|e| e.into_inner());
+        let mut matching = credentials.iter().filter(|c| c.auth.validate(challenge, tag)).peekable();
+        let Some(&first) = matching.peek() else {
+            bail!("invalid secret");
+        };
+
+        let Some(credential) = matching.find(|c| c.is_usable(now)) else {
+            if !first.enabled {
+                bail!("credential '{}' is disabled", first.name);
+            }
+            bail!("credential '{}' has expired", first.name);
+        };
+
+        Ok(Identity::Credential {
+            name: credential.name.clone(),
+            ports: credential.ports.clone(),
+        })
+    }
+}
+
+fn port_range(start: Option<i64>, end: Option<i64>) -> Option<RangeInclusive<u16>> {
+    let start = u16::try_from(start?).ok()?;
+    let end = u16::try_from(end?).ok()?;
+    Some(start..=end)
+}
@@ -0,0 +1,102 @@
+//! Probes of a client's local target.
+//!
+//! A tunnel can be connected while nothing listens behind it, so the client manager
+//! periodically checks that `local_host:local_port` accepts connections (or answers an
+//! HTTP GET) and reports when that changes.
+
+use anyhow::{Result, bail};
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::time::Duration;
+use tokio::net::TcpStream;
+
+/// How long a single probe may take before the target counts as down
+pub const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+// 0 means no probe has finished yet
+const HEALTHY: u8 = 1;
+const DOWN: u8 = 2;
+
+/// Last probe result of a local target, unknown until the first probe finishes
+#[derive(Debug, Default)]
+pub struct TargetHealth(AtomicU8);
+
+impl TargetHealth {
+    pub fn get(&self) -> Option<bool> {
+        decode(self.0.load(Ordering::Relaxed))
+    }
+
+    /// Record a probe result and return the previous state
+    pub fn swap(&self, healthy: bool) -> Option<bool> {
+        decode(self.0.swap(if healthy { HEALTHY } else { DOWN }, Ordering::Relaxed))
+    }
+}
+
+fn decode(value: u8) -> Option<bool> {
+    match value {
+        HEALTHY => Some(true),
+        DOWN => Some(false),
+        _ => None,
+    }
+}
+
+/// Checks one local target, either with a TCP connect or an HTTP GET of `path`
+pub struct Probe {
+    host: String,
+    port: u16,
+    path: Option<String>,
+    http: reqwest::Client,
+}
+
+impl Probe {
+    pub fn new(host: &str, port: u16, path: Option<&str>) -> Self {
+        // Redirects count as healthy, so they are not followed
+        let http = reqwest::Client::builder()
+            .timeout(PROBE_TIMEOUT)
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .unwrap_or_else(|e| {
+                tracing::warn!("Failed to build health check HTTP client: {}. Using default client.", e);
+                reqwest::Client::new()
+            });
+
+        Self {
+            host: host.to_string(),
+            port,
+            path: path.map(str::to_string),
+            http,
+        }
+    }
+
+    /// Succeeds if the target is up
+    pub async fn check(&self) -> Result<()> {
+        match &self.path {
+            Some(path) => self.check_http(path).await,
+            None => self.check_tcp().await,
+        }
+    }
+
+    async fn check_tcp(&self) -> Result<()> {
+        match tokio::time::timeout(PROBE_TIMEOUT, TcpStream::connect((self.host.as_str(), self.port))).await {
+            Ok(Ok(_)) => Ok(()),
+            Ok(Err(e)) => bail!("connect to {}:{} failed: {}", self.host, self.port, e),
+            Err(_) => bail!("connect to {}:{} timed out", self.host, self.port),
+        }
+    }
+
+    async fn check_http(&self, path: &str) -> Result<()> {
+        let host = if self.host.contains(':') {
+            format!("[{}]", self.host)
+        } else {
+            self.host.clone()
+        };
+        let url = format!("http://{}:{}{}", host, self.port, path);
+
+        let response = self.http.get(&url).send().await?;
+        let status = response.status();
+        if !status.is_success() && !status.is_redirection() {
+            bail!("GET {} returned HTTP {}", url, status);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,166 @@
+//! Token bucket bandwidth limits for proxied traffic.
+
+use crate::tunnel::status::BandwidthInfo;
+use std::sync::Mutex;
+use tokio::time::{Duration, Instant};
+
+/// Limits one direction of a tunnel to `rate` bytes per second, shared by all of its
+/// connections. The bucket holds up to one second worth of tokens, so short bursts
+/// pass at full speed.
+#[derive(Debug)]
+pub struct RateLimiter {
+    /// Bytes per second, 0 for unlimited
+    rate: u64,
+    bucket: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    /// May go negative: a caller takes what it needs and sleeps off the debt
+    tokens: f64,
+    refilled_at: Instant,
+    /// Until when callers are waiting for tokens
+    throttled_until: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(rate: u64) -> Self {
+        let now = Instant::now();
+        Self {
+            rate,
+            bucket: Mutex::new(Bucket {
+                tokens: rate as f64,
+                refilled_at: now,
+                throttled_until: now,
+            }),
+        }
+    }
+
+    pub fn rate(&self) -> u64 {
+        self.rate
+    }
+
+    /// Wait until `bytes` may be sent
+    pub async fn acquire(&self, bytes: usize) {
+        if self.rate == 0 {
+            return;
+        }
+
+        let wait = {
+            let mut bucket = self.bucket.lock().unwrap_or_else(|e| e.into_inner());
+            let now = Instant::now();
+            let rate = self.rate as f64;
+
+            let elapsed = now.duration_since(bucket.refilled_at).as_secs_f64();
+            bucket.tokens = (bucket.tokens + elapsed * rate).min(rate);
+            bucket.refilled_at = now;
+            bucket.tokens -= bytes as f64;
+
+            if bucket.tokens >= 0.0 {
+                return;
+            }
+
+            let wait = Duration::from_secs_f64(-bucket.tokens / rate);
+            bucket.throttled_until = bucket.throttled_until.max(now + wait);
+            wait
+        };
+
+        tokio::time::sleep(wait).await;
+    }
+
+    /// Whether traffic is currently being held back
+    pub fn is_throttled(&self) -> bool {
+        let bucket = self.bucket.lock().unwrap_or_else(|e| e.into_inner());
+        Instant::now() < bucket.throttled_until
+    }
+}
+
+/// Upload and download limits of one tunnel.
+///
+/// "Upload" is traffic from the forwarded service to public peers (counted as sent),
+/// "download" is traffic from public peers to the service (counted as received).
+#[derive(Debug)]
+pub struct Bandwidth {
+    pub upload: RateLimiter,
+    pub download: RateLimiter,
+}
+
+impl Bandwidth {
+    pub fn new(upload_limit: u64, download_limit: u64) -> Self {
+        Self {
+            upload: RateLimiter::new(upload_limit),
+            download: RateLimiter::new(download_limit),
+        }
+    }
+
+    pub fn unlimited() -> Self {
+        Self::new(0, 0)
+    }
+
+    pub fn info(&self) -> BandwidthInfo {
+        BandwidthInfo {
+            upload_limit: self.upload.rate(),
+            download_limit: self.download.rate(),
+            upload_throttled: self.upload.is_throttled(),
+            download_throttled: self.download.is_throttled(),
+        }
+    }
+}
+
+impl Default for Bandwidth {
+    fn default() -> Self {
+        Self::unlimited()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn throughput_is_capped_at_the_rate() {
+        let limiter = RateLimiter::new(1000);
+        let started = Instant::now();
+
+        // The first second worth of bytes passes as a burst
+        limiter.acquire(1000).await;
+        assert_eq!(started.elapsed(), Duration::ZERO);
+
+        for _ in 0..30 {
+            limiter.acquire(100).await;
+        }
+        // 3000 bytes beyond the burst take 3 seconds at 1000 bytes/s
+        let elapsed = started.elapsed();
+        assert!(elapsed >= Duration::from_secs(3), "took {:?}", elapsed);
+        assert!(elapsed < Duration::from_millis(3100), "took {:?}", elapsed);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn bucket_refills_over_time() {
+        let limiter = RateLimiter::new(1000);
+        limiter.acquire(1000).await;
+
+        tokio::time::advance(Duration::from_millis(500)).await;
+        let started = Instant::now();
+        limiter.acquire(500).await;
+        assert_eq!(started.elapsed(), Duration::ZERO);
+        assert!(!limiter.is_throttled());
+
+        // The bucket is empty again, so the next bytes wait for it to refill
+        limiter.acquire(250).await;
+        assert_eq!(started.elapsed(), Duration::from_millis(250));
+        assert!(!limiter.is_throttled());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn zero_rate_is_unlimited() {
+        let limiter = RateLimiter::new(0);
+        let started = Instant::now();
+
+        for _ in 0..100 {
+            limiter.acquire(1 << 20).await;
+        }
+        assert_eq!(started.elapsed(), Duration::ZERO);
+        assert!(!limiter.is_throttled());
+    }
+}
@@ -1,7 +1,20 @@
 pub mod server_manager;
 pub mod client_manager;
 pub mod status;
+pub mod stats;
+pub mod shutdown;
+pub mod credentials;
+pub mod access;
+pub mod reservations;
+pub mod proxy;
+pub mod limiter;
+pub mod health;
+pub mod connlog;
+pub mod bore_server;
+pub mod bore_client;
 
 pub use server_manager::ServerManager;
 pub use client_manager::ClientManager;
 pub use status::*;
+pub use stats::TunnelStats;
+pub use shutdown::DrainReport;
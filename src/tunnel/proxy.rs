@@ -0,0 +1,88 @@
+use crate::models::CloseReason;
+use crate::tunnel::connlog::Transfer;
+use crate::tunnel::limiter::{Bandwidth, RateLimiter};
+use crate::tunnel::shutdown::ShutdownSignal;
+use crate::tunnel::stats::TunnelStats;
+use std::io;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+const BUFFER_SIZE: usize = 16 * 1024;
+
+/// Copy data in both directions until both sides are closed or the tunnel is
+/// terminated, counting every byte and holding each direction to its bandwidth limit.
+/// Returns what this connection carried and why it ended.
+///
+/// `inbound` is the side carrying the public peer's traffic, `outbound` the side
+/// leading to the forwarded service.
+pub async fn proxy(
+    inbound: TcpStream,
+    outbound: TcpStream,
+    stats: &TunnelStats,
+    bandwidth: &Bandwidth,
+    mut shutdown: ShutdownSignal,
+) -> Transfer {
+    let _guard = stats.connection_opened();
+    let sent = AtomicU64::new(0);
+    let received = AtomicU64::new(0);
+
+    let (mut inbound_read, mut inbound_write) = inbound.into_split();
+    let (mut outbound_read, mut outbound_write) = outbound.into_split();
+
+    let transfer = async {
+        tokio::try_join!(
+            pipe(&mut inbound_read, &mut outbound_write, &bandwidth.download, |n| {
+                stats.add_received(n);
+                received.fetch_add(n, Ordering::Relaxed);
+            }),
+            pipe(&mut outbound_read, &mut inbound_write, &bandwidth.upload, |n| {
+                stats.add_sent(n);
+                sent.fetch_add(n, Ordering::Relaxed);
+            }),
+        )
+    };
+
+    let (reason, error) = tokio::select! {
+        result = transfer => match result {
+            Ok(_) => (CloseReason::Closed, None),
+            Err(e) => (CloseReason::Error, Some(e.to_string())),
+        },
+        _ = shutdown.terminated() => {
+            tracing::debug!("Connection closed by tunnel shutdown");
+            (CloseReason::Shutdown, None)
+        }
+    };
+
+    Transfer {
+        bytes_sent: sent.into_inner(),
+        bytes_received: received.into_inner(),
+        reason,
+        error,
+    }
+}
+
+async fn pipe<R, W>(
+    reader: &mut R,
+    writer: &mut W,
+    limiter: &RateLimiter,
+    record: impl Fn(u64),
+) -> io::Result<()>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut buf = vec![0u8; BUFFER_SIZE];
+
+    loop {
+        let n = reader.read(&mut buf).await?;
+        if n == 0 {
+            // Propagate the half-close so the other direction can finish
+            return writer.shutdown().await;
+        }
+
+        limiter.acquire(n).await;
+        writer.write_all(&buf[..n]).await?;
+        record(n as u64);
+    }
+}
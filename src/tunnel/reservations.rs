@@ -0,0 +1,83 @@
+//! Ports of an embedded bore server that are set aside for one credential.
+
+use crate::models::PortReservation;
+use crate::tunnel::access::AccessRules;
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+struct Reservation {
+    owner: String,
+    /// Replaces the server's access rules for this port
+    access: Option<AccessRules>,
+}
+
+/// Reserved ports of one server and their owners, replaceable while it runs
+#[derive(Default)]
+pub struct PortReservations {
+    ports: RwLock<HashMap<u16, Reservation>>,
+}
+
+impl PortReservations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Swap in the current reservation rows; tunnels already open are unaffected
+    pub fn replace(&self, rows: &[PortReservation]) {
+        let ports = rows
+            .iter()
+            .filter_map(|row| {
+                let port = u16::try_from(row.port).ok()?;
+                let access = if row.allow_cidrs.is_some() || row.deny_cidrs.is_some() {
+                    let allow = row.allow_cidrs.as_deref().unwrap_or_default();
+                    let deny = row.deny_cidrs.as_deref().unwrap_or_default();
+                    match AccessRules::parse(allow, deny) {
+                        Ok(rules) => Some(rules),
+                        Err(e) => {
+                            // Fail closed rather than fall back to the server's rules
+                            tracing::error!("Invalid access rules on reserved port {}: {}", port, e);
+                            Some(AccessRules::deny_all())
+                        }
+                    }
+                } else {
+                    None
+                };
+                Some((port, Reservation { owner: row.owner.clone(), access }))
+            })
+            .collect();
+
+        *self.ports.write().unwrap_or_else(|e| e.into_inner()) = ports;
+    }
+
+    /// Owner of a port, if it is reserved
+    pub fn owner(&self, port: u16) -> Option<String> {
+        self.ports
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&port)
+            .map(|reservation| reservation.owner.clone())
+    }
+
+    /// Access rules overriding the server's on a reserved port
+    pub fn access(&self, port: u16) -> Option<AccessRules> {
+        self.ports
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&port)
+            .and_then(|reservation| reservation.access.clone())
+    }
+
+    /// Ports reserved for `owner`, lowest first
+    pub fn ports_of(&self, owner: &str) -> Vec<u16> {
+        let mut ports: Vec<u16> = self
+            .ports
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .filter(|(_, reservation)| reservation.owner == owner)
+            .map(|(port, _)| *port)
+            .collect();
+        ports.sort_unstable();
+        ports
+    }
+}
@@ -1,37 +1,77 @@
+use crate::db;
 use crate::error::{AppError, Result};
-use crate::models::Server;
-use crate::tunnel::status::ServerStatusInfo;
-use bore_cli::server::Server as BoreServer;
+use crate::models::{Server, SessionType};
+use crate::tunnel::access::{AccessRules, AccessStore};
+use crate::tunnel::bore_server::BoreServer;
+use crate::tunnel::connlog::ConnectionLog;
+use crate::tunnel::credentials::CredentialStore;
+use crate::tunnel::limiter::Bandwidth;
+use crate::tunnel::reservations::PortReservations;
+use crate::tunnel::shutdown::{DrainReport, Shutdown};
+use crate::tunnel::stats::{MetricsSample, TunnelStats};
+use crate::tunnel::status::{BandwidthInfo, ConnectionEvent, ServerStatusInfo, TaskExit};
+use crate::webhook::{WebhookEvent, send_webhook};
+use crate::ws::{WsBroadcaster, WsMessage};
 use dashmap::DashMap;
+use sqlx::SqlitePool;
 use std::net::IpAddr;
 use std::sync::Arc;
 use std::time::{Duration, SystemTime};
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, oneshot};
 use tokio::task::JoinHandle;
+use tracing::Instrument;
 
 pub struct ServerManager {
     servers: Arc<DashMap<i64, ServerHandle>>,
+    db: SqlitePool,
+    ws_broadcaster: Arc<WsBroadcaster>,
 }
 
 struct ServerHandle {
+    /// Settings the server was started with
+    server: Server,
+    control_port: u16,
     handle: JoinHandle<anyhow::Result<()>>,
     started_at: SystemTime,
-    _tx: mpsc::Sender<ServerCommand>,
+    stats: Arc<TunnelStats>,
+    shutdown: Arc<Shutdown>,
+    credentials: Arc<CredentialStore>,
+    reservations: Arc<PortReservations>,
+    access: Arc<AccessStore>,
+    /// Default upload and download limits of each tunnel
+    tunnel_limits: (u64, u64),
+    tunnels: Arc<DashMap<u16, Arc<Bandwidth>>>,
+    session_id: Option<i64>,
+    tx: mpsc::Sender<ServerCommand>,
 }
 
-#[allow(dead_code)]
 enum ServerCommand {
-    Stop,
+    /// Stop accepting, drain in-flight connections for up to `drain_timeout`, then exit
+    Stop {
+        drain_timeout: Duration,
+        reply: oneshot::Sender<DrainReport>,
+    },
 }
 
+/// Extra time given to a server task to acknowledge a stop after its drain timeout
+const STOP_GRACE_PERIOD: Duration = Duration::from_secs(2);
+
 impl ServerManager {
-    pub fn new() -> Self {
+    pub fn new(db: SqlitePool, ws_broadcaster: Arc<WsBroadcaster>) -> Self {
         Self {
             servers: Arc::new(DashMap::new()),
+            db,
+            ws_broadcaster,
         }
     }
 
     pub async fn start_server(&self, server: Server) -> Result<()> {
+        // Tasks of the server log into its span, see `crate::logs`
+        let span = tracing::info_span!("server", server_id = server.id);
+        self.start(server).instrument(span).await
+    }
+
+    async fn start(&self, server: Server) -> Result<()> {
         tracing::info!("Starting bore server: {} (id: {})", server.name, server.id);
 
         // Check if already running
@@ -42,6 +82,27 @@ impl ServerManager {
             )));
         }
 
+        let control_port = u16::try_from(server.control_port)
+            .ok()
+            .filter(|port| *port != 0)
+            .ok_or_else(|| AppError::BadRequest(format!(
+                "Invalid control_port: {}",
+                server.control_port
+            )))?;
+
+        // Refuse to start if another running server already owns the control port
+        if let Some(other) = self
+            .servers
+            .iter()
+            .find(|entry| entry.control_port == control_port && !entry.handle.is_finished())
+        {
+            return Err(AppError::BadRequest(format!(
+                "Control port {} is already in use by running server {}",
+                control_port,
+                other.key()
+            )));
+        }
+
         // Parse bind addresses
         let bind_addr: IpAddr = server.bind_addr.parse().map_err(|e| {
             AppError::BadRequest(format!("Invalid bind_addr: {}", e))
@@ -51,63 +112,238 @@ impl ServerManager {
             AppError::BadRequest(format!("Invalid bind_tunnels: {}", e))
         })?;
 
+        let tunnel_limits = (
+            u64::try_from(server.upload_limit).unwrap_or(0),
+            u64::try_from(server.download_limit).unwrap_or(0),
+        );
+
+        let access = Arc::new(AccessStore::new(parse_access_rules(&server)?));
+
+        // Load the secrets clients may authenticate with
+        let credentials = Arc::new(CredentialStore::new(server.secret.as_deref()));
+        credentials.replace(&db::list_server_credentials(&self.db, server.id).await?);
+
+        let reservations = Arc::new(PortReservations::new());
+        reservations.replace(&db::list_port_reservations(&self.db, server.id).await?);
+
         // Create bore server
         let port_range = server.port_range_start as u16..=server.port_range_end as u16;
         let mut bore_server = BoreServer::new(port_range, server.secret.as_deref());
+        bore_server.set_credentials(credentials.clone());
+        bore_server.set_reservations(reservations.clone());
+        bore_server.set_access(access.clone());
+        bore_server.set_tunnel_limits(tunnel_limits.0, tunnel_limits.1);
+        bore_server.set_connection_log(ConnectionLog::spawn(self.db.clone(), SessionType::Server, server.id));
+        let tunnels = bore_server.tunnels();
         bore_server.set_bind_addr(bind_addr);
         bore_server.set_bind_tunnels(bind_tunnels);
+        bore_server.set_control_port(control_port);
+
+        // Bind up front so a port conflict is reported to the caller
+        let listener = bore_server.bind().await.map_err(|e| {
+            tracing::error!("Bore server {} cannot bind control port {}: {}", server.name, control_port, e);
+            AppError::BadRequest(format!(
+                "Cannot bind control port {}:{}: {}",
+                bind_addr, control_port, e
+            ))
+        })?;
+
+        let stats = Arc::new(TunnelStats::new());
+        bore_server.set_stats(stats.clone());
 
-        // Create command channel (currently unused, but available for future control)
-        let (_tx, mut _rx) = mpsc::channel::<ServerCommand>(10);
+        let shutdown = Arc::new(Shutdown::new());
+        bore_server.set_shutdown(shutdown.clone());
+
+        // Forward connection events to WebSocket subscribers until the server is dropped
+        let (events_tx, events_rx) = mpsc::unbounded_channel();
+        bore_server.set_events(events_tx);
+        tokio::spawn(
+            forward_connection_events(server.id, events_rx, stats.clone(), self.ws_broadcaster.clone())
+                .in_current_span(),
+        );
+
+        // Create command channel used by stop_server
+        let (tx, mut rx) = mpsc::channel::<ServerCommand>(10);
 
         // Spawn server task
         let server_id = server.id;
         let server_name = server.name.clone();
+        let task_server = server.clone();
+        let task_db = self.db.clone();
+        let task_stats = stats.clone();
+        let task_shutdown = shutdown.clone();
+        let started_at = SystemTime::now();
         let handle = tokio::spawn(async move {
-            tracing::info!("Bore server {} listening on port 7835", server_name);
-            match bore_server.listen().await {
-                Ok(_) => {
-                    tracing::info!("Bore server {} stopped normally", server_name);
-                    Ok(())
+            tracing::info!("Bore server {} listening on port {}", server_name, control_port);
+            tokio::select! {
+                result = bore_server.listen(listener) => {
+                    // Tunnels must not outlive their server
+                    task_shutdown.drain(&task_stats, Duration::ZERO).await;
+                    match result {
+                        Ok(_) => {
+                            tracing::info!("Bore server {} stopped normally", server_name);
+                            Ok(())
+                        }
+                        Err(e) => {
+                            tracing::error!("Bore server {} error: {}", server_name, e);
+                            send_webhook(
+                                &task_db,
+                                WebhookEvent::Crashed,
+                                task_server.clone(),
+                                serde_json::json!({
+                                    "error": format!("{:#}", e),
+                                    "uptime_seconds": uptime_seconds(started_at),
+                                }),
+                            );
+                            Err(e)
+                        }
+                    }
                 }
-                Err(e) => {
-                    tracing::error!("Bore server {} error: {}", server_name, e);
-                    Err(e)
+                Some(ServerCommand::Stop { drain_timeout, reply }) = rx.recv() => {
+                    // The control listener was closed by dropping `listen`
+                    tracing::info!(
+                        "Bore server {} draining {} connections (timeout {:?})",
+                        server_name,
+                        task_stats.active_connections(),
+                        drain_timeout
+                    );
+                    let report = task_shutdown.drain(&task_stats, drain_timeout).await;
+                    tracing::info!(
+                        "Bore server {} drained {} connections, killed {}",
+                        server_name,
+                        report.drained,
+                        report.killed
+                    );
+                    let _ = reply.send(report);
+                    Ok(())
                 }
             }
-        });
+        }.in_current_span());
+
+        let session_id = match db::create_session(&self.db, SessionType::Server, server_id).await {
+            Ok(session) => Some(session.id),
+            Err(e) => {
+                tracing::error!("Failed to open session for server {}: {}", server_id, e);
+                None
+            }
+        };
 
         // Store handle
         self.servers.insert(
             server_id,
             ServerHandle {
+                server: server.clone(),
+                control_port,
                 handle,
-                started_at: SystemTime::now(),
-                _tx,
+                started_at,
+                stats,
+                shutdown,
+                credentials,
+                reservations,
+                access,
+                tunnel_limits,
+                tunnels,
+                session_id,
+                tx,
             },
         );
 
         tracing::info!("Bore server {} started successfully", server.name);
+
+        send_webhook(&self.db, WebhookEvent::Started, server, serde_json::json!({}));
+
         Ok(())
     }
 
-    pub async fn stop_server(&self, id: i64) -> Result<()> {
+    /// Stop a server gracefully: new connections are refused at once, in-flight ones
+    /// get up to `drain_timeout` to finish before they are cut off.
+    pub async fn stop_server(&self, id: i64, drain_timeout: Duration) -> Result<DrainReport> {
         tracing::info!("Stopping bore server id: {}", id);
 
         if let Some((_, handle)) = self.servers.remove(&id) {
-            // Abort the server task
-            handle.handle.abort();
+            let (reply_tx, reply_rx) = oneshot::channel();
+            let command = ServerCommand::Stop {
+                drain_timeout,
+                reply: reply_tx,
+            };
+
+            let report = if handle.tx.send(command).await.is_ok() {
+                tokio::time::timeout(drain_timeout + STOP_GRACE_PERIOD, reply_rx)
+                    .await
+                    .ok()
+                    .and_then(|reply| reply.ok())
+            } else {
+                None
+            };
 
-            // Wait a bit for cleanup
-            tokio::time::sleep(Duration::from_millis(100)).await;
+            let report = match report {
+                Some(report) => report,
+                None => {
+                    // Task is gone or unresponsive, tear everything down
+                    tracing::warn!("Bore server {} did not acknowledge stop, aborting", id);
+                    handle.handle.abort();
+                    handle.shutdown.drain(&handle.stats, Duration::ZERO).await
+                }
+            };
+
+            self.close_session(&handle).await;
 
             tracing::info!("Bore server {} stopped", id);
-            Ok(())
+
+            send_webhook(
+                &self.db,
+                WebhookEvent::Stopped,
+                handle.server.clone(),
+                serde_json::json!({
+                    "uptime_seconds": uptime_seconds(handle.started_at),
+                }),
+            );
+
+            Ok(report)
         } else {
             Err(AppError::NotFound(format!("Server {} not running", id)))
         }
     }
 
+    /// Apply credential changes to a running server without restarting it
+    pub async fn reload_credentials(&self, id: i64) -> Result<()> {
+        let Some(credentials) = self.servers.get(&id).map(|entry| entry.credentials.clone()) else {
+            // Not running, the credentials are loaded on the next start
+            return Ok(());
+        };
+
+        let rows = db::list_server_credentials(&self.db, id).await?;
+        credentials.replace(&rows);
+        tracing::info!("Reloaded {} credentials for bore server {}", rows.len(), id);
+        Ok(())
+    }
+
+    /// Apply reservation changes to a running server without restarting it
+    pub async fn reload_reservations(&self, id: i64) -> Result<()> {
+        let Some(reservations) = self.servers.get(&id).map(|entry| entry.reservations.clone()) else {
+            // Not running, the reservations are loaded on the next start
+            return Ok(());
+        };
+
+        let rows = db::list_port_reservations(&self.db, id).await?;
+        reservations.replace(&rows);
+        tracing::info!("Reloaded {} port reservations for bore server {}", rows.len(), id);
+        Ok(())
+    }
+
+    /// Apply allow and deny list changes to a running server without restarting it
+    pub async fn reload_access(&self, id: i64) -> Result<()> {
+        let Some(access) = self.servers.get(&id).map(|entry| entry.access.clone()) else {
+            // Not running, the rules are loaded on the next start
+            return Ok(());
+        };
+
+        let server = db::get_server(&self.db, id).await?;
+        access.replace(parse_access_rules(&server)?);
+        tracing::info!("Reloaded access rules for bore server {}", id);
+        Ok(())
+    }
+
     pub fn get_status(&self, id: i64) -> Option<ServerStatusInfo> {
         self.servers.get(&id).and_then(|entry| {
             // Check if task is still running
@@ -115,22 +351,33 @@ impl ServerManager {
                 // Task has finished, return None to indicate it's not running
                 None
             } else {
-                let uptime = entry
-                    .started_at
-                    .elapsed()
-                    .unwrap_or(Duration::from_secs(0))
-                    .as_secs();
+                // Limits are per tunnel; the server counts as throttled if any tunnel is
+                let (upload_limit, download_limit) = entry.tunnel_limits;
+                let bandwidth = BandwidthInfo {
+                    upload_limit,
+                    download_limit,
+                    upload_throttled: entry.tunnels.iter().any(|t| t.upload.is_throttled()),
+                    download_throttled: entry.tunnels.iter().any(|t| t.download.is_throttled()),
+                };
 
                 Some(ServerStatusInfo {
                     id,
                     status: "running".to_string(),
-                    active_connections: 0, // TODO: Track actual connections
-                    uptime_seconds: uptime,
+                    uptime_seconds: uptime_seconds(entry.started_at),
+                    traffic: entry.stats.snapshot(),
+                    bandwidth,
                 })
             }
         })
     }
 
+    /// Status of every running server
+    pub fn list_statuses(&self) -> Vec<ServerStatusInfo> {
+        // Collect ids first; looking entries up while iterating could deadlock
+        let ids: Vec<i64> = self.servers.iter().map(|entry| *entry.key()).collect();
+        ids.into_iter().filter_map(|id| self.get_status(id)).collect()
+    }
+
     /// Check all running servers and return IDs of servers whose tasks have finished
     pub fn get_finished_servers(&self) -> Vec<i64> {
         self.servers
@@ -140,14 +387,74 @@ impl ServerManager {
             .collect()
     }
 
-    /// Remove a server from the manager without stopping it (for cleanup of finished tasks)
-    pub fn remove_finished_server(&self, id: i64) -> Option<()> {
-        self.servers.remove(&id).map(|_| ())
+    /// Remove a server from the manager without stopping it (for cleanup of finished tasks),
+    /// returning why its task ended
+    pub async fn remove_finished_server(&self, id: i64) -> Option<TaskExit> {
+        let (_, handle) = self.servers.remove(&id)?;
+        self.close_session(&handle).await;
+        let result = handle.handle.await;
+        // A listener error was already sent as `server.crashed` by the task
+        let webhook_sent = matches!(result, Ok(Err(_)));
+        Some(TaskExit {
+            webhook_sent,
+            ..TaskExit::new(result, handle.started_at, "Server")
+        })
+    }
+
+    /// Refresh throughput figures and write the counters of every running server to its session
+    pub async fn record_sessions(&self) {
+        let sessions: Vec<_> = self
+            .servers
+            .iter()
+            .filter_map(|entry| {
+                entry.stats.sample_rates();
+                entry.session_id.map(|session_id| (session_id, entry.stats.snapshot()))
+            })
+            .collect();
+
+        for (session_id, traffic) in sessions {
+            if let Err(e) = db::update_session_heartbeat(&self.db, session_id, &traffic).await {
+                tracing::error!("Failed to record session {} heartbeat: {}", session_id, e);
+            }
+        }
+    }
+
+    /// Traffic of every running server since the previous call
+    pub fn take_metrics(&self) -> Vec<(i64, MetricsSample)> {
+        self.servers
+            .iter()
+            .map(|entry| (*entry.key(), entry.stats.take_metrics()))
+            .collect()
+    }
+
+    async fn close_session(&self, handle: &ServerHandle) {
+        if let Some(session_id) = handle.session_id
+            && let Err(e) = db::close_session(&self.db, session_id, &handle.stats.snapshot()).await
+        {
+            tracing::error!("Failed to close session {}: {}", session_id, e);
+        }
     }
 }
 
-impl Default for ServerManager {
-    fn default() -> Self {
-        Self::new()
+async fn forward_connection_events(
+    server_id: i64,
+    mut events: mpsc::UnboundedReceiver<ConnectionEvent>,
+    stats: Arc<TunnelStats>,
+    ws_broadcaster: Arc<WsBroadcaster>,
+) {
+    while let Some(event) = events.recv().await {
+        let mut data = serde_json::to_value(&event).unwrap_or_default();
+        data["server_id"] = server_id.into();
+        data["rejected_connections"] = stats.snapshot().rejected_connections.into();
+        ws_broadcaster.broadcast(WsMessage::ConnectionEvent(data));
     }
 }
+
+fn uptime_seconds(started_at: SystemTime) -> u64 {
+    started_at.elapsed().map(|uptime| uptime.as_secs()).unwrap_or(0)
+}
+
+fn parse_access_rules(server: &Server) -> Result<AccessRules> {
+    AccessRules::parse(&server.allow_cidrs, &server.deny_cidrs)
+        .map_err(|e| AppError::BadRequest(format!("Invalid access rules: {}", e)))
+}
@@ -0,0 +1,93 @@
+use crate::tunnel::stats::TunnelStats;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::time::Instant;
+
+/// How often the number of in-flight connections is checked while draining
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Phase {
+    Running,
+    /// No new connections are accepted, in-flight ones may finish
+    Draining,
+    /// In-flight connections must be closed immediately
+    Terminated,
+}
+
+/// Outcome of a graceful stop
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DrainReport {
+    /// Connections that finished on their own within the drain timeout
+    pub drained: u64,
+    /// Connections still open at the deadline and cut off
+    pub killed: u64,
+}
+
+/// Drives the stop protocol of one tunnel: stop accepting, wait, then tear down
+pub struct Shutdown {
+    phase: watch::Sender<Phase>,
+}
+
+/// Receiving side of [`Shutdown`], held by accept loops and proxied connections
+#[derive(Clone)]
+pub struct ShutdownSignal {
+    phase: watch::Receiver<Phase>,
+}
+
+impl Shutdown {
+    pub fn new() -> Self {
+        let (phase, _) = watch::channel(Phase::Running);
+        Self { phase }
+    }
+
+    pub fn subscribe(&self) -> ShutdownSignal {
+        ShutdownSignal {
+            phase: self.phase.subscribe(),
+        }
+    }
+
+    /// Stop accepting connections and wait up to `timeout` for in-flight ones to finish,
+    /// then terminate whatever is left.
+    pub async fn drain(&self, stats: &TunnelStats, timeout: Duration) -> DrainReport {
+        let in_flight = stats.active_connections();
+        self.phase.send_replace(Phase::Draining);
+
+        let deadline = Instant::now() + timeout;
+        while stats.active_connections() > 0 && Instant::now() < deadline {
+            tokio::time::sleep(DRAIN_POLL_INTERVAL).await;
+        }
+
+        let killed = stats.active_connections();
+        self.phase.send_replace(Phase::Terminated);
+
+        DrainReport {
+            drained: in_flight.saturating_sub(killed),
+            killed,
+        }
+    }
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ShutdownSignal {
+    /// Resolves once the tunnel stops accepting new connections
+    pub async fn draining(&mut self) {
+        self.wait_for(Phase::Draining).await;
+    }
+
+    /// Resolves once in-flight connections must be closed
+    pub async fn terminated(&mut self) {
+        self.wait_for(Phase::Terminated).await;
+    }
+
+    async fn wait_for(&mut self, phase: Phase) {
+        // A dropped sender means the tunnel is gone, which counts as every phase
+        let _ = self.phase.wait_for(|current| *current >= phase).await;
+    }
+}
@@ -0,0 +1,149 @@
+use crate::tunnel::status::TrafficInfo;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+
+/// Live traffic counters for one tunnel, shared by all of its proxied connections.
+///
+/// "Received" is traffic coming from the public peer towards the forwarded service,
+/// "sent" is the response traffic going back to the public peer.
+#[derive(Debug, Default)]
+pub struct TunnelStats {
+    active_connections: AtomicU64,
+    total_connections: AtomicU64,
+    rejected_connections: AtomicU64,
+    bytes_sent: AtomicU64,
+    bytes_received: AtomicU64,
+    rates: Mutex<RateSample>,
+    metrics: Mutex<MetricsBaseline>,
+}
+
+#[derive(Debug, Default)]
+struct RateSample {
+    taken_at: Option<Instant>,
+    bytes_sent: u64,
+    bytes_received: u64,
+    send_rate: u64,
+    receive_rate: u64,
+}
+
+/// Counters at the previous metrics sample, plus the peak of open connections since
+#[derive(Debug, Default)]
+struct MetricsBaseline {
+    total_connections: u64,
+    bytes_sent: u64,
+    bytes_received: u64,
+    peak_connections: u64,
+}
+
+/// Traffic of a tunnel since the previous [`TunnelStats::take_metrics`]
+#[derive(Debug, Clone, Default)]
+pub struct MetricsSample {
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    /// Connections opened
+    pub connections: u64,
+    /// Peak of concurrently open connections
+    pub active_connections: u64,
+}
+
+/// Marks a connection as active for as long as it is alive
+pub struct ConnectionGuard<'a> {
+    stats: &'a TunnelStats,
+}
+
+impl TunnelStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn connection_opened(&self) -> ConnectionGuard<'_> {
+        let active = self.active_connections.fetch_add(1, Ordering::Relaxed) + 1;
+        self.total_connections.fetch_add(1, Ordering::Relaxed);
+
+        let mut metrics = self.metrics.lock().unwrap_or_else(|e| e.into_inner());
+        metrics.peak_connections = metrics.peak_connections.max(active);
+
+        ConnectionGuard { stats: self }
+    }
+
+    /// Count a public connection refused by the access rules, returning the new total
+    pub fn connection_rejected(&self) -> u64 {
+        self.rejected_connections.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    pub fn add_sent(&self, bytes: u64) {
+        self.bytes_sent.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn add_received(&self, bytes: u64) {
+        self.bytes_received.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub fn active_connections(&self) -> u64 {
+        self.active_connections.load(Ordering::Relaxed)
+    }
+
+    /// Recompute throughput from the bytes transferred since the previous sample
+    pub fn sample_rates(&self) {
+        let bytes_sent = self.bytes_sent.load(Ordering::Relaxed);
+        let bytes_received = self.bytes_received.load(Ordering::Relaxed);
+        let now = Instant::now();
+
+        let mut sample = self.rates.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(taken_at) = sample.taken_at {
+            let elapsed = now.duration_since(taken_at).as_secs_f64();
+            if elapsed > 0.0 {
+                sample.send_rate = ((bytes_sent - sample.bytes_sent) as f64 / elapsed) as u64;
+                sample.receive_rate = ((bytes_received - sample.bytes_received) as f64 / elapsed) as u64;
+            }
+        }
+        sample.taken_at = Some(now);
+        sample.bytes_sent = bytes_sent;
+        sample.bytes_received = bytes_received;
+    }
+
+    /// Traffic since the previous call, for the metrics history
+    pub fn take_metrics(&self) -> MetricsSample {
+        let total_connections = self.total_connections.load(Ordering::Relaxed);
+        let bytes_sent = self.bytes_sent.load(Ordering::Relaxed);
+        let bytes_received = self.bytes_received.load(Ordering::Relaxed);
+        let active = self.active_connections.load(Ordering::Relaxed);
+
+        let mut metrics = self.metrics.lock().unwrap_or_else(|e| e.into_inner());
+        let sample = MetricsSample {
+            bytes_sent: bytes_sent - metrics.bytes_sent,
+            bytes_received: bytes_received - metrics.bytes_received,
+            connections: total_connections - metrics.total_connections,
+            active_connections: metrics.peak_connections.max(active),
+        };
+        *metrics = MetricsBaseline {
+            total_connections,
+            bytes_sent,
+            bytes_received,
+            peak_connections: active,
+        };
+
+        sample
+    }
+
+    pub fn snapshot(&self) -> TrafficInfo {
+        let sample = self.rates.lock().unwrap_or_else(|e| e.into_inner());
+
+        TrafficInfo {
+            active_connections: self.active_connections.load(Ordering::Relaxed),
+            total_connections: self.total_connections.load(Ordering::Relaxed),
+            rejected_connections: self.rejected_connections.load(Ordering::Relaxed),
+            bytes_sent: self.bytes_sent.load(Ordering::Relaxed),
+            bytes_received: self.bytes_received.load(Ordering::Relaxed),
+            send_rate: sample.send_rate,
+            receive_rate: sample.receive_rate,
+        }
+    }
+}
+
+impl Drop for ConnectionGuard<'_> {
+    fn drop(&mut self) {
+        self.stats.active_connections.fetch_sub(1, Ordering::Relaxed);
+    }
+}
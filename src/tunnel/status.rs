@@ -4,8 +4,11 @@ use serde::{Deserialize, Serialize};
 pub struct ServerStatusInfo {
     pub id: i64,
     pub status: String,
-    pub active_connections: u64,
     pub uptime_seconds: u64,
+    #[serde(flatten)]
+    pub traffic: TrafficInfo,
+    #[serde(flatten)]
+    pub bandwidth: BandwidthInfo,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -14,4 +17,82 @@ pub struct ClientStatusInfo {
     pub status: String,
     pub assigned_port: Option<u16>,
     pub uptime_seconds: u64,
+    pub reconnect_attempts: u32,
+    /// Result of the last local target probe, `None` if health checks are off or
+    /// no probe has finished yet
+    pub target_healthy: Option<bool>,
+    #[serde(flatten)]
+    pub traffic: TrafficInfo,
+    #[serde(flatten)]
+    pub bandwidth: BandwidthInfo,
+}
+
+/// Connection and byte counters for a running tunnel; rates are bytes per second
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TrafficInfo {
+    pub active_connections: u64,
+    pub total_connections: u64,
+    pub rejected_connections: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub send_rate: u64,
+    pub receive_rate: u64,
+}
+
+/// Bandwidth limits in bytes per second (0 = unlimited) and whether traffic is
+/// currently held back by them
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BandwidthInfo {
+    pub upload_limit: u64,
+    pub download_limit: u64,
+    pub upload_throttled: bool,
+    pub download_throttled: bool,
+}
+
+/// How a tunnel task that ended on its own finished
+#[derive(Debug, Clone)]
+pub struct TaskExit {
+    pub error: String,
+    pub uptime_seconds: u64,
+    /// The task already sent a webhook about how it ended
+    pub webhook_sent: bool,
+}
+
+impl TaskExit {
+    /// Describe the result of a finished task
+    pub(crate) fn new(
+        result: std::result::Result<anyhow::Result<()>, tokio::task::JoinError>,
+        started_at: std::time::SystemTime,
+        kind: &str,
+    ) -> Self {
+        let error = match result {
+            Ok(Ok(())) => format!("{} task stopped unexpectedly", kind),
+            Ok(Err(e)) => format!("{:#}", e),
+            Err(e) if e.is_panic() => format!("{} task panicked", kind),
+            Err(e) => e.to_string(),
+        };
+
+        Self {
+            error,
+            uptime_seconds: started_at.elapsed().map(|uptime| uptime.as_secs()).unwrap_or(0),
+            webhook_sent: false,
+        }
+    }
+}
+
+/// Something that happened to a public connection of an embedded server, pushed to
+/// `/ws` subscribers as `connection_event`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConnectionEvent {
+    pub event: ConnectionEventKind,
+    pub port: u16,
+    pub peer: String,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConnectionEventKind {
+    /// Refused by the source address rules
+    Rejected,
 }
@@ -0,0 +1,164 @@
+//! Payload formats of webhooks.
+//!
+//! Besides our own JSON and custom handlebars templates, webhooks can post a ready-made
+//! message straight into chat and push services, built from the same event data.
+
+use crate::webhook::sender::WebhookEvent;
+use serde_json::{json, Map, Value};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WebhookFormat {
+    /// Our structured JSON payload
+    Json,
+    /// A handlebars template
+    Custom,
+    /// Slack incoming webhook
+    Slack,
+    /// Discord channel webhook
+    Discord,
+    /// Telegram Bot API `sendMessage`, with `chat_id` in the URL query
+    Telegram,
+    /// Feishu / Lark custom bot
+    Feishu,
+    /// DingTalk custom robot
+    Dingtalk,
+    /// ntfy topic URL
+    Ntfy,
+}
+
+impl WebhookFormat {
+    pub const ALL: [WebhookFormat; 8] = [
+        WebhookFormat::Json,
+        WebhookFormat::Custom,
+        WebhookFormat::Slack,
+        WebhookFormat::Discord,
+        WebhookFormat::Telegram,
+        WebhookFormat::Feishu,
+        WebhookFormat::Dingtalk,
+        WebhookFormat::Ntfy,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WebhookFormat::Json => "json",
+            WebhookFormat::Custom => "custom",
+            WebhookFormat::Slack => "slack",
+            WebhookFormat::Discord => "discord",
+            WebhookFormat::Telegram => "telegram",
+            WebhookFormat::Feishu => "feishu",
+            WebhookFormat::Dingtalk => "dingtalk",
+            WebhookFormat::Ntfy => "ntfy",
+        }
+    }
+
+    pub fn parse(format: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|known| known.as_str() == format)
+    }
+}
+
+/// Render a message for a chat or push service from the payload data of an event
+pub fn render_message(
+    format: WebhookFormat,
+    event: &WebhookEvent,
+    data: &Map<String, Value>,
+    url: &str,
+) -> Result<(String, &'static str), String> {
+    let text = summary(event, data);
+
+    let body = match format {
+        WebhookFormat::Slack => json!({ "text": text }),
+        WebhookFormat::Discord => json!({
+            "embeds": [{
+                "description": text,
+                "color": if is_problem(event) { 0xE74C3C } else { 0x2ECC71 },
+                "timestamp": data.get("timestamp"),
+            }],
+        }),
+        WebhookFormat::Telegram => json!({
+            "chat_id": telegram_chat_id(url)?,
+            "text": text,
+        }),
+        WebhookFormat::Feishu => json!({
+            "msg_type": "text",
+            "content": { "text": text },
+        }),
+        WebhookFormat::Dingtalk => json!({
+            "msgtype": "text",
+            "text": { "content": text },
+        }),
+        // ntfy publishes the plain request body
+        WebhookFormat::Ntfy => return Ok((text, "text/plain")),
+        WebhookFormat::Json | WebhookFormat::Custom => {
+            return Err(format!("{} is not a message format", format.as_str()));
+        }
+    };
+
+    Ok((body.to_string(), "application/json"))
+}
+
+/// `chat_id` query parameter of a Telegram `sendMessage` URL
+pub fn telegram_chat_id(url: &str) -> Result<String, String> {
+    url::Url::parse(url)
+        .ok()
+        .and_then(|url| {
+            url.query_pairs()
+                .find(|(key, _)| key == "chat_id")
+                .map(|(_, value)| value.into_owned())
+        })
+        .filter(|chat_id| !chat_id.is_empty())
+        .ok_or_else(|| "Telegram webhooks need the chat_id query parameter in their URL".to_string())
+}
+
+/// Events that deserve attention
+fn is_problem(event: &WebhookEvent) -> bool {
+    matches!(
+        event,
+        WebhookEvent::Disconnected
+            | WebhookEvent::Reconnecting
+            | WebhookEvent::TargetDown
+            | WebhookEvent::Crashed
+            | WebhookEvent::Error
+    )
+}
+
+/// One line describing an event for people
+fn summary(event: &WebhookEvent, data: &Map<String, Value>) -> String {
+    let field = |key: &str| match data.get(key) {
+        Some(Value::String(value)) => value.clone(),
+        Some(value) => value.to_string(),
+        None => "?".to_string(),
+    };
+    let (kind, name) = if data.contains_key("server_id") {
+        ("Server", field("server_name"))
+    } else {
+        ("Client", field("client_name"))
+    };
+    let icon = if is_problem(event) { "🔴" } else { "🟢" };
+
+    let what = match event {
+        WebhookEvent::Connected => match data.get("assigned_port") {
+            Some(port) => format!("connected on port {}", port),
+            None => "connected".to_string(),
+        },
+        WebhookEvent::Disconnected => format!("disconnected after {}s", field("uptime_seconds")),
+        WebhookEvent::Reconnecting => format!("is reconnecting: {}", field("error")),
+        WebhookEvent::TargetDown => format!(
+            "cannot reach {}:{}: {}",
+            field("local_host"),
+            field("local_port"),
+            field("error")
+        ),
+        WebhookEvent::TargetRecovered => format!(
+            "reaches {}:{} again after {}s",
+            field("local_host"),
+            field("local_port"),
+            field("down_seconds")
+        ),
+        WebhookEvent::Started => format!("started on {}:{}", field("bind_addr"), field("control_port")),
+        WebhookEvent::Stopped => format!("stopped after {}s", field("uptime_seconds")),
+        WebhookEvent::Crashed => format!("crashed: {}", field("error")),
+        WebhookEvent::Error => format!("failed: {}", field("error")),
+    };
+
+    format!("{} {} {} {}", icon, kind, name, what)
+}
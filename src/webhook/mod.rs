@@ -1,3 +1,10 @@
 mod sender;
+pub mod format;
+pub mod queue;
+pub mod sample;
+pub mod signature;
+pub mod ssrf;
 
-pub use sender::{WebhookEvent, WebhookSender, send_webhook};
+pub use format::WebhookFormat;
+pub use queue::{RetryPolicy, delivery_counts, send_webhook};
+pub use sender::{AttemptOutcome, WebhookConfig, WebhookEntity, WebhookEvent, WebhookSender};
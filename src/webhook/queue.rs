@@ -0,0 +1,262 @@
+//! Durable webhook delivery.
+//!
+//! Events are rendered as soon as they happen and stored in `webhook_deliveries`; a
+//! background worker sends due deliveries and reschedules failed ones with exponential
+//! backoff, so nothing is lost when the receiver is down or the application restarts.
+
+use crate::db;
+use crate::models::{DeliveryStatus, NewWebhookDelivery, WebhookDelivery};
+use crate::webhook::sender::{AttemptOutcome, WebhookConfig, WebhookEntity, WebhookEvent, WebhookSender};
+use futures_util::future::join_all;
+use sqlx::SqlitePool;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::sync::Notify;
+
+/// Deliveries sent concurrently by the worker
+const BATCH_SIZE: i64 = 20;
+
+/// Longest the worker sleeps without checking the queue
+const MAX_IDLE: Duration = Duration::from_secs(60);
+
+/// Wakes the worker when a delivery is queued
+static WAKE: Notify = Notify::const_new();
+
+/// Webhooks delivered and given up on since startup, exported on `/metrics`
+static DELIVERED: AtomicU64 = AtomicU64::new(0);
+static FAILED: AtomicU64 = AtomicU64::new(0);
+
+/// Number of successful and failed webhook deliveries since startup
+pub fn delivery_counts() -> (u64, u64) {
+    (DELIVERED.load(Ordering::Relaxed), FAILED.load(Ordering::Relaxed))
+}
+
+/// How failed deliveries are retried
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// Attempts before a delivery is marked failed
+    pub max_attempts: u32,
+    /// Seconds before the first retry, doubled on each further one
+    pub base_delay: u64,
+    /// Longest wait between two attempts, in seconds
+    pub max_delay: u64,
+}
+
+impl RetryPolicy {
+    /// Seconds to wait after the given number of failed attempts
+    fn delay(&self, attempts: u32) -> u64 {
+        let factor = 2u64.saturating_pow(attempts.saturating_sub(1));
+        self.base_delay.saturating_mul(factor).min(self.max_delay)
+    }
+}
+
+/// What the worker records about an attempt
+#[derive(Debug)]
+pub struct DeliveryAttempt {
+    pub status: DeliveryStatus,
+    /// Seconds until the next attempt of a delivery still pending
+    pub retry_in: u64,
+    pub response_status: Option<i64>,
+    pub response_body: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Queue webhooks for an event: to the entity's own webhook URL and to every enabled
+/// subscription covering the entity and event. Payloads are rendered right away, so they
+/// reflect the entity as it was when the event happened.
+pub fn send_webhook(
+    db: &SqlitePool,
+    event: WebhookEvent,
+    entity: impl Into<WebhookEntity>,
+    extra_data: serde_json::Value,
+) {
+    let db = db.clone();
+    let entity = entity.into();
+    tokio::spawn(async move {
+        let mut configs: Vec<WebhookConfig> = entity.webhook_config().into_iter().collect();
+        match db::list_webhooks_for(&db, entity.entity_type(), entity.id(), event.as_str()).await {
+            Ok(webhooks) => configs.extend(webhooks.into_iter().map(WebhookConfig::from)),
+            Err(e) => tracing::error!(
+                "Failed to load webhooks of {} {}: {}",
+                entity.kind(),
+                entity.id(),
+                e
+            ),
+        }
+
+        for config in configs {
+            queue(&db, event.clone(), &entity, config, extra_data.clone()).await;
+        }
+
+        WAKE.notify_one();
+    });
+}
+
+/// Render and store a single delivery
+async fn queue(
+    db: &SqlitePool,
+    event: WebhookEvent,
+    entity: &WebhookEntity,
+    config: WebhookConfig,
+    extra_data: serde_json::Value,
+) {
+    let name = format!("{}.{}", entity.kind(), event.as_str());
+    let (content_type, body, error) = match WebhookSender::render(event, entity, &config, extra_data) {
+        Ok((body, content_type)) => (content_type, body, None),
+        Err(e) => ("text/plain", String::new(), Some(e.to_string())),
+    };
+
+    let input = NewWebhookDelivery {
+        entity_type: entity.entity_type(),
+        entity_id: entity.id(),
+        event: name.clone(),
+        method: config.method,
+        url: config.url.clone(),
+        content_type: content_type.to_string(),
+        body,
+        signing_secret: config.secret,
+        webhook_id: config.webhook_id,
+        headers: config.headers,
+    };
+    let delivery = match db::create_webhook_delivery(db, &input).await {
+        Ok(delivery) => delivery,
+        Err(e) => {
+            tracing::error!("Failed to queue webhook {} to {}: {}", name, config.url, e);
+            return;
+        }
+    };
+
+    // A payload that cannot be rendered will never be delivered
+    if let Some(error) = error {
+        tracing::error!("Failed to render webhook {}: {}", name, error);
+        record(db, &delivery, DeliveryAttempt {
+            status: DeliveryStatus::Failed,
+            retry_in: 0,
+            response_status: None,
+            response_body: None,
+            error: Some(error),
+        })
+        .await;
+    }
+}
+
+/// Wake the worker, e.g. after a delivery was queued again
+pub fn wake_worker() {
+    WAKE.notify_one();
+}
+
+/// Start the worker sending queued webhooks
+pub fn start_worker(db: SqlitePool, sender: WebhookSender, policy: RetryPolicy) {
+    tokio::spawn(async move {
+
+        loop {
+            match db::list_due_webhook_deliveries(&db, BATCH_SIZE).await {
+                Ok(due) if !due.is_empty() => {
+                    join_all(due.iter().map(|delivery| deliver(&db, &sender, policy, delivery))).await;
+                    continue;
+                }
+                Ok(_) => {}
+                Err(e) => tracing::error!("Failed to load due webhook deliveries: {}", e),
+            }
+
+            // Sleep until the next retry is due or a new delivery is queued
+            let idle = match db::seconds_until_next_webhook_attempt(&db).await {
+                Ok(Some(seconds)) => Duration::from_secs(seconds.max(1) as u64).min(MAX_IDLE),
+                Ok(None) => MAX_IDLE,
+                Err(e) => {
+                    tracing::error!("Failed to schedule webhook deliveries: {}", e);
+                    MAX_IDLE
+                }
+            };
+            tokio::select! {
+                _ = WAKE.notified() => {}
+                _ = tokio::time::sleep(idle) => {}
+            }
+        }
+    });
+}
+
+async fn deliver(db: &SqlitePool, sender: &WebhookSender, policy: RetryPolicy, delivery: &WebhookDelivery) {
+    let attempts = delivery.attempts as u32 + 1;
+    let outcome = sender
+        .attempt(
+            &delivery.method,
+            &delivery.url,
+            &delivery.content_type,
+            &delivery.body,
+            &delivery.headers,
+            delivery.signing_secret.as_deref(),
+        )
+        .await;
+
+    let attempt = match outcome {
+        AttemptOutcome::Delivered { status, body } => {
+            tracing::info!(
+                "Webhook {} delivered to {} (attempt {})",
+                delivery.event,
+                delivery.url,
+                attempts
+            );
+            DeliveryAttempt {
+                status: DeliveryStatus::Delivered,
+                retry_in: 0,
+                response_status: Some(status as i64),
+                response_body: Some(body),
+                error: None,
+            }
+        }
+        AttemptOutcome::Rejected { status, body, error } => {
+            tracing::error!("Webhook {} to {} rejected: {}", delivery.event, delivery.url, error);
+            DeliveryAttempt {
+                status: DeliveryStatus::Failed,
+                retry_in: 0,
+                response_status: status.map(i64::from),
+                response_body: body,
+                error: Some(error),
+            }
+        }
+        AttemptOutcome::Failed { status, body, error } => {
+            let status_after = if attempts >= policy.max_attempts {
+                tracing::error!(
+                    "Webhook {} to {} failed after {} attempts: {}",
+                    delivery.event,
+                    delivery.url,
+                    attempts,
+                    error
+                );
+                DeliveryStatus::Failed
+            } else {
+                tracing::warn!(
+                    "Webhook {} to {} failed (attempt {}), retrying in {}s: {}",
+                    delivery.event,
+                    delivery.url,
+                    attempts,
+                    policy.delay(attempts),
+                    error
+                );
+                DeliveryStatus::Pending
+            };
+            DeliveryAttempt {
+                status: status_after,
+                retry_in: policy.delay(attempts),
+                response_status: status.map(i64::from),
+                response_body: body,
+                error: Some(error),
+            }
+        }
+    };
+
+    record(db, delivery, attempt).await;
+}
+
+async fn record(db: &SqlitePool, delivery: &WebhookDelivery, attempt: DeliveryAttempt) {
+    match attempt.status {
+        DeliveryStatus::Delivered => DELIVERED.fetch_add(1, Ordering::Relaxed),
+        DeliveryStatus::Failed => FAILED.fetch_add(1, Ordering::Relaxed),
+        DeliveryStatus::Pending => 0,
+    };
+
+    if let Err(e) = db::record_webhook_attempt(db, delivery.id, &attempt).await {
+        tracing::error!("Failed to record attempt of webhook delivery {}: {}", delivery.id, e);
+    }
+}
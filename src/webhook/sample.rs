@@ -0,0 +1,55 @@
+//! Made-up data for previewing and testing webhooks without a real event.
+
+use crate::models::{Client, ClientStatus};
+use crate::webhook::sender::WebhookEvent;
+use serde_json::json;
+
+/// A client to render previews against when none is given
+pub fn sample_client() -> Client {
+    let now = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+
+    Client {
+        id: 0,
+        name: "example".to_string(),
+        description: Some("Sample client".to_string()),
+        local_host: "127.0.0.1".to_string(),
+        local_port: 8080,
+        remote_server: "bore.example.com".to_string(),
+        remote_port: 0,
+        assigned_port: Some(41234),
+        secret: None,
+        status: ClientStatus::Connected,
+        auto_start: false,
+        auto_reconnect: true,
+        reconnect_max_attempts: 0,
+        reconnect_max_delay: 60,
+        upload_limit: 0,
+        download_limit: 0,
+        webhook_url: None,
+        webhook_format: "json".to_string(),
+        webhook_template: None,
+        webhook_secret: None,
+        health_check_enabled: false,
+        health_check_interval: 30,
+        health_check_path: None,
+        created_at: now.clone(),
+        updated_at: now,
+        last_connected_at: None,
+        error_message: None,
+    }
+}
+
+/// Details the caller of a real event would pass along
+pub fn sample_extra(event: &WebhookEvent) -> serde_json::Value {
+    match event {
+        WebhookEvent::Connected => json!({ "reconnect_attempts": 0 }),
+        WebhookEvent::Disconnected | WebhookEvent::Stopped => json!({ "uptime_seconds": 3600 }),
+        WebhookEvent::Reconnecting | WebhookEvent::TargetDown => json!({ "error": "Connection refused" }),
+        WebhookEvent::TargetRecovered => json!({ "down_seconds": 120 }),
+        WebhookEvent::Crashed | WebhookEvent::Error => json!({
+            "error": "Connection refused",
+            "uptime_seconds": 3600,
+        }),
+        WebhookEvent::Started => json!({}),
+    }
+}
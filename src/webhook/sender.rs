@@ -1,195 +1,285 @@
-use crate::models::Client;
+use crate::models::{Client, Server, SessionType, Webhook, WebhookAuth};
+use crate::webhook::format::{render_message, WebhookFormat};
+use crate::webhook::signature;
+use crate::webhook::ssrf::{blocked_cause, BlockedDestination, DestinationPolicy, PolicyResolver};
+use base64::Engine;
 use handlebars::Handlebars;
+use reqwest::redirect::Policy;
+use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
+use std::collections::BTreeMap;
+use std::sync::Arc;
 use std::time::Duration;
-use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// Most bytes of a receiver's response kept with a delivery
+const MAX_RESPONSE_BODY: usize = 1024;
+
+/// Redirects followed by a single attempt
+const MAX_REDIRECTS: usize = 5;
+
+/// Result of a single delivery attempt
+#[derive(Debug)]
+pub enum AttemptOutcome {
+    /// The receiver answered 2xx
+    Delivered { status: u16, body: String },
+    /// Not worth retrying: the URL is refused or the receiver answered 4xx
+    Rejected {
+        status: Option<u16>,
+        body: Option<String>,
+        error: String,
+    },
+    /// May succeed later: network errors, timeouts, 5xx, 408 and 429
+    Failed {
+        status: Option<u16>,
+        body: Option<String>,
+        error: String,
+    },
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum WebhookEvent {
     Connected,
     Disconnected,
+    Reconnecting,
+    /// Nothing answers on the client's local target any more
+    TargetDown,
+    /// The local target answers again after being down
+    TargetRecovered,
+    /// An embedded server began listening
+    Started,
+    /// An embedded server was stopped
+    Stopped,
+    /// An embedded server's listener failed
+    Crashed,
+    /// The server or client was put into the error state, by a task that ended on its
+    /// own or a failed auto-start
+    Error,
 }
 
 impl WebhookEvent {
+    pub const ALL: [WebhookEvent; 9] = [
+        WebhookEvent::Connected,
+        WebhookEvent::Disconnected,
+        WebhookEvent::Reconnecting,
+        WebhookEvent::TargetDown,
+        WebhookEvent::TargetRecovered,
+        WebhookEvent::Started,
+        WebhookEvent::Stopped,
+        WebhookEvent::Crashed,
+        WebhookEvent::Error,
+    ];
+
+    pub fn parse(event: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|known| known.as_str() == event)
+    }
+
     pub fn as_str(&self) -> &str {
         match self {
             WebhookEvent::Connected => "connected",
             WebhookEvent::Disconnected => "disconnected",
+            WebhookEvent::Reconnecting => "reconnecting",
+            WebhookEvent::TargetDown => "target_down",
+            WebhookEvent::TargetRecovered => "target_recovered",
+            WebhookEvent::Started => "started",
+            WebhookEvent::Stopped => "stopped",
+            WebhookEvent::Crashed => "crashed",
+            WebhookEvent::Error => "error",
         }
     }
 }
 
+/// Server or client a webhook reports on, carrying its webhook settings
 #[derive(Debug, Clone)]
-pub struct WebhookSender {
-    client: reqwest::Client,
+pub enum WebhookEntity {
+    Server(Server),
+    Client(Client),
 }
 
-impl WebhookSender {
-    pub fn new() -> Self {
-        let client = reqwest::Client::builder()
-            .timeout(Duration::from_secs(10))
-            .build()
-            .unwrap_or_else(|e| {
-                tracing::warn!("Failed to build webhook HTTP client with custom config: {}. Using default client.", e);
-                reqwest::Client::new()
-            });
-
-        Self { client }
+impl WebhookEntity {
+    /// Prefix of the entity's event names
+    pub fn kind(&self) -> &'static str {
+        match self {
+            WebhookEntity::Server(_) => "server",
+            WebhookEntity::Client(_) => "client",
+        }
     }
 
-    /// Validate webhook URL to prevent SSRF attacks
-    fn validate_webhook_url(url: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        let parsed = url::Url::parse(url)
-            .map_err(|e| format!("Invalid webhook URL: {}", e))?;
-
-        // Only allow HTTP and HTTPS schemes
-        let scheme = parsed.scheme();
-        if scheme != "http" && scheme != "https" {
-            return Err(format!("Invalid webhook URL scheme: {}. Only http and https are allowed.", scheme).into());
+    pub fn entity_type(&self) -> SessionType {
+        match self {
+            WebhookEntity::Server(_) => SessionType::Server,
+            WebhookEntity::Client(_) => SessionType::Client,
         }
+    }
 
-        // Check for private/internal IP addresses
-        if let Some(host) = parsed.host_str() {
-            // Resolve hostname to IP if it's not already an IP
-            if let Ok(ip) = host.parse::<IpAddr>() {
-                if Self::is_private_ip(&ip) {
-                    return Err("Webhook URL points to private IP address. This is not allowed for security reasons.".into());
-                }
-            } else {
-                // For hostnames, check common private network patterns
-                let host_lower = host.to_lowercase();
-                if host_lower == "localhost"
-                    || host_lower.ends_with(".local")
-                    || host_lower.ends_with(".internal") {
-                    return Err(format!("Webhook hostname '{}' appears to be internal. This is not allowed for security reasons.", host).into());
-                }
-            }
+    pub fn id(&self) -> i64 {
+        match self {
+            WebhookEntity::Server(server) => server.id,
+            WebhookEntity::Client(client) => client.id,
         }
+    }
 
-        Ok(())
+    /// The entity's own webhook, if it has a URL
+    pub fn webhook_config(&self) -> Option<WebhookConfig> {
+        let (url, format, template, secret) = match self {
+            WebhookEntity::Server(server) => (
+                &server.webhook_url,
+                &server.webhook_format,
+                &server.webhook_template,
+                &server.webhook_secret,
+            ),
+            WebhookEntity::Client(client) => (
+                &client.webhook_url,
+                &client.webhook_format,
+                &client.webhook_template,
+                &client.webhook_secret,
+            ),
+        };
+
+        url.clone().map(|url| WebhookConfig {
+            webhook_id: None,
+            method: "POST".to_string(),
+            url,
+            format: format.clone(),
+            template: template.clone(),
+            headers: BTreeMap::new(),
+            secret: secret.clone(),
+        })
     }
+}
 
-    fn is_private_ip(ip: &IpAddr) -> bool {
-        match ip {
-            IpAddr::V4(ipv4) => {
-                ipv4.is_private()
-                    || ipv4.is_loopback()
-                    || ipv4.is_link_local()
-                    || ipv4.is_broadcast()
-                    || ipv4.is_documentation()
-                    || *ipv4 == Ipv4Addr::new(0, 0, 0, 0) // 0.0.0.0
-            }
-            IpAddr::V6(ipv6) => {
-                ipv6.is_loopback()
-                    || ipv6.is_unspecified()
-                    || *ipv6 == Ipv6Addr::new(0, 0, 0, 0, 0, 0, 0, 1) // ::1
-            }
-        }
+impl From<Server> for WebhookEntity {
+    fn from(server: Server) -> Self {
+        WebhookEntity::Server(server)
     }
+}
 
-    pub async fn send(
-        &self,
-        url: &str,
-        event: WebhookEvent,
-        client_data: &Client,
-        extra_data: serde_json::Value,
-    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        // Validate URL before sending
-        Self::validate_webhook_url(url)?;
+impl From<Client> for WebhookEntity {
+    fn from(client: Client) -> Self {
+        WebhookEntity::Client(client)
+    }
+}
 
-        let format = &client_data.webhook_format;
+/// Where and how a webhook is sent: the entity's own webhook or a subscription
+#[derive(Debug, Clone)]
+pub struct WebhookConfig {
+    /// Subscription, none for the entity's own webhook
+    pub webhook_id: Option<i64>,
+    pub method: String,
+    pub url: String,
+    pub format: String,
+    pub template: Option<String>,
+    /// Extra request headers, including credentials
+    pub headers: BTreeMap<String, String>,
+    pub secret: Option<String>,
+}
 
-        let (body, content_type) = if format == "custom" {
-            let body = self.render_custom_template(event.clone(), client_data, extra_data)?;
-            (body, "text/plain")
-        } else {
-            let body = self.build_json_payload(event, client_data, extra_data)?;
-            (body, "application/json")
+impl From<Webhook> for WebhookConfig {
+    fn from(webhook: Webhook) -> Self {
+        let mut headers = webhook.headers;
+        let authorization = match webhook.auth {
+            Some(WebhookAuth::Basic { username, password }) => Some(format!(
+                "Basic {}",
+                base64::engine::general_purpose::STANDARD.encode(format!("{}:{}", username, password))
+            )),
+            Some(WebhookAuth::Bearer { token }) => Some(format!("Bearer {}", token)),
+            Some(WebhookAuth::None) | None => None,
         };
+        if let Some(authorization) = authorization {
+            // Authentication settings win over a header of the same name
+            headers.retain(|name, _| !name.eq_ignore_ascii_case("authorization"));
+            headers.insert("Authorization".to_string(), authorization);
+        }
 
-        self.send_with_retry(url, body, content_type).await?;
-        Ok(())
+        Self {
+            webhook_id: Some(webhook.id),
+            method: webhook.method,
+            url: webhook.url,
+            format: webhook.format,
+            template: webhook.template,
+            headers,
+            secret: webhook.secret,
+        }
     }
+}
 
-    fn build_json_payload(
-        &self,
-        event: WebhookEvent,
-        client: &Client,
-        extra_data: serde_json::Value,
-    ) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
-        let timestamp = chrono::Utc::now().to_rfc3339();
+#[derive(Debug, Clone)]
+pub struct WebhookSender {
+    client: reqwest::Client,
+    policy: Arc<DestinationPolicy>,
+}
 
-        let mut payload = json!({
-            "event": format!("client.{}", event.as_str()),
-            "timestamp": timestamp,
-            "client_id": client.id,
-            "client_name": &client.name,
-        });
+impl WebhookSender {
+    /// Sender refusing destinations the policy does not allow, including through DNS
+    /// answers and redirects. Fails rather than fall back to a client without those
+    /// checks.
+    pub fn new(policy: Arc<DestinationPolicy>) -> reqwest::Result<Self> {
+        let redirect_policy = policy.clone();
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .dns_resolver(Arc::new(PolicyResolver::new(policy.clone())))
+            .redirect(Policy::custom(move |attempt| {
+                if attempt.previous().len() >= MAX_REDIRECTS {
+                    attempt.error(BlockedDestination("Too many redirects".to_string()))
+                } else if let Err(e) = redirect_policy.check_url(attempt.url()) {
+                    attempt.error(e)
+                } else {
+                    attempt.follow()
+                }
+            }))
+            .build()?;
 
-        if let Some(desc) = &client.description {
-            payload["description"] = json!(desc);
-        }
+        Ok(Self { client, policy })
+    }
 
-        match event {
-            WebhookEvent::Connected => {
-                payload["local_host"] = json!(&client.local_host);
-                payload["local_port"] = json!(client.local_port);
-                payload["remote_server"] = json!(&client.remote_server);
-                if let Some(port) = client.assigned_port {
-                    payload["assigned_port"] = json!(port);
-                }
+    /// Render the body of a webhook and its content type according to its format
+    pub fn render(
+        event: WebhookEvent,
+        entity: &WebhookEntity,
+        config: &WebhookConfig,
+        extra_data: serde_json::Value,
+    ) -> Result<(String, &'static str), Box<dyn std::error::Error + Send + Sync>> {
+        let format = WebhookFormat::parse(&config.format)
+            .ok_or_else(|| format!("Unknown webhook format '{}'", config.format))?;
+
+        match format {
+            WebhookFormat::Json => {
+                let body = Self::build_json_payload(event, entity, extra_data)?;
+                Ok((body, "application/json"))
             }
-            WebhookEvent::Disconnected => {
-                if let Some(uptime) = extra_data.get("uptime_seconds") {
-                    payload["uptime_seconds"] = uptime.clone();
-                }
+            WebhookFormat::Custom => {
+                let template = config.template.as_deref().ok_or("No webhook template configured")?;
+                let body = Self::render_custom_template(event, entity, template, extra_data)?;
+                Ok((body, "text/plain"))
+            }
+            _ => {
+                let data = payload_data(&event, entity, &extra_data);
+                Ok(render_message(format, &event, &data, &config.url)?)
             }
         }
+    }
+
+    fn build_json_payload(
+        event: WebhookEvent,
+        entity: &WebhookEntity,
+        extra_data: serde_json::Value,
+    ) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        let mut payload = payload_data(&event, entity, &extra_data);
+        payload.insert("event".to_string(), json!(format!("{}.{}", entity.kind(), event.as_str())));
 
         serde_json::to_string(&payload)
             .map_err(|e| format!("Failed to serialize webhook payload: {}", e).into())
     }
 
     fn render_custom_template(
-        &self,
         event: WebhookEvent,
-        client: &Client,
+        entity: &WebhookEntity,
+        template: &str,
         extra_data: serde_json::Value,
     ) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
-        let template = client
-            .webhook_template
-            .as_ref()
-            .ok_or("No webhook template configured")?;
-
-        let timestamp = chrono::Utc::now().to_rfc3339();
-
-        let mut data = serde_json::Map::new();
+        let mut data = payload_data(&event, entity, &extra_data);
         data.insert("event".to_string(), json!(event.as_str()));
-        data.insert("timestamp".to_string(), json!(timestamp));
-        data.insert("client_id".to_string(), json!(client.id));
-        data.insert("client_name".to_string(), json!(&client.name));
-
-        if let Some(desc) = &client.description {
-            data.insert("description".to_string(), json!(desc));
-        }
-
-        match event {
-            WebhookEvent::Connected => {
-                data.insert("local_host".to_string(), json!(&client.local_host));
-                data.insert("local_port".to_string(), json!(client.local_port));
-                data.insert("remote_server".to_string(), json!(&client.remote_server));
-                if let Some(port) = client.assigned_port {
-                    data.insert("assigned_port".to_string(), json!(port));
-                }
-            }
-            WebhookEvent::Disconnected => {
-                if let Some(uptime) = extra_data.get("uptime_seconds") {
-                    data.insert("uptime_seconds".to_string(), uptime.clone());
-                }
-            }
-        }
 
         let handlebars = Handlebars::new();
         let rendered = handlebars.render_template(template, &data)?;
@@ -197,83 +287,179 @@ impl WebhookSender {
         Ok(rendered)
     }
 
-    async fn send_with_retry(
+    /// Make a single delivery attempt with the given method and extra headers, signing
+    /// the body when a secret is given; retrying is up to the caller
+    pub async fn attempt(
         &self,
+        method: &str,
         url: &str,
-        body: String,
         content_type: &str,
-    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        let max_retries = 3;
-        let mut last_error = None;
-
-        for attempt in 1..=max_retries {
-            match self
-                .client
-                .post(url)
-                .header("Content-Type", content_type)
-                .body(body.clone())
-                .send()
-                .await
-            {
-                Ok(response) => {
-                    let status = response.status();
-                    if status.is_success() {
-                        tracing::info!(
-                            "Webhook sent successfully to {} (attempt {})",
-                            url,
-                            attempt
-                        );
-                        return Ok(());
-                    } else if status.is_client_error() {
-                        // Don't retry 4xx errors - they won't succeed on retry
-                        let error_msg = format!("HTTP {} (client error, not retrying)", status);
-                        tracing::error!("Webhook to {} failed: {}", url, error_msg);
-                        return Err(error_msg.into());
-                    } else {
-                        // Retry 5xx server errors
-                        tracing::warn!(
-                            "Webhook returned status {} (attempt {})",
-                            status,
-                            attempt
-                        );
-                        last_error = Some(format!("HTTP {}", status));
-                    }
+        body: &str,
+        headers: &BTreeMap<String, String>,
+        secret: Option<&str>,
+    ) -> AttemptOutcome {
+        let checked = url::Url::parse(url)
+            .map_err(|e| BlockedDestination(format!("Invalid webhook URL: {}", e)))
+            .and_then(|parsed| self.policy.check_url(&parsed));
+        if let Err(e) = checked {
+            return AttemptOutcome::Rejected {
+                status: None,
+                body: None,
+                error: e.to_string(),
+            };
+        }
+
+        let Ok(method) = reqwest::Method::from_bytes(method.as_bytes()) else {
+            return AttemptOutcome::Rejected {
+                status: None,
+                body: None,
+                error: format!("Invalid webhook method: {}", method),
+            };
+        };
+
+        let mut request = self
+            .client
+            .request(method, url)
+            .header("Content-Type", content_type);
+        for (name, value) in headers {
+            request = request.header(name, value);
+        }
+        if let Some(secret) = secret {
+            let timestamp = chrono::Utc::now().timestamp().to_string();
+            request = request
+                .header(signature::SIGNATURE_HEADER, signature::sign(secret, &timestamp, body.as_bytes()))
+                .header(signature::TIMESTAMP_HEADER, timestamp);
+        }
+
+        let response = match request.body(body.to_string()).send().await {
+            Ok(response) => response,
+            Err(e) => {
+                // A refused destination stays refused
+                if let Some(blocked) = blocked_cause(&e) {
+                    return AttemptOutcome::Rejected {
+                        status: None,
+                        body: None,
+                        error: blocked.to_string(),
+                    };
                 }
-                Err(e) => {
-                    tracing::warn!("Webhook failed (attempt {}): {}", attempt, e);
-                    last_error = Some(e.to_string());
+                // The URL may carry a token, and is shown with the delivery already
+                let e = e.without_url();
+                let mut error = e.to_string();
+                for cause in std::iter::successors(std::error::Error::source(&e), |cause| cause.source()) {
+                    error.push_str(&format!(": {}", cause));
                 }
+                return AttemptOutcome::Failed {
+                    status: None,
+                    body: None,
+                    error,
+                };
             }
+        };
 
-            if attempt < max_retries {
-                let delay = Duration::from_millis(100 * 2u64.pow(attempt - 1));
-                tokio::time::sleep(delay).await;
-            }
+        let status = response.status();
+        let mut body = response.text().await.unwrap_or_default();
+        if body.len() > MAX_RESPONSE_BODY {
+            let end = (0..=MAX_RESPONSE_BODY).rev().find(|i| body.is_char_boundary(*i)).unwrap_or(0);
+            body.truncate(end);
         }
 
-        let error_msg = last_error.unwrap_or_else(|| "Unknown error".to_string());
-        tracing::error!("Webhook to {} failed after {} attempts: {}", url, max_retries, error_msg);
-        Err(error_msg.into())
+        if status.is_success() {
+            AttemptOutcome::Delivered {
+                status: status.as_u16(),
+                body,
+            }
+        } else if status.is_client_error()
+            && status != StatusCode::REQUEST_TIMEOUT
+            && status != StatusCode::TOO_MANY_REQUESTS
+        {
+            // Other 4xx answers won't change on retry
+            AttemptOutcome::Rejected {
+                status: Some(status.as_u16()),
+                body: Some(body),
+                error: format!("HTTP {}", status),
+            }
+        } else {
+            AttemptOutcome::Failed {
+                status: Some(status.as_u16()),
+                body: Some(body),
+                error: format!("HTTP {}", status),
+            }
+        }
     }
 }
 
-impl Default for WebhookSender {
-    fn default() -> Self {
-        Self::new()
+/// Fields of a webhook payload other than the event name, shared by the JSON format and
+/// custom templates
+fn payload_data(
+    event: &WebhookEvent,
+    entity: &WebhookEntity,
+    extra_data: &serde_json::Value,
+) -> serde_json::Map<String, serde_json::Value> {
+    let mut data = serde_json::Map::new();
+    let mut set = |key: &str, value: serde_json::Value| {
+        data.insert(key.to_string(), value);
+    };
+    let extra = |key: &str| extra_data.get(key).cloned();
+
+    set("timestamp", json!(chrono::Utc::now().to_rfc3339()));
+
+    match entity {
+        WebhookEntity::Client(client) => {
+            set("client_id", json!(client.id));
+            set("client_name", json!(&client.name));
+            if let Some(desc) = &client.description {
+                set("description", json!(desc));
+            }
+
+            match event {
+                WebhookEvent::Connected => {
+                    set("local_host", json!(&client.local_host));
+                    set("local_port", json!(client.local_port));
+                    set("remote_server", json!(&client.remote_server));
+                    if let Some(port) = client.assigned_port {
+                        set("assigned_port", json!(port));
+                    }
+                }
+                WebhookEvent::Reconnecting => {
+                    set("remote_server", json!(&client.remote_server));
+                }
+                WebhookEvent::TargetDown | WebhookEvent::TargetRecovered => {
+                    set("local_host", json!(&client.local_host));
+                    set("local_port", json!(client.local_port));
+                }
+                _ => {}
+            }
+        }
+        WebhookEntity::Server(server) => {
+            set("server_id", json!(server.id));
+            set("server_name", json!(&server.name));
+            if let Some(desc) = &server.description {
+                set("description", json!(desc));
+            }
+
+            if let WebhookEvent::Started = event {
+                set("bind_addr", json!(&server.bind_addr));
+                set("control_port", json!(server.control_port));
+                set("port_range_start", json!(server.port_range_start));
+                set("port_range_end", json!(server.port_range_end));
+            }
+        }
     }
-}
 
-/// Fire-and-forget webhook sending
-pub fn send_webhook(
-    url: String,
-    event: WebhookEvent,
-    client: Client,
-    extra_data: serde_json::Value,
-) {
-    tokio::spawn(async move {
-        let sender = WebhookSender::new();
-        if let Err(e) = sender.send(&url, event, &client, extra_data).await {
-            tracing::error!("Webhook error: {}", e);
+    // Details known only to the caller
+    let extra_keys: &[&str] = match event {
+        WebhookEvent::Connected => &["reconnect_attempts"],
+        WebhookEvent::Disconnected | WebhookEvent::Stopped => &["uptime_seconds"],
+        WebhookEvent::Reconnecting | WebhookEvent::TargetDown => &["error"],
+        WebhookEvent::TargetRecovered => &["down_seconds"],
+        WebhookEvent::Crashed | WebhookEvent::Error => &["error", "uptime_seconds"],
+        WebhookEvent::Started => &[],
+    };
+    for key in extra_keys {
+        if let Some(value) = extra(key) {
+            set(key, value);
         }
-    });
+    }
+
+    data
 }
@@ -0,0 +1,116 @@
+//! Signatures proving webhooks come from borui.
+//!
+//! When a webhook has a secret, every attempt carries two headers:
+//!
+//! - `X-Borui-Timestamp`: Unix time of the attempt, in seconds
+//! - `X-Borui-Signature`: `sha256=` followed by the hex HMAC-SHA256, keyed with the secret,
+//!   of the timestamp, a `.` and the raw request body
+//!
+//! Receivers recompute the HMAC over the timestamp header and the body exactly as
+//! received, compare it in constant time, and reject timestamps too far from their own
+//! clock so captured requests cannot be replayed later.
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+pub const TIMESTAMP_HEADER: &str = "X-Borui-Timestamp";
+pub const SIGNATURE_HEADER: &str = "X-Borui-Signature";
+
+const SIGNATURE_PREFIX: &str = "sha256=";
+
+fn mac(secret: &str, timestamp: &str, body: &[u8]) -> Hmac<Sha256> {
+    // HMAC accepts keys of any length
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes any key length");
+    mac.update(timestamp.as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    mac
+}
+
+/// Value of the signature header for a body sent at `timestamp`
+pub fn sign(secret: &str, timestamp: &str, body: &[u8]) -> String {
+    let digest = mac(secret, timestamp, body).finalize().into_bytes();
+    format!("{}{}", SIGNATURE_PREFIX, hex::encode(digest))
+}
+
+/// Check the signature header of a received webhook. `tolerance_seconds` bounds how far
+/// the timestamp may be from now; 0 skips that check.
+pub fn verify(secret: &str, timestamp: &str, body: &[u8], signature: &str, tolerance_seconds: u64) -> bool {
+    if tolerance_seconds > 0 {
+        let Ok(sent_at) = timestamp.parse::<i64>() else {
+            return false;
+        };
+        if chrono::Utc::now().timestamp().abs_diff(sent_at) > tolerance_seconds {
+            return false;
+        }
+    }
+
+    let Some(digest) = signature
+        .strip_prefix(SIGNATURE_PREFIX)
+        .and_then(|digest| hex::decode(digest).ok())
+    else {
+        return false;
+    };
+
+    mac(secret, timestamp, body).verify_slice(&digest).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "s3cret";
+    const BODY: &[u8] = br#"{"event":"server.started"}"#;
+
+    fn now() -> String {
+        chrono::Utc::now().timestamp().to_string()
+    }
+
+    #[test]
+    fn signed_body_verifies() {
+        let timestamp = now();
+        let signature = sign(SECRET, &timestamp, BODY);
+
+        assert!(signature.starts_with(SIGNATURE_PREFIX));
+        assert!(verify(SECRET, &timestamp, BODY, &signature, 300));
+    }
+
+    #[test]
+    fn tampered_body_is_rejected() {
+        let timestamp = now();
+        let signature = sign(SECRET, &timestamp, BODY);
+
+        assert!(!verify(SECRET, &timestamp, br#"{"event":"server.stopped"}"#, &signature, 300));
+    }
+
+    #[test]
+    fn wrong_secret_or_timestamp_is_rejected() {
+        let timestamp = now();
+        let signature = sign(SECRET, &timestamp, BODY);
+
+        assert!(!verify("other", &timestamp, BODY, &signature, 300));
+        let later = (timestamp.parse::<i64>().unwrap() + 1).to_string();
+        assert!(!verify(SECRET, &later, BODY, &signature, 300));
+    }
+
+    #[test]
+    fn stale_timestamp_is_rejected() {
+        let timestamp = (chrono::Utc::now().timestamp() - 600).to_string();
+        let signature = sign(SECRET, &timestamp, BODY);
+
+        assert!(!verify(SECRET, &timestamp, BODY, &signature, 300));
+        // A tolerance of 0 leaves replay protection to the receiver
+        assert!(verify(SECRET, &timestamp, BODY, &signature, 0));
+    }
+
+    #[test]
+    fn malformed_signature_is_rejected() {
+        let timestamp = now();
+        let signature = sign(SECRET, &timestamp, BODY);
+        let digest = signature.strip_prefix(SIGNATURE_PREFIX).unwrap();
+
+        assert!(!verify(SECRET, &timestamp, BODY, digest, 300));
+        assert!(!verify(SECRET, &timestamp, BODY, "sha256=not-hex", 300));
+        assert!(!verify(SECRET, "yesterday", BODY, &signature, 300));
+    }
+}
@@ -0,0 +1,340 @@
+//! Destinations webhooks may be sent to.
+//!
+//! Webhooks to loopback, private, link-local and other internal addresses are refused
+//! unless an administrator allows them with `WEBHOOK_ALLOWED_HOSTS`. Host names are
+//! checked on the addresses they resolve to when connecting, so a name cannot be pointed
+//! at an internal address once it has been checked.
+
+use ipnet::IpNet;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use url::{Host, Url};
+
+/// Why a webhook destination was refused
+#[derive(Debug)]
+pub struct BlockedDestination(pub String);
+
+impl fmt::Display for BlockedDestination {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for BlockedDestination {}
+
+/// Internal hosts and networks webhooks may be sent to; every public address is allowed
+#[derive(Debug, Clone, Default)]
+pub struct DestinationPolicy {
+    /// Host names allowed whatever they resolve to; `*.example.com` covers subdomains
+    hosts: Vec<String>,
+    /// Addresses allowed although they are internal
+    networks: Vec<IpNet>,
+}
+
+impl DestinationPolicy {
+    /// Parse a comma separated list of host names and CIDRs
+    pub fn parse(list: &str) -> Result<Self, String> {
+        let mut policy = Self::default();
+
+        for entry in list.split(|c: char| c == ',' || c.is_whitespace()).filter(|entry| !entry.is_empty()) {
+            if let Ok(network) = entry.parse::<IpNet>() {
+                policy.networks.push(network);
+            } else if let Ok(ip) = entry.parse::<IpAddr>() {
+                policy.networks.push(IpNet::from(ip));
+            } else if is_host_pattern(entry) {
+                policy.hosts.push(entry.to_lowercase());
+            } else {
+                return Err(format!("invalid host or CIDR '{}'", entry));
+            }
+        }
+
+        Ok(policy)
+    }
+
+    /// Check a URL before connecting: its scheme, and its host when that is an address
+    /// or a name of the local network. Other names are checked once resolved.
+    pub fn check_url(&self, url: &Url) -> Result<(), BlockedDestination> {
+        let scheme = url.scheme();
+        if scheme != "http" && scheme != "https" {
+            return Err(BlockedDestination(format!(
+                "Invalid webhook URL scheme: {}. Only http and https are allowed.",
+                scheme
+            )));
+        }
+
+        match url.host() {
+            Some(Host::Ipv4(ip)) => self.check_addr(None, IpAddr::V4(ip)),
+            Some(Host::Ipv6(ip)) => self.check_addr(None, IpAddr::V6(ip)),
+            Some(Host::Domain(host)) => {
+                let host = host.to_lowercase();
+                let internal = host == "localhost"
+                    || host.ends_with(".localhost")
+                    || host.ends_with(".local")
+                    || host.ends_with(".internal");
+                if internal && !self.allows_host(&host) {
+                    return Err(BlockedDestination(format!(
+                        "Webhook hostname '{}' appears to be internal. Allow it with WEBHOOK_ALLOWED_HOSTS if intended.",
+                        host
+                    )));
+                }
+                Ok(())
+            }
+            None => Err(BlockedDestination("Webhook URL has no host".to_string())),
+        }
+    }
+
+    /// Check an address a webhook would connect to, given the host name it was resolved
+    /// from if any
+    pub fn check_addr(&self, host: Option<&str>, ip: IpAddr) -> Result<(), BlockedDestination> {
+        let ip = ip.to_canonical();
+
+        if !is_internal(&ip)
+            || self.networks.iter().any(|network| network.contains(&ip))
+            || host.is_some_and(|host| self.allows_host(host))
+        {
+            return Ok(());
+        }
+
+        Err(BlockedDestination(match host {
+            Some(host) => format!(
+                "Webhook host '{}' resolves to internal address {}. Allow it with WEBHOOK_ALLOWED_HOSTS if intended.",
+                host, ip
+            ),
+            None => format!(
+                "Webhook URL points to internal address {}. Allow it with WEBHOOK_ALLOWED_HOSTS if intended.",
+                ip
+            ),
+        }))
+    }
+
+    fn allows_host(&self, host: &str) -> bool {
+        let host = host.trim_end_matches('.').to_lowercase();
+        self.hosts.iter().any(|allowed| match allowed.strip_prefix("*.") {
+            Some(domain) => host.ends_with(&format!(".{}", domain)),
+            None => *allowed == host,
+        })
+    }
+}
+
+/// DNS resolver of the webhook HTTP client, leaving out addresses the policy refuses
+pub struct PolicyResolver {
+    policy: Arc<DestinationPolicy>,
+}
+
+impl PolicyResolver {
+    pub fn new(policy: Arc<DestinationPolicy>) -> Self {
+        Self { policy }
+    }
+}
+
+impl Resolve for PolicyResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let policy = self.policy.clone();
+        Box::pin(async move {
+            let host = name.as_str().to_string();
+            let resolved: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), 0)).await?.collect();
+
+            let mut blocked = None;
+            let allowed: Vec<SocketAddr> = resolved
+                .into_iter()
+                .filter(|addr| match policy.check_addr(Some(&host), addr.ip()) {
+                    Ok(()) => true,
+                    Err(e) => {
+                        blocked = Some(e);
+                        false
+                    }
+                })
+                .collect();
+
+            match blocked {
+                Some(blocked) if allowed.is_empty() => Err(Box::new(blocked) as _),
+                _ => Ok(Box::new(allowed.into_iter()) as Addrs),
+            }
+        })
+    }
+}
+
+/// Find the policy violation behind an error of the HTTP client, if that is what it is
+pub fn blocked_cause<'a>(error: &'a (dyn std::error::Error + 'static)) -> Option<&'a BlockedDestination> {
+    let mut source = Some(error);
+    while let Some(error) = source {
+        if let Some(blocked) = error.downcast_ref::<BlockedDestination>() {
+            return Some(blocked);
+        }
+        source = error.source();
+    }
+    None
+}
+
+/// Host name, or a `*.` wildcard over a domain
+fn is_host_pattern(entry: &str) -> bool {
+    let name = entry.strip_prefix("*.").unwrap_or(entry);
+    !name.is_empty()
+        && name
+            .split('.')
+            .all(|label| !label.is_empty() && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-'))
+}
+
+/// Addresses of this host, private networks and ranges not routed on the internet
+fn is_internal(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_internal_v4(ip),
+        IpAddr::V6(ip) => is_internal_v6(ip),
+    }
+}
+
+fn is_internal_v4(ip: &Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+
+    ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        || a == 0 // "this network"
+        || (a == 100 && (b & 0xc0) == 64) // shared address space of carrier-grade NAT
+        || (a == 198 && (b & 0xfe) == 18) // benchmarking
+        || a >= 240 // reserved
+}
+
+fn is_internal_v6(ip: &Ipv6Addr) -> bool {
+    let segments = ip.segments();
+
+    ip.is_loopback()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        || (segments[0] & 0xfe00) == 0xfc00 // unique local
+        || (segments[0] & 0xffc0) == 0xfe80 // link-local
+        || (segments[0] & 0xffc0) == 0xfec0 // deprecated site-local
+        || (segments[0] == 0x2001 && segments[1] == 0x0db8) // documentation
+        || segments[..6] == [0; 6] // deprecated IPv4-compatible
+        // NAT64 and 6to4 addresses embed an IPv4 address
+        || (segments[..6] == [0x64, 0xff9b, 0, 0, 0, 0] && is_internal_v4(&embedded_v4(segments[6], segments[7])))
+        || (segments[0] == 0x2002 && is_internal_v4(&embedded_v4(segments[1], segments[2])))
+}
+
+fn embedded_v4(high: u16, low: u16) -> Ipv4Addr {
+    Ipv4Addr::from(((high as u32) << 16) | low as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v4(addr: &str) -> Ipv4Addr {
+        addr.parse().unwrap()
+    }
+
+    fn v6(addr: &str) -> Ipv6Addr {
+        addr.parse().unwrap()
+    }
+
+    fn check(policy: &DestinationPolicy, url: &str) -> Result<(), BlockedDestination> {
+        policy.check_url(&Url::parse(url).unwrap())
+    }
+
+    #[test]
+    fn internal_v4_ranges() {
+        for addr in ["10.1.2.3", "172.16.0.1", "172.31.255.255", "192.168.1.1", "127.0.0.1", "127.8.8.8"] {
+            assert!(is_internal_v4(&v4(addr)), "{}", addr);
+        }
+        for addr in ["100.64.0.1", "100.127.255.254", "169.254.169.254", "0.0.0.0", "255.255.255.255"] {
+            assert!(is_internal_v4(&v4(addr)), "{}", addr);
+        }
+
+        for addr in ["8.8.8.8", "172.32.0.1", "100.63.255.255", "100.128.0.1", "192.169.0.1"] {
+            assert!(!is_internal_v4(&v4(addr)), "{}", addr);
+        }
+    }
+
+    #[test]
+    fn internal_v6_ranges() {
+        for addr in ["::1", "::", "fc00::1", "fd12:3456::1", "fe80::1", "febf::1", "ff02::1"] {
+            assert!(is_internal_v6(&v6(addr)), "{}", addr);
+        }
+
+        for addr in ["2606:4700::1111", "2001:4860:4860::8888", "fbff::1", "fe7f::1"] {
+            assert!(!is_internal_v6(&v6(addr)), "{}", addr);
+        }
+    }
+
+    #[test]
+    fn internal_v6_embedding_v4() {
+        // NAT64 and 6to4 follow the embedded address
+        assert!(is_internal_v6(&v6("64:ff9b::10.0.0.1")));
+        assert!(is_internal_v6(&v6("64:ff9b::127.0.0.1")));
+        assert!(!is_internal_v6(&v6("64:ff9b::8.8.8.8")));
+        assert!(is_internal_v6(&v6("2002:c0a8:0101::1")));
+        assert!(is_internal_v6(&v6("2002:7f00:1::1")));
+        assert!(!is_internal_v6(&v6("2002:0808:0808::1")));
+
+        // IPv4-mapped addresses are checked as IPv4
+        let policy = DestinationPolicy::default();
+        assert!(check(&policy, "http://[::ffff:127.0.0.1]/").is_err());
+        assert!(check(&policy, "http://[::ffff:192.168.0.10]/").is_err());
+        assert!(check(&policy, "http://[::ffff:8.8.8.8]/").is_ok());
+    }
+
+    #[test]
+    fn urls_to_internal_addresses_are_refused() {
+        let policy = DestinationPolicy::default();
+
+        for url in ["http://127.0.0.1/hook", "https://10.0.0.5:8443/", "http://100.64.1.1/", "http://[::1]/", "http://[fd00::1]/"] {
+            assert!(check(&policy, url).is_err(), "{}", url);
+        }
+        assert!(check(&policy, "https://93.184.215.14/hook").is_ok());
+        assert!(check(&policy, "https://example.com/hook").is_ok());
+        assert!(check(&policy, "ftp://example.com/").is_err());
+    }
+
+    #[test]
+    fn internal_names_are_refused() {
+        let policy = DestinationPolicy::default();
+
+        for url in ["http://localhost:8080/", "http://LOCALHOST/", "http://api.localhost/", "http://printer.local/", "http://metadata.google.internal/"] {
+            assert!(check(&policy, url).is_err(), "{}", url);
+        }
+        assert!(check(&policy, "https://internal.example.com/").is_ok());
+    }
+
+    #[test]
+    fn allowed_hosts_let_internal_names_through() {
+        let policy = DestinationPolicy::parse("localhost, *.corp.internal").unwrap();
+
+        assert!(check(&policy, "http://localhost:3000/").is_ok());
+        assert!(check(&policy, "http://hooks.corp.internal/").is_ok());
+        assert!(check(&policy, "http://a.b.corp.internal/").is_ok());
+        // The wildcard covers subdomains only
+        assert!(check(&policy, "http://corp.internal/").is_err());
+        assert!(check(&policy, "http://other.internal/").is_err());
+
+        // A resolved name is allowed whatever it points to
+        assert!(policy.check_addr(Some("hooks.corp.internal"), IpAddr::V4(v4("10.0.0.1"))).is_ok());
+        assert!(policy.check_addr(Some("hooks.example.com"), IpAddr::V4(v4("10.0.0.1"))).is_err());
+    }
+
+    #[test]
+    fn allowed_networks_let_private_addresses_through() {
+        let policy = DestinationPolicy::parse("192.168.1.0/24 10.0.0.7 fd00::/8").unwrap();
+
+        assert!(check(&policy, "http://192.168.1.20/").is_ok());
+        assert!(check(&policy, "http://10.0.0.7/").is_ok());
+        assert!(check(&policy, "http://[fd00::1]/").is_ok());
+        assert!(check(&policy, "http://[::ffff:192.168.1.20]/").is_ok());
+        assert!(check(&policy, "http://192.168.2.20/").is_err());
+        assert!(check(&policy, "http://10.0.0.8/").is_err());
+        assert!(check(&policy, "http://[fd01::1]/").is_ok());
+        assert!(check(&policy, "http://[fc00::1]/").is_err());
+    }
+
+    #[test]
+    fn invalid_entries_are_rejected() {
+        assert!(DestinationPolicy::parse("").is_ok());
+        assert!(DestinationPolicy::parse("10.0.0.0/33").is_err());
+        assert!(DestinationPolicy::parse("http://example.com").is_err());
+        assert!(DestinationPolicy::parse("*.").is_err());
+    }
+}
@@ -11,22 +11,76 @@ use std::sync::Arc;
 use tokio::sync::mpsc;
 use uuid::Uuid;
 
+use crate::db;
+use crate::logs::TunnelLogLine;
+use crate::models::{ClientStatus, ServerStatus, SessionType};
 use crate::state::AppState;
 use crate::middleware::verify_token;
+use super::status::{Snapshot, StatusUpdate};
+use super::topic::{Topic, TopicEntity};
+
+/// Topics a new connection is subscribed to
+const DEFAULT_TOPICS: [Topic; 2] = [
+    Topic::Entity(TopicEntity::Server, None),
+    Topic::Entity(TopicEntity::Client, None),
+];
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", content = "data")]
 pub enum WsMessage {
     #[serde(rename = "server_status")]
-    ServerStatus(serde_json::Value),
+    ServerStatus(StatusUpdate<ServerStatus>),
     #[serde(rename = "client_status")]
-    ClientStatus(serde_json::Value),
+    ClientStatus(StatusUpdate<ClientStatus>),
+    /// Status of every server and client, sent once on connect
+    #[serde(rename = "snapshot")]
+    Snapshot(Snapshot),
     #[serde(rename = "connection_event")]
     ConnectionEvent(serde_json::Value),
+    #[serde(rename = "metrics")]
+    Metrics(serde_json::Value),
+    #[serde(rename = "log")]
+    Log(TunnelLogLine),
     #[serde(rename = "error")]
     Error(serde_json::Value),
     #[serde(rename = "pong")]
     Pong,
+    /// Topics the socket is subscribed to, after a change
+    #[serde(rename = "subscriptions")]
+    Subscriptions(serde_json::Value),
+}
+
+impl WsMessage {
+    /// Topic a broadcast message is routed by; messages without one reach every socket
+    fn topic(&self) -> Option<Topic> {
+        let id = |data: &serde_json::Value, key: &str| data.get(key).and_then(|id| id.as_i64());
+
+        match self {
+            WsMessage::ServerStatus(update) => Some(Topic::Entity(TopicEntity::Server, Some(update.id))),
+            WsMessage::ClientStatus(update) => Some(Topic::Entity(TopicEntity::Client, Some(update.id))),
+            WsMessage::ConnectionEvent(data) => {
+                Some(Topic::Entity(TopicEntity::Server, id(data, "server_id")))
+            }
+            WsMessage::Metrics(_) => Some(Topic::Metrics),
+            WsMessage::Log(line) => {
+                let entity = match line.entity_type {
+                    SessionType::Server => TopicEntity::Server,
+                    SessionType::Client => TopicEntity::Client,
+                };
+                Some(Topic::Logs(entity, Some(line.entity_id)))
+            }
+            WsMessage::Snapshot(_) | WsMessage::Error(_) | WsMessage::Pong | WsMessage::Subscriptions(_) => None,
+        }
+    }
+}
+
+/// Messages sent by WebSocket clients
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum ClientMessage {
+    Subscribe { topics: Vec<String> },
+    Unsubscribe { topics: Vec<String> },
+    Ping,
 }
 
 #[derive(Debug, Deserialize)]
@@ -35,7 +89,12 @@ pub struct WsQuery {
 }
 
 pub struct WsBroadcaster {
-    clients: Arc<DashMap<Uuid, mpsc::UnboundedSender<WsMessage>>>,
+    clients: Arc<DashMap<Uuid, Subscriber>>,
+}
+
+struct Subscriber {
+    tx: mpsc::UnboundedSender<WsMessage>,
+    topics: Vec<Topic>,
 }
 
 impl WsBroadcaster {
@@ -45,11 +104,18 @@ impl WsBroadcaster {
         }
     }
 
+    /// Send a message to the sockets subscribed to its topic
     pub fn broadcast(&self, message: WsMessage) {
+        let topic = message.topic();
         let mut dead_clients = Vec::new();
 
         for entry in self.clients.iter() {
-            if entry.value().send(message.clone()).is_err() {
+            let subscriber = entry.value();
+            let subscribed = match &topic {
+                Some(topic) => subscriber.topics.iter().any(|subscription| subscription.covers(topic)),
+                None => true,
+            };
+            if subscribed && subscriber.tx.send(message.clone()).is_err() {
                 dead_clients.push(*entry.key());
             }
         }
@@ -59,8 +125,58 @@ impl WsBroadcaster {
         }
     }
 
+    /// Announce a status transition of a server
+    pub fn server_status(&self, id: i64, status: ServerStatus, port: Option<i64>, error_message: Option<String>) {
+        self.broadcast(WsMessage::ServerStatus(StatusUpdate::new(id, status, port, error_message)));
+    }
+
+    /// Announce a status transition of a client
+    pub fn client_status(&self, id: i64, status: ClientStatus, port: Option<i64>, error_message: Option<String>) {
+        self.broadcast(WsMessage::ClientStatus(StatusUpdate::new(id, status, port, error_message)));
+    }
+
+    /// Whether any socket would receive messages of a topic
+    pub fn has_subscribers(&self, topic: &Topic) -> bool {
+        self.clients
+            .iter()
+            .any(|entry| entry.value().topics.iter().any(|subscription| subscription.covers(topic)))
+    }
+
+    /// Send a message to a single socket
+    fn send_to(&self, id: &Uuid, message: WsMessage) {
+        if let Some(subscriber) = self.clients.get(id) {
+            let _ = subscriber.tx.send(message);
+        }
+    }
+
+    fn subscribe(&self, id: &Uuid, topics: &[Topic]) {
+        if let Some(mut subscriber) = self.clients.get_mut(id) {
+            for topic in topics {
+                if !subscriber.topics.contains(topic) {
+                    subscriber.topics.push(*topic);
+                }
+            }
+        }
+    }
+
+    fn unsubscribe(&self, id: &Uuid, topics: &[Topic]) {
+        if let Some(mut subscriber) = self.clients.get_mut(id) {
+            subscriber.topics.retain(|topic| !topics.contains(topic));
+        }
+    }
+
+    fn topics(&self, id: &Uuid) -> Vec<String> {
+        self.clients
+            .get(id)
+            .map(|subscriber| subscriber.topics.iter().map(Topic::to_string).collect())
+            .unwrap_or_default()
+    }
+
     fn add_client(&self, id: Uuid, tx: mpsc::UnboundedSender<WsMessage>) {
-        self.clients.insert(id, tx);
+        self.clients.insert(id, Subscriber {
+            tx,
+            topics: DEFAULT_TOPICS.to_vec(),
+        });
     }
 
     fn remove_client(&self, id: &Uuid) {
@@ -83,6 +199,11 @@ pub async fn ws_handler(
     if let Some(token) = query.token {
         match verify_token(&token) {
             Ok(claims) => {
+                // Tokens outlive deleted users
+                if db::get_user_by_id(&state.db, claims.sub).await.is_err() {
+                    tracing::warn!("WebSocket token of deleted user {} rejected", claims.sub);
+                    return Err(StatusCode::UNAUTHORIZED);
+                }
                 tracing::debug!("WebSocket authenticated for user: {}", claims.username);
                 Ok(ws.on_upgrade(move |socket| handle_socket(socket, state, claims.username)))
             }
@@ -107,12 +228,27 @@ async fn handle_socket(socket: WebSocket, state: AppState, username: String) {
 
     tracing::info!("WebSocket client connected: {} (user: {})", client_id, username);
 
+    // The snapshot goes straight to the socket, ahead of updates broadcast while it
+    // loads: those wait in the channel and are applied on top of it, never under it
+    match Snapshot::load(&state.db).await {
+        Ok(snapshot) => {
+            if let Ok(json) = serde_json::to_string(&WsMessage::Snapshot(snapshot))
+                && sender.send(Message::Text(json.into())).await.is_err()
+            {
+                state.ws_broadcaster.remove_client(&client_id);
+                tracing::info!("WebSocket client disconnected: {}", client_id);
+                return;
+            }
+        }
+        Err(e) => tracing::error!("Failed to load status snapshot: {}", e),
+    }
+
     let mut send_task = tokio::spawn(async move {
         while let Some(msg) = rx.recv().await {
-            if let Ok(json) = serde_json::to_string(&msg) {
-                if sender.send(Message::Text(json.into())).await.is_err() {
-                    break;
-                }
+            if let Ok(json) = serde_json::to_string(&msg)
+                && sender.send(Message::Text(json.into())).await.is_err()
+            {
+                break;
             }
         }
     });
@@ -121,12 +257,10 @@ async fn handle_socket(socket: WebSocket, state: AppState, username: String) {
     let mut recv_task = tokio::spawn(async move {
         while let Some(Ok(msg)) = receiver.next().await {
             if let Message::Text(text) = msg {
-                // Handle incoming messages (ping, subscribe, etc.)
                 tracing::debug!("Received WS message: {}", text);
-                if text.contains("\"ping\"") {
-                    // Respond with pong
-                    ws_broadcaster.broadcast(WsMessage::Pong);
-                }
+                // Replies go to this socket only
+                let reply = handle_client_message(&ws_broadcaster, &client_id, &text);
+                ws_broadcaster.send_to(&client_id, reply);
             } else if let Message::Close(_) = msg {
                 break;
             }
@@ -141,3 +275,33 @@ async fn handle_socket(socket: WebSocket, state: AppState, username: String) {
     state.ws_broadcaster.remove_client(&client_id);
     tracing::info!("WebSocket client disconnected: {}", client_id);
 }
+
+fn handle_client_message(ws_broadcaster: &WsBroadcaster, client_id: &Uuid, text: &str) -> WsMessage {
+    let message = match serde_json::from_str::<ClientMessage>(text) {
+        Ok(message) => message,
+        Err(e) => {
+            return WsMessage::Error(serde_json::json!({
+                "message": format!("Invalid message: {}", e),
+            }));
+        }
+    };
+
+    let topics = match &message {
+        ClientMessage::Subscribe { topics } | ClientMessage::Unsubscribe { topics } => {
+            match topics.iter().map(|topic| topic.parse()).collect::<Result<Vec<Topic>, String>>() {
+                Ok(topics) => topics,
+                Err(e) => return WsMessage::Error(serde_json::json!({ "message": e })),
+            }
+        }
+        ClientMessage::Ping => return WsMessage::Pong,
+    };
+
+    match message {
+        ClientMessage::Subscribe { .. } => ws_broadcaster.subscribe(client_id, &topics),
+        _ => ws_broadcaster.unsubscribe(client_id, &topics),
+    }
+
+    WsMessage::Subscriptions(serde_json::json!({
+        "topics": ws_broadcaster.topics(client_id),
+    }))
+}
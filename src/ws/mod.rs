@@ -1,3 +1,7 @@
 pub mod handler;
+pub mod status;
+pub mod topic;
 
-pub use handler::{ws_handler, WsBroadcaster};
+pub use handler::{ws_handler, WsBroadcaster, WsMessage};
+pub use status::{Snapshot, StatusUpdate};
+pub use topic::{Topic, TopicEntity};
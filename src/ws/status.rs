@@ -0,0 +1,85 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
+
+use crate::db;
+use crate::error::Result;
+use crate::models::{Client, ClientStatus, Server, ServerStatus};
+use crate::time;
+
+/// A status transition of a server or client
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatusUpdate<S> {
+    pub id: i64,
+    pub status: S,
+    /// Control port of a server, or the public port assigned to a client
+    pub port: Option<i64>,
+    pub error_message: Option<String>,
+    pub timestamp: DateTime<Utc>,
+    /// Fields specific to the transition, such as `uptime_seconds`
+    #[serde(flatten)]
+    pub details: serde_json::Map<String, serde_json::Value>,
+}
+
+impl<S> StatusUpdate<S> {
+    pub fn new(id: i64, status: S, port: Option<i64>, error_message: Option<String>) -> Self {
+        Self {
+            id,
+            status,
+            port,
+            error_message,
+            timestamp: Utc::now(),
+            details: serde_json::Map::new(),
+        }
+    }
+
+    /// Add a transition-specific field
+    pub fn with(mut self, key: &str, value: impl Into<serde_json::Value>) -> Self {
+        self.details.insert(key.to_string(), value.into());
+        self
+    }
+}
+
+impl From<&Server> for StatusUpdate<ServerStatus> {
+    fn from(server: &Server) -> Self {
+        let port = (server.status == ServerStatus::Running).then_some(server.control_port);
+        Self {
+            timestamp: parse_timestamp(&server.updated_at),
+            ..Self::new(server.id, server.status.clone(), port, server.error_message.clone())
+        }
+        .with("name", server.name.clone())
+    }
+}
+
+impl From<&Client> for StatusUpdate<ClientStatus> {
+    fn from(client: &Client) -> Self {
+        Self {
+            timestamp: parse_timestamp(&client.updated_at),
+            ..Self::new(client.id, client.status.clone(), client.assigned_port, client.error_message.clone())
+        }
+        .with("name", client.name.clone())
+    }
+}
+
+/// Current status of every server and client, sent to new connections
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub servers: Vec<StatusUpdate<ServerStatus>>,
+    pub clients: Vec<StatusUpdate<ClientStatus>>,
+}
+
+impl Snapshot {
+    pub async fn load(pool: &SqlitePool) -> Result<Self> {
+        let servers = db::list_servers(pool).await?;
+        let clients = db::list_clients(pool).await?;
+
+        Ok(Self {
+            servers: servers.iter().map(StatusUpdate::from).collect(),
+            clients: clients.iter().map(StatusUpdate::from).collect(),
+        })
+    }
+}
+
+fn parse_timestamp(timestamp: &str) -> DateTime<Utc> {
+    time::parse(timestamp).unwrap_or_else(Utc::now)
+}
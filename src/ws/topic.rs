@@ -0,0 +1,93 @@
+//! Topics WebSocket clients subscribe to.
+
+use std::fmt;
+use std::str::FromStr;
+
+/// Kind of entity a topic is about
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TopicEntity {
+    Server,
+    Client,
+}
+
+impl TopicEntity {
+    fn as_str(&self) -> &'static str {
+        match self {
+            TopicEntity::Server => "server",
+            TopicEntity::Client => "client",
+        }
+    }
+}
+
+/// A stream of messages: `server:5`, `client:*`, `metrics` or `logs:client:5`.
+/// No id stands for every server or client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Topic {
+    /// Status changes and connection events
+    Entity(TopicEntity, Option<i64>),
+    /// Traffic samples of running tunnels
+    Metrics,
+    /// Log lines
+    Logs(TopicEntity, Option<i64>),
+}
+
+impl Topic {
+    /// Whether a subscription to this topic receives messages of `other`
+    pub fn covers(&self, other: &Topic) -> bool {
+        match (self, other) {
+            (Topic::Entity(kind, None), Topic::Entity(other_kind, _))
+            | (Topic::Logs(kind, None), Topic::Logs(other_kind, _)) => kind == other_kind,
+            _ => self == other,
+        }
+    }
+}
+
+impl fmt::Display for Topic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let entity = |f: &mut fmt::Formatter<'_>, kind: &TopicEntity, id: &Option<i64>| match id {
+            Some(id) => write!(f, "{}:{}", kind.as_str(), id),
+            None => write!(f, "{}:*", kind.as_str()),
+        };
+
+        match self {
+            Topic::Entity(kind, id) => entity(f, kind, id),
+            Topic::Metrics => f.write_str("metrics"),
+            Topic::Logs(kind, id) => {
+                f.write_str("logs:")?;
+                entity(f, kind, id)
+            }
+        }
+    }
+}
+
+impl FromStr for Topic {
+    type Err = String;
+
+    fn from_str(topic: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!(
+            "Invalid topic '{}', expected server:<id>, client:<id>, server:*, client:*, metrics, logs:server:<id> or logs:client:<id>",
+            topic
+        );
+        let entity = |entity: &str| -> Result<(TopicEntity, Option<i64>), String> {
+            let (kind, id) = entity.split_once(':').ok_or_else(invalid)?;
+            let kind = match kind {
+                "server" => TopicEntity::Server,
+                "client" => TopicEntity::Client,
+                _ => return Err(invalid()),
+            };
+            let id = match id {
+                "*" => None,
+                id => Some(id.parse().map_err(|_| invalid())?),
+            };
+            Ok((kind, id))
+        };
+
+        match topic {
+            "metrics" => Ok(Topic::Metrics),
+            _ => match topic.strip_prefix("logs:") {
+                Some(rest) => entity(rest).map(|(kind, id)| Topic::Logs(kind, id)),
+                None => entity(topic).map(|(kind, id)| Topic::Entity(kind, id)),
+            },
+        }
+    }
+}
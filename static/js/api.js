@@ -166,6 +166,24 @@ class API {
         });
     }
 
+    // Port reservations
+    async listReservations(serverId) {
+        return this.request(`/servers/${serverId}/reservations`);
+    }
+
+    async createReservation(serverId, data) {
+        return this.request(`/servers/${serverId}/reservations`, {
+            method: 'POST',
+            body: JSON.stringify(data),
+        });
+    }
+
+    async deleteReservation(serverId, id) {
+        return this.request(`/servers/${serverId}/reservations/${id}`, {
+            method: 'DELETE',
+        });
+    }
+
     // Clients
     async listClients() {
         return this.request('/clients');
@@ -207,6 +225,10 @@ class API {
         });
     }
 
+    async getClientLogs(id, since = 0) {
+        return this.request(`/clients/${id}/logs?since=${since}`);
+    }
+
     // System
     async getHealth() {
         return this.request('/system/health');
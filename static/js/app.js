@@ -205,6 +205,10 @@
         clientsUI.loadClients();
     });
 
+    wsClient.on('connection_event', (data) => {
+        console.log('Connection event:', data);
+    });
+
     // Load initial data
     serversUI.loadServers();
 })();
@@ -222,6 +226,16 @@ function closeServerModal() {
     document.getElementById('server-form').reset();
 }
 
+function closeReservationModal() {
+    document.getElementById('reservation-modal').classList.remove('show');
+    document.getElementById('reservation-form').reset();
+}
+
+function closeClientLogsModal() {
+    document.getElementById('client-logs-modal').classList.remove('show');
+    clientsUI.closeLogs();
+}
+
 function closeClientModal() {
     document.getElementById('client-modal').classList.remove('show');
     document.getElementById('client-form').reset();
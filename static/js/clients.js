@@ -57,10 +57,11 @@ window.clientsUI = {
                 <div class="item-actions">
                     ${client.status === 'stopped' ?
                         `<button class="btn-success" onclick="clientsUI.startClient(${client.id})">${getIcon('play')}<span data-i18n="clients.start">Start</span></button>` :
-                        client.status === 'connected' ?
+                        client.status === 'connected' || client.status === 'reconnecting' ?
                         `<button class="btn-danger" onclick="clientsUI.stopClient(${client.id})">${getIcon('stop')}<span data-i18n="clients.stop">Stop</span></button>` :
                         ''
                     }
+                    <button class="btn-secondary" onclick="clientsUI.showLogs(${client.id})">${getIcon('info')}<span data-i18n="clients.logs">Logs</span></button>
                     <button class="btn-secondary" onclick="clientsUI.showEditForm(${client.id})" ${client.status !== 'stopped' ? 'disabled' : ''}>${getIcon('edit')}<span data-i18n="common.edit">Edit</span></button>
                     <button class="btn-danger" onclick="clientsUI.deleteClient(${client.id})" ${client.status !== 'stopped' ? 'disabled' : ''}>${getIcon('trash')}<span data-i18n="common.delete">Delete</span></button>
                 </div>
@@ -104,6 +105,63 @@ window.clientsUI = {
         });
     },
 
+    async showLogs(id) {
+        const modal = document.getElementById('client-logs-modal');
+        const container = document.getElementById('client-logs');
+        container.textContent = '';
+        this.closeLogs();
+
+        let lastSeq = 0;
+        const append = (entry) => {
+            if (entry.seq <= lastSeq) {
+                return;
+            }
+            lastSeq = entry.seq;
+            const line = document.createElement('div');
+            line.className = `log-line log-${entry.level.toLowerCase()}`;
+            line.textContent = `${new Date(entry.timestamp).toLocaleTimeString()} ${entry.level.padEnd(5)} ${entry.message}`;
+            const atBottom = container.scrollTop + container.clientHeight >= container.scrollHeight - 4;
+            container.appendChild(line);
+            if (atBottom) {
+                container.scrollTop = container.scrollHeight;
+            }
+        };
+
+        // Lines streamed while the history loads are deduplicated by seq
+        const topic = `logs:client:${id}`;
+        this.logListener = (line) => {
+            if (line.entity_type === 'client' && line.entity_id === id) {
+                append(line);
+            }
+        };
+        this.logTopic = topic;
+        wsClient.on('log', this.logListener);
+        wsClient.subscribe(topic);
+        modal.classList.add('show');
+
+        try {
+            const entries = await api.getClientLogs(id);
+            container.textContent = '';
+            lastSeq = 0;
+            entries.forEach(append);
+            if (entries.length === 0) {
+                container.innerHTML = '<p class="loading" data-i18n="clients.noLogs">No log lines yet</p>';
+                i18n.applyTranslations();
+            }
+        } catch (e) {
+            toast.error(i18n.t('clients.loadError') + ': ' + e.message);
+        }
+    },
+
+    closeLogs() {
+        if (this.logTopic) {
+            wsClient.unsubscribe(this.logTopic);
+            wsClient.off('log', this.logListener);
+            this.logTopic = null;
+            this.logListener = null;
+        }
+    },
+
     showCreateForm() {
         this.showForm(null);
     },
@@ -145,7 +203,12 @@ window.clientsUI = {
             document.getElementById('client-remote-server').value = client.remote_server;
             document.getElementById('client-remote-port').value = client.remote_port;
             document.getElementById('client-secret').value = client.secret || '';
+            document.getElementById('client-upload-limit').value = Math.round((client.upload_limit || 0) / 1024);
+            document.getElementById('client-download-limit').value = Math.round((client.download_limit || 0) / 1024);
             document.getElementById('client-auto-start').checked = client.auto_start || false;
+            document.getElementById('client-health-check-enabled').checked = client.health_check_enabled || false;
+            document.getElementById('client-health-check-interval').value = client.health_check_interval || 30;
+            document.getElementById('client-health-check-path').value = client.health_check_path || '';
 
             // Set webhook fields
             const webhookEnabled = !!client.webhook_url;
@@ -153,6 +216,7 @@ window.clientsUI = {
             document.getElementById('client-webhook-url').value = client.webhook_url || '';
             document.getElementById('client-webhook-format').value = client.webhook_format || 'json';
             document.getElementById('client-webhook-template').value = client.webhook_template || '';
+            document.getElementById('client-webhook-secret').value = client.webhook_secret || '';
 
             // Update webhook UI state
             toggleWebhookSection();
@@ -194,10 +258,16 @@ window.clientsUI = {
                 remote_server: document.getElementById('client-remote-server').value,
                 remote_port: parseInt(document.getElementById('client-remote-port').value),
                 secret: document.getElementById('client-secret').value || null,
+                upload_limit: (parseInt(document.getElementById('client-upload-limit').value) || 0) * 1024,
+                download_limit: (parseInt(document.getElementById('client-download-limit').value) || 0) * 1024,
                 auto_start: document.getElementById('client-auto-start').checked,
+                health_check_enabled: document.getElementById('client-health-check-enabled').checked,
+                health_check_interval: parseInt(document.getElementById('client-health-check-interval').value) || 30,
+                health_check_path: document.getElementById('client-health-check-path').value,
                 webhook_url: webhookUrl,
                 webhook_format: document.getElementById('client-webhook-format').value,
                 webhook_template: document.getElementById('client-webhook-template').value || null,
+                webhook_secret: document.getElementById('client-webhook-secret').value,
             };
 
             try {
@@ -277,7 +347,9 @@ function toggleWebhookTemplate() {
         templateGroup.classList.add('hidden');
         templateGroup.classList.remove('visible');
         if (formatInfo) {
-            formatInfo.querySelector('span').textContent = i18n.t('clients.webhookFormatInfo') || 'Standard JSON sends structured data ideal for integrations';
+            formatInfo.querySelector('span').textContent = format === 'json'
+                ? i18n.t('clients.webhookFormatInfo') || 'Standard JSON sends structured data ideal for integrations'
+                : i18n.t('clients.webhookFormatInfoPlatform') || 'Posts a ready-made message. Telegram URLs need ?chat_id= in the query';
         }
     }
 }
@@ -25,6 +25,14 @@ window.serversUI = {
         container.innerHTML = servers.map(server => {
             const description = server.description || `<span data-i18n="common.noDescription">No description</span>`;
             const authInfo = server.secret ? `<br><strong data-i18n="servers.auth">Auth</strong>: <span data-i18n="servers.authEnabled">Enabled</span>` : '';
+            const accessInfo = server.allow_cidrs || server.deny_cidrs ?
+                `<br><strong data-i18n="servers.access">Sources</strong>: ${[
+                    server.allow_cidrs ? `+ ${server.allow_cidrs}` : '',
+                    server.deny_cidrs ? `- ${server.deny_cidrs}` : '',
+                ].filter(Boolean).join(' ')}` : '';
+            const reservations = server.reservations || [];
+            const reservationInfo = reservations.length > 0 ?
+                `<br><strong data-i18n="servers.reservedPorts">Reserved Ports</strong>: ${reservations.map(r => `${r.port} (${r.owner})`).join(', ')}` : '';
 
             return `
             <div class="item-card">
@@ -35,10 +43,12 @@ window.serversUI = {
                 <div class="item-details">
                     ${description}
                     <br>
-                    <strong data-i18n="servers.address">Address</strong>: ${server.bind_addr}
+                    <strong data-i18n="servers.address">Address</strong>: ${server.bind_addr}:${server.control_port}
                     <br>
                     <strong data-i18n="servers.portRange">Port Range</strong>: ${server.port_range_start}-${server.port_range_end}
                     ${authInfo}
+                    ${accessInfo}
+                    ${reservationInfo}
                 </div>
                 <div class="item-actions">
                     ${server.status === 'stopped' ?
@@ -47,6 +57,7 @@ window.serversUI = {
                         `<button class="btn-danger" onclick="serversUI.stopServer(${server.id})">${getIcon('stop')}<span data-i18n="servers.stop">Stop</span></button>` :
                         ''
                     }
+                    <button class="btn-secondary" onclick="serversUI.showReservations(${server.id})">${getIcon('server')}<span data-i18n="servers.reservations">Ports</span></button>
                     <button class="btn-secondary" onclick="serversUI.showEditForm(${server.id})" ${server.status !== 'stopped' ? 'disabled' : ''}>${getIcon('edit')}<span data-i18n="common.edit">Edit</span></button>
                     <button class="btn-danger" onclick="serversUI.deleteServer(${server.id})" ${server.status !== 'stopped' ? 'disabled' : ''}>${getIcon('trash')}<span data-i18n="common.delete">Delete</span></button>
                 </div>
@@ -98,6 +109,15 @@ window.serversUI = {
             document.getElementById('server-bind-tunnels').value = server.bind_tunnels || server.bind_addr;
             document.getElementById('server-port-start').value = server.port_range_start;
             document.getElementById('server-port-end').value = server.port_range_end;
+            document.getElementById('server-control-port').value = server.control_port;
+            document.getElementById('server-allow-cidrs').value = server.allow_cidrs || '';
+            document.getElementById('server-deny-cidrs').value = server.deny_cidrs || '';
+            document.getElementById('server-upload-limit').value = Math.round((server.upload_limit || 0) / 1024);
+            document.getElementById('server-download-limit').value = Math.round((server.download_limit || 0) / 1024);
+            document.getElementById('server-webhook-url').value = server.webhook_url || '';
+            document.getElementById('server-webhook-format').value = server.webhook_format || 'json';
+            document.getElementById('server-webhook-template').value = server.webhook_template || '';
+            document.getElementById('server-webhook-secret').value = server.webhook_secret || '';
             document.getElementById('server-secret').value = server.secret || '';
             document.getElementById('server-auto-start').checked = server.auto_start || false;
         }
@@ -118,6 +138,15 @@ window.serversUI = {
                 bind_tunnels: document.getElementById('server-bind-tunnels').value,
                 port_range_start: parseInt(document.getElementById('server-port-start').value),
                 port_range_end: parseInt(document.getElementById('server-port-end').value),
+                control_port: parseInt(document.getElementById('server-control-port').value),
+                allow_cidrs: document.getElementById('server-allow-cidrs').value.trim(),
+                deny_cidrs: document.getElementById('server-deny-cidrs').value.trim(),
+                upload_limit: (parseInt(document.getElementById('server-upload-limit').value) || 0) * 1024,
+                download_limit: (parseInt(document.getElementById('server-download-limit').value) || 0) * 1024,
+                webhook_url: document.getElementById('server-webhook-url').value.trim(),
+                webhook_format: document.getElementById('server-webhook-format').value,
+                webhook_template: document.getElementById('server-webhook-template').value,
+                webhook_secret: document.getElementById('server-webhook-secret').value,
                 secret: document.getElementById('server-secret').value || null,
                 auto_start: document.getElementById('server-auto-start').checked,
             };
@@ -136,6 +165,76 @@ window.serversUI = {
         });
     },
 
+    async showReservations(id) {
+        const modal = document.getElementById('reservation-modal');
+        const form = document.getElementById('reservation-form');
+
+        try {
+            await this.renderReservations(id);
+        } catch (e) {
+            toast.error(i18n.t('servers.loadError') + ': ' + e.message);
+            return;
+        }
+
+        form.reset();
+        modal.classList.add('show');
+
+        // Remove old event listener and add new one
+        const newForm = form.cloneNode(true);
+        form.parentNode.replaceChild(newForm, form);
+
+        newForm.addEventListener('submit', async (e) => {
+            e.preventDefault();
+
+            const data = {
+                port: parseInt(document.getElementById('reservation-port').value),
+                owner: document.getElementById('reservation-owner').value.trim(),
+                description: document.getElementById('reservation-description').value || null,
+                allow_cidrs: document.getElementById('reservation-allow-cidrs').value.trim() || null,
+                deny_cidrs: document.getElementById('reservation-deny-cidrs').value.trim() || null,
+            };
+
+            try {
+                await api.createReservation(id, data);
+                newForm.reset();
+                await this.renderReservations(id);
+                await this.loadServers();
+                toast.success(i18n.t('servers.reserveSuccess'));
+            } catch (e) {
+                toast.error(i18n.t('servers.reserveError') + ': ' + e.message);
+            }
+        });
+    },
+
+    async renderReservations(serverId) {
+        const container = document.getElementById('reservation-list');
+        const reservations = await api.listReservations(serverId);
+
+        if (reservations.length === 0) {
+            container.innerHTML = `<p class="loading" data-i18n="servers.noReservations">No ports reserved on this server.</p>`;
+        } else {
+            container.innerHTML = reservations.map(r => `
+                <div class="reservation-item">
+                    <span><strong>${r.port}</strong> &rarr; ${r.owner}${r.description ? ` <small>${r.description}</small>` : ''}${r.allow_cidrs ? ` <small>+ ${r.allow_cidrs}</small>` : ''}${r.deny_cidrs ? ` <small>- ${r.deny_cidrs}</small>` : ''}</span>
+                    <button class="btn-danger" onclick="serversUI.deleteReservation(${serverId}, ${r.id})">${getIcon('trash')}</button>
+                </div>
+            `).join('');
+        }
+
+        i18n.applyTranslations();
+    },
+
+    async deleteReservation(serverId, id) {
+        try {
+            await api.deleteReservation(serverId, id);
+            await this.renderReservations(serverId);
+            await this.loadServers();
+            toast.success(i18n.t('servers.releaseSuccess'));
+        } catch (e) {
+            toast.error(i18n.t('servers.releaseError') + ': ' + e.message);
+        }
+    },
+
     async createServer(data) {
         try {
             await api.createServer(data);
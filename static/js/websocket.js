@@ -5,6 +5,8 @@ class WSClient {
         this.reconnectInterval = 5000;
         this.reconnectTimer = null;
         this.listeners = new Map();
+        // Topics beyond the defaults, restored after reconnecting
+        this.topics = new Set();
         this.token = localStorage.getItem('token');
     }
 
@@ -24,6 +26,9 @@ class WSClient {
         this.ws.onopen = () => {
             console.log('WebSocket connected');
             this.updateStatus(true);
+            if (this.topics.size > 0) {
+                this.send({ type: 'subscribe', topics: [...this.topics] });
+            }
             if (this.reconnectTimer) {
                 clearTimeout(this.reconnectTimer);
                 this.reconnectTimer = null;
@@ -80,6 +85,28 @@ class WSClient {
         }
     }
 
+    send(message) {
+        if (this.ws && this.ws.readyState === WebSocket.OPEN) {
+            this.ws.send(JSON.stringify(message));
+        }
+    }
+
+    subscribe(topic) {
+        this.topics.add(topic);
+        this.send({ type: 'subscribe', topics: [topic] });
+    }
+
+    unsubscribe(topic) {
+        this.topics.delete(topic);
+        this.send({ type: 'unsubscribe', topics: [topic] });
+    }
+
+    off(type, callback) {
+        if (this.listeners.has(type)) {
+            this.listeners.set(type, this.listeners.get(type).filter(cb => cb !== callback));
+        }
+    }
+
     on(type, callback) {
         if (!this.listeners.has(type)) {
             this.listeners.set(type, []);